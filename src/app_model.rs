@@ -1,6 +1,10 @@
+use crate::db::search_index::SearchIndex;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 
+/// How many index candidates are fuzzy-scored when the search index is used
+const MAX_INDEX_CANDIDATES: usize = 200;
+
 /// Type of application entry
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppEntryType {
@@ -17,6 +21,36 @@ pub struct AppEntry {
     pub icon_index: i32,
     pub usage_count: i32,
     pub entry_type: AppEntryType,
+    /// File the icon was taken from, when the shortcut names one
+    pub icon_path: Option<String>,
+    /// The .lnk/.url file this entry was read from
+    pub shortcut_path: Option<String>,
+    /// Modification time of `shortcut_path` when it was scanned (seconds since the epoch)
+    pub source_mtime: Option<i64>,
+    /// The shortcut's target file was missing at the last scan (e.g. the app was
+    /// uninstalled but its shortcut left behind)
+    pub broken: bool,
+    /// Start Menu folder the entry was found in, relative to the scanned root
+    /// (e.g. `Visual Studio 2022\Tools`); `None` at the top level
+    pub folder: Option<String>,
+    /// The shortcut's comment; searched along with the name
+    pub description: Option<String>,
+    /// More words to find the entry by, such as the description and product
+    /// name in the target's version resource
+    pub keywords: Option<String>,
+    /// Directory to start the target in ("Start in"), variables expanded
+    pub working_dir: Option<String>,
+    /// Index of the icon inside `icon_path`
+    pub icon_resource: i32,
+    /// How to show the target's window (an `SW_*` value); `None` for a normal window
+    pub show_cmd: Option<i32>,
+    /// The shortcut's key: virtual key in the low byte, `HOTKEYF_*` modifiers in the high byte
+    pub hotkey: Option<u16>,
+    /// Command that removes the program the entry belongs to, from its
+    /// Uninstall registry entry
+    pub uninstall: Option<String>,
+    /// Name of the program `uninstall` removes, as Apps & Features lists it
+    pub uninstall_name: Option<String>,
 }
 
 impl AppEntry {
@@ -29,6 +63,19 @@ impl AppEntry {
             icon_index,
             usage_count,
             entry_type: AppEntryType::Application,
+            icon_path: None,
+            shortcut_path: None,
+            source_mtime: None,
+            broken: false,
+            folder: None,
+            description: None,
+            keywords: None,
+            working_dir: None,
+            icon_resource: 0,
+            show_cmd: None,
+            hotkey: None,
+            uninstall: None,
+            uninstall_name: None,
         }
     }
 
@@ -47,6 +94,19 @@ impl AppEntry {
             icon_index,
             usage_count,
             entry_type: AppEntryType::Application,
+            icon_path: None,
+            shortcut_path: None,
+            source_mtime: None,
+            broken: false,
+            folder: None,
+            description: None,
+            keywords: None,
+            working_dir: None,
+            icon_resource: 0,
+            show_cmd: None,
+            hotkey: None,
+            uninstall: None,
+            uninstall_name: None,
         }
     }
 
@@ -59,14 +119,204 @@ impl AppEntry {
             icon_index,
             usage_count: 0, // Settings items don't track usage
             entry_type: AppEntryType::Settings,
+            icon_path: None,
+            shortcut_path: None,
+            source_mtime: None,
+            broken: false,
+            folder: None,
+            description: None,
+            keywords: None,
+            working_dir: None,
+            icon_resource: 0,
+            show_cmd: None,
+            hotkey: None,
+            uninstall: None,
+            uninstall_name: None,
         }
     }
 }
 
+impl AppEntry {
+    /// Components of `folder`, outermost first
+    pub fn folder_path(&self) -> Vec<&str> {
+        self.folder
+            .as_deref()
+            .map(|folder| folder.split('\\').filter(|c| !c.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Identity of the entry across scans: the file it was read from, or its
+    /// target when it doesn't come from a file (e.g. Settings pages)
+    pub fn key(&self) -> &str {
+        self.shortcut_path.as_deref().unwrap_or(&self.parse_name)
+    }
+
+    /// The icon to extract from `icon_path` when the file's own icon isn't the
+    /// one meant: an icon other than the first, or one inside a library
+    pub fn icon_location(&self) -> Option<(&str, i32)> {
+        let path = self.icon_path.as_deref()?;
+        let lower = path.to_lowercase();
+        let own_icon = lower.ends_with(".exe") || lower.ends_with(".ico");
+        (self.icon_resource != 0 || !own_icon).then_some((path, self.icon_resource))
+    }
+
+    /// The shortcut's key as Windows shows it, e.g. `Ctrl+Alt+N`
+    pub fn hotkey_label(&self) -> Option<String> {
+        const HOTKEYF_SHIFT: u16 = 0x01;
+        const HOTKEYF_CONTROL: u16 = 0x02;
+        const HOTKEYF_ALT: u16 = 0x04;
+
+        let hotkey = self.hotkey?;
+        let (key, modifiers) = (hotkey & 0xFF, hotkey >> 8);
+        let key = match key {
+            0x30..=0x39 | 0x41..=0x5A => char::from(key as u8).to_string(),
+            0x60..=0x69 => format!("Num {}", key - 0x60),
+            0x70..=0x87 => format!("F{}", key - 0x6F),
+            0x21 => "Page Up".to_string(),
+            0x22 => "Page Down".to_string(),
+            0x23 => "End".to_string(),
+            0x24 => "Home".to_string(),
+            0x2D => "Insert".to_string(),
+            0x2E => "Delete".to_string(),
+            _ => format!("0x{:02X}", key),
+        };
+
+        let mut parts = Vec::new();
+        for (flag, label) in [
+            (HOTKEYF_CONTROL, "Ctrl"),
+            (HOTKEYF_SHIFT, "Shift"),
+            (HOTKEYF_ALT, "Alt"),
+        ] {
+            if modifiers & flag != 0 {
+                parts.push(label.to_string());
+            }
+        }
+        parts.push(key);
+        Some(parts.join("+"))
+    }
+
+    /// The shortcut's key as `SHELLEXECUTEINFOW::dwHotKey` takes it: virtual
+    /// key in the low word, `HOTKEYF_*` modifiers in the high word
+    pub fn shell_hotkey(&self) -> Option<u32> {
+        let hotkey = self.hotkey.filter(|&hotkey| hotkey & 0xFF != 0)?;
+        Some(u32::from(hotkey & 0xFF) | u32::from(hotkey >> 8) << 16)
+    }
+}
+
+/// Why `AppManager::add_app` turned an entry away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateReason {
+    /// Another entry opens the same target
+    SameTarget,
+    /// Another entry has the same name, ignoring case
+    SameName,
+}
+
+/// An entry already in the list that a new one duplicates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Duplicate {
+    /// Position of the existing entry in `AppManager::apps`
+    pub index: usize,
+    pub reason: DuplicateReason,
+}
+
+/// What changed between two scans, by entry key (see `AppEntry::key`)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScanDiff {
+    pub added: Vec<AppEntry>,
+    /// Keys of entries that are gone
+    pub removed: Vec<String>,
+    /// New versions of entries whose name, target, arguments or icon changed
+    pub changed: Vec<AppEntry>,
+}
+
+impl ScanDiff {
+    /// Compare the entries shown before a scan with the ones it produced.
+    /// Usage counts are not compared; they are tracked separately.
+    pub fn between(old: &[AppEntry], new: &[AppEntry]) -> Self {
+        let old_by_key = old
+            .iter()
+            .map(|app| (app.key(), app))
+            .collect::<std::collections::HashMap<_, _>>();
+        let new_keys = new
+            .iter()
+            .map(|app| app.key())
+            .collect::<std::collections::HashSet<_>>();
+
+        let mut diff = ScanDiff::default();
+        for app in new {
+            match old_by_key.get(app.key()) {
+                None => diff.added.push(app.clone()),
+                Some(previous) => {
+                    let mut unchanged = (*previous).clone();
+                    unchanged.usage_count = app.usage_count;
+                    if unchanged != *app {
+                        diff.changed.push(app.clone());
+                    }
+                }
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|app| !new_keys.contains(app.key()))
+            .map(|app| app.key().to_string())
+            .collect();
+        diff
+    }
+
+    /// True when the scan found nothing new
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Apply the changes to `apps`. Entries that keep their target keep their usage count.
+    pub fn apply_to(&self, apps: &mut Vec<AppEntry>) {
+        let removed = self
+            .removed
+            .iter()
+            .map(|key| key.as_str())
+            .collect::<std::collections::HashSet<_>>();
+        apps.retain(|app| !removed.contains(app.key()));
+
+        for changed in &self.changed {
+            if let Some(app) = apps.iter_mut().find(|app| app.key() == changed.key()) {
+                let usage_count = if app.parse_name == changed.parse_name {
+                    app.usage_count
+                } else {
+                    changed.usage_count
+                };
+                *app = changed.clone();
+                app.usage_count = usage_count;
+            }
+        }
+
+        apps.extend(self.added.iter().cloned());
+    }
+}
+
+/// A row of the list: an app, or a folder while browsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListRow<'a> {
+    Folder(&'a str),
+    /// Position in `AppManager::apps`
+    App(usize),
+}
+
 /// Manages a collection of applications
 pub struct AppManager {
     apps: Vec<AppEntry>,
     filtered_indices: Vec<usize>,
+    search_index: Option<SearchIndex>,
+    /// Use the search index only from this many apps on
+    index_min_apps: usize,
+    /// The apps changed since the search index was last rebuilt
+    index_stale: bool,
+    /// Search term of the last `filter` call
+    search: String,
+    /// Folder being browsed, outermost component first; `None` when searching
+    browse: Option<Vec<String>>,
+    /// Folders inside the browsed one, listed before its apps
+    subfolders: Vec<String>,
 }
 
 impl AppManager {
@@ -75,36 +325,63 @@ impl AppManager {
         Self {
             apps: Vec::new(),
             filtered_indices: Vec::new(),
+            search_index: None,
+            index_min_apps: 0,
+            index_stale: true,
+            search: String::new(),
+            browse: None,
+            subfolders: Vec::new(),
         }
     }
 
-    /// Add an application to the manager (with deduplication)
-    pub fn add_app(&mut self, app: AppEntry) {
+    /// Prefilter searches through a full-text index once there are at least
+    /// `min_apps` apps; smaller lists are cheaper to scan directly
+    pub fn enable_search_index(&mut self, min_apps: usize) -> rusqlite::Result<()> {
+        self.search_index = Some(SearchIndex::new()?);
+        self.index_min_apps = min_apps;
+        self.index_stale = true;
+        Ok(())
+    }
+
+    /// Add an application to the manager (with deduplication).
+    ///
+    /// Returns the entry already in the list that `app` duplicates, if any, in
+    /// which case `app` is not added.
+    pub fn add_app(&mut self, app: AppEntry) -> Option<Duplicate> {
         // Check for duplicates by parse_name (exact match)
-        if self
+        if let Some(index) = self
             .apps
             .iter()
-            .any(|existing| existing.parse_name == app.parse_name)
+            .position(|existing| existing.parse_name == app.parse_name)
         {
-            return;
+            return Some(Duplicate {
+                index,
+                reason: DuplicateReason::SameTarget,
+            });
         }
 
         // Also check for duplicates by name (case-insensitive)
         let app_name_lower = app.name.to_lowercase();
-        if self
+        if let Some(index) = self
             .apps
             .iter()
-            .any(|existing| existing.name.to_lowercase() == app_name_lower)
+            .position(|existing| existing.name.to_lowercase() == app_name_lower)
         {
-            return;
+            return Some(Duplicate {
+                index,
+                reason: DuplicateReason::SameName,
+            });
         }
 
         self.apps.push(app);
+        self.index_stale = true;
+        None
     }
 
     /// Add an application without deduplication (for loading from cache)
     pub fn add_app_unchecked(&mut self, app: AppEntry) {
         self.apps.push(app);
+        self.index_stale = true;
     }
 
     /// Set the apps list directly (replaces all apps)
@@ -112,12 +389,15 @@ impl AppManager {
     pub fn set_apps(&mut self, apps: Vec<AppEntry>) {
         self.apps = apps;
         self.filtered_indices.clear();
+        self.index_stale = true;
     }
 
     /// Clear all applications
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.apps.clear();
         self.filtered_indices.clear();
+        self.index_stale = true;
     }
 
     /// Get all applications
@@ -126,22 +406,29 @@ impl AppManager {
     }
 
     /// Get filtered indices
+    #[allow(dead_code)]
     pub fn filtered_indices(&self) -> &[usize] {
         &self.filtered_indices
     }
 
     /// Sort applications by usage count (descending) and name (ascending)
     pub fn sort_by_usage(&mut self) {
+        // Broken shortcuts go last, however often they were used
         self.apps.sort_by(|a, b| {
-            b.usage_count
-                .cmp(&a.usage_count)
+            a.broken
+                .cmp(&b.broken)
+                .then_with(|| b.usage_count.cmp(&a.usage_count))
                 .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
         });
+        self.index_stale = true;
     }
 
-    /// Filter applications by search term using fuzzy matching
+    /// Filter applications by search term using fuzzy matching; ends browse mode
     pub fn filter(&mut self, search: &str) {
         self.filtered_indices.clear();
+        self.search = search.to_string();
+        self.browse = None;
+        self.subfolders.clear();
 
         if search.is_empty() {
             // If search is empty, show all apps
@@ -152,22 +439,174 @@ impl AppManager {
         }
 
         let matcher = SkimMatcherV2::default();
+        let search_lower = search.to_lowercase();
         let mut matches: Vec<(usize, i64)> = Vec::new();
 
-        for (i, app) in self.apps.iter().enumerate() {
-            // Try fuzzy matching on the app name
-            if let Some(score) = matcher.fuzzy_match(&app.name, search) {
+        // Score only the index candidates when there are any; otherwise (no index,
+        // a too-short term or an abbreviation that is not a substring) score everything
+        let candidates = self.index_candidates(search).filter(|c| !c.is_empty());
+        let candidates = candidates.unwrap_or_else(|| (0..self.apps.len()).collect());
+
+        for i in candidates {
+            let Some(app) = self.apps.get(i) else {
+                continue;
+            };
+            // Try fuzzy matching on the app name, then on its folder and name
+            // together, so "vs dev" finds "Visual Studio 2022 > Developer ..."
+            let in_folder = app.folder.as_ref().and_then(|folder| {
+                matcher.fuzzy_match(&format!("{} {}", folder, app.name), search)
+            });
+            // Descriptions and keywords are long enough for almost any query to
+            // match loosely, so they only count when they contain the search as typed
+            let in_text = |text: &Option<String>| {
+                text.as_ref()
+                    .filter(|text| text.to_lowercase().contains(&search_lower))
+                    .and_then(|text| matcher.fuzzy_match(text, search))
+            };
+            if let Some(score) = matcher
+                .fuzzy_match(&app.name, search)
+                .max(in_folder)
+                .max(in_text(&app.description))
+                .max(in_text(&app.keywords))
+            {
                 matches.push((i, score));
             }
         }
 
-        // Sort by score (highest first)
-        matches.sort_by(|a, b| b.1.cmp(&a.1));
+        // Sort by score (highest first), broken shortcuts after every working match
+        matches.sort_by_key(|&(idx, score)| (self.apps[idx].broken, std::cmp::Reverse(score)));
 
         // Store the indices of matched apps
         self.filtered_indices = matches.iter().map(|(idx, _)| *idx).collect();
     }
 
+    /// Candidates from the search index, or `None` when it can't narrow the search
+    fn index_candidates(&mut self, search: &str) -> Option<Vec<usize>> {
+        if self.apps.len() < self.index_min_apps {
+            return None;
+        }
+        let index = self.search_index.as_mut()?;
+
+        if self.index_stale {
+            index.rebuild(&self.apps).ok()?;
+            self.index_stale = false;
+        }
+
+        index
+            .candidates(search, MAX_INDEX_CANDIDATES)
+            .ok()
+            .flatten()
+    }
+
+    /// Apply a scan's changes in place: entries that keep their target keep
+    /// their usage count, and the list is re-filtered with the current search
+    pub fn apply_diff(&mut self, diff: ScanDiff) {
+        if diff.is_empty() {
+            return;
+        }
+
+        let ScanDiff {
+            added,
+            removed,
+            changed,
+        } = diff;
+        ScanDiff {
+            added: Vec::new(),
+            removed,
+            changed,
+        }
+        .apply_to(&mut self.apps);
+        // New entries go through the same deduplication as a full scan
+        for app in added {
+            self.add_app(app);
+        }
+
+        self.sort_by_usage();
+        match self.browse.take() {
+            Some(folder) => self.browse(folder),
+            None => {
+                let search = std::mem::take(&mut self.search);
+                self.filter(&search);
+            }
+        }
+    }
+
+    /// Row of the entry with `key` (see `AppEntry::key`), counting the folder
+    /// rows shown while browsing
+    pub fn filtered_position(&self, key: &str) -> Option<usize> {
+        self.filtered_indices
+            .iter()
+            .position(|&app_idx| self.apps.get(app_idx).is_some_and(|app| app.key() == key))
+            .map(|position| self.subfolders.len() + position)
+    }
+
+    /// Show the contents of `folder` (outermost component first; empty for the
+    /// top level) instead of search results: its subfolders, then its apps
+    pub fn browse(&mut self, folder: Vec<String>) {
+        let depth = folder.len();
+        let mut subfolders = Vec::new();
+        self.filtered_indices.clear();
+
+        for (i, app) in self.apps.iter().enumerate() {
+            let path = app.folder_path();
+            let inside = path.len() >= depth
+                && path
+                    .iter()
+                    .zip(&folder)
+                    .all(|(a, b)| a.eq_ignore_ascii_case(b));
+            if !inside {
+                continue;
+            }
+            match path.get(depth) {
+                None => self.filtered_indices.push(i),
+                Some(child) => {
+                    if !subfolders
+                        .iter()
+                        .any(|known: &String| known.eq_ignore_ascii_case(child))
+                    {
+                        subfolders.push(child.to_string());
+                    }
+                }
+            }
+        }
+
+        subfolders.sort_by_key(|name| name.to_lowercase());
+        self.subfolders = subfolders;
+        self.search.clear();
+        self.browse = Some(folder);
+    }
+
+    /// The folder being browsed, or `None` when showing search results
+    pub fn browsed_folder(&self) -> Option<&[String]> {
+        self.browse.as_deref()
+    }
+
+    /// The app shown at `row`, if that row is an app
+    pub fn app_at_row(&self, row: usize) -> Option<&AppEntry> {
+        let app_idx = self.filtered_indices.get(self.app_position(row)?)?;
+        self.apps.get(*app_idx)
+    }
+
+    /// Position of `row` among the app rows, leaving out the folder rows shown
+    /// while browsing; `None` for a folder row
+    pub fn app_position(&self, row: usize) -> Option<usize> {
+        row.checked_sub(self.subfolders.len())
+    }
+
+    /// The folder shown at `row`, if that row is a folder
+    pub fn folder_at_row(&self, row: usize) -> Option<&str> {
+        self.subfolders.get(row).map(String::as_str)
+    }
+
+    /// Rows to show: the subfolders of the browsed folder, then the filtered apps
+    pub fn rows(&self) -> Vec<ListRow<'_>> {
+        self.subfolders
+            .iter()
+            .map(|name| ListRow::Folder(name))
+            .chain(self.filtered_indices.iter().map(|&i| ListRow::App(i)))
+            .collect()
+    }
+
     /// Get an application by its index in the filtered list
     #[allow(dead_code)]
     pub fn get_filtered_app(&self, filtered_index: usize) -> Option<&AppEntry> {
@@ -225,6 +664,27 @@ mod tests {
         assert_eq!(settings.entry_type, AppEntryType::Settings);
     }
 
+    #[test]
+    fn test_icon_location() {
+        let mut app = AppEntry::new("Tool".to_string(), "C:\\Tool\\tool.exe".to_string(), 0, 0);
+        assert_eq!(app.icon_location(), None);
+
+        // The file's default icon is the right one
+        app.icon_path = Some("C:\\Tool\\tool.exe".to_string());
+        assert_eq!(app.icon_location(), None);
+        app.icon_path = Some("C:\\Tool\\tool.ICO".to_string());
+        assert_eq!(app.icon_location(), None);
+
+        app.icon_resource = 2;
+        assert_eq!(app.icon_location(), Some(("C:\\Tool\\tool.ICO", 2)));
+        app.icon_path = Some("C:\\Windows\\System32\\imageres.dll".to_string());
+        app.icon_resource = 0;
+        assert_eq!(
+            app.icon_location(),
+            Some(("C:\\Windows\\System32\\imageres.dll", 0))
+        );
+    }
+
     #[test]
     fn test_app_manager_add_and_get() {
         let mut manager = AppManager::new();
@@ -253,6 +713,177 @@ mod tests {
         assert_eq!(manager.apps()[2].name, "App3");
     }
 
+    fn in_folder(name: &str, folder: &str) -> AppEntry {
+        let mut app = AppEntry::new(name.to_string(), format!("{}.exe", name), 0, 0);
+        app.folder = (!folder.is_empty()).then(|| folder.to_string());
+        app
+    }
+
+    fn start_menu() -> AppManager {
+        let mut manager = AppManager::new();
+        for (name, folder) in [
+            ("Paint", ""),
+            (
+                "Developer Command Prompt for VS 2022",
+                "Visual Studio 2022\\Visual Studio Tools",
+            ),
+            ("Visual Studio 2022", "Visual Studio 2022"),
+            ("Blend for Visual Studio 2022", "Visual Studio 2022"),
+            ("Notepad", "Accessories"),
+        ] {
+            manager.add_app(in_folder(name, folder));
+        }
+        manager
+    }
+
+    fn row_names(manager: &AppManager) -> Vec<String> {
+        manager
+            .rows()
+            .iter()
+            .map(|row| match row {
+                ListRow::Folder(name) => format!("[{}]", name),
+                ListRow::App(i) => manager.apps()[*i].name.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_search_matches_description() {
+        let mut manager = start_menu();
+        let mut editor = in_folder("Notepad++", "");
+        editor.description = Some("Source code editor".to_string());
+        manager.add_app(editor);
+
+        manager.filter("code editor");
+        assert_eq!(row_names(&manager), vec!["Notepad++"]);
+
+        // Scattered letters in a description are not a match
+        manager.filter("sce");
+        assert!(!row_names(&manager).contains(&"Notepad++".to_string()));
+    }
+
+    #[test]
+    fn test_search_matches_keywords() {
+        let mut manager = start_menu();
+        let mut shell = in_folder("pwsh", "");
+        shell.keywords = Some("PowerShell 7, Microsoft Corporation".to_string());
+        manager.add_app(shell);
+
+        manager.filter("powershell");
+        assert_eq!(row_names(&manager), vec!["pwsh"]);
+    }
+
+    #[test]
+    fn test_hotkey_label() {
+        let mut app = AppEntry::new("A".to_string(), "a".to_string(), 0, 0);
+        assert_eq!(app.hotkey_label(), None);
+
+        app.hotkey = Some(0x0600 | u16::from(b'N'));
+        assert_eq!(app.hotkey_label().as_deref(), Some("Ctrl+Alt+N"));
+        app.hotkey = Some(0x0172);
+        assert_eq!(app.hotkey_label().as_deref(), Some("Shift+F3"));
+    }
+
+    #[test]
+    fn test_shell_hotkey() {
+        let mut app = AppEntry::new("A".to_string(), "a".to_string(), 0, 0);
+        assert_eq!(app.shell_hotkey(), None);
+
+        app.hotkey = Some(0x0600 | u16::from(b'N'));
+        assert_eq!(app.shell_hotkey(), Some(0x0006_004E));
+        // Modifiers without a key are no hotkey
+        app.hotkey = Some(0x0200);
+        assert_eq!(app.shell_hotkey(), None);
+    }
+
+    #[test]
+    fn test_search_matches_folder() {
+        let mut manager = start_menu();
+        manager.filter("vs dev");
+        assert_eq!(
+            row_names(&manager).first().map(String::as_str),
+            Some("Developer Command Prompt for VS 2022")
+        );
+    }
+
+    #[test]
+    fn test_browse_folders() {
+        let mut manager = start_menu();
+
+        manager.browse(Vec::new());
+        assert_eq!(
+            row_names(&manager),
+            vec!["[Accessories]", "[Visual Studio 2022]", "Paint"]
+        );
+
+        manager.browse(vec!["visual studio 2022".to_string()]);
+        assert_eq!(
+            row_names(&manager),
+            vec![
+                "[Visual Studio Tools]",
+                "Visual Studio 2022",
+                "Blend for Visual Studio 2022"
+            ]
+        );
+        assert_eq!(manager.filtered_position("Visual Studio 2022.exe"), Some(1));
+        assert_eq!(manager.folder_at_row(0), Some("Visual Studio Tools"));
+        assert_eq!(manager.app_at_row(0), None);
+        assert_eq!(manager.app_at_row(1).unwrap().name, "Visual Studio 2022");
+        assert_eq!(manager.app_position(0), None);
+        assert_eq!(manager.app_position(2), Some(1));
+
+        // A rescan keeps the browsed folder
+        manager.apply_diff(ScanDiff {
+            added: vec![in_folder("Installer", "Visual Studio 2022")],
+            ..ScanDiff::default()
+        });
+        assert_eq!(row_names(&manager).len(), 4);
+        assert!(manager.browsed_folder().is_some());
+
+        // Searching leaves browse mode
+        manager.filter("");
+        assert_eq!(manager.browsed_folder(), None);
+        assert_eq!(manager.rows().len(), 6);
+    }
+
+    #[test]
+    fn test_broken_entries_rank_last() {
+        let mut manager = AppManager::new();
+        let mut old_code =
+            AppEntry::new("Code".to_string(), "C:\\old\\code.exe".to_string(), 0, 50);
+        old_code.broken = true;
+        manager.add_app(old_code);
+        manager.add_app(AppEntry::new(
+            "Codec Tool".to_string(),
+            "codec.exe".to_string(),
+            0,
+            1,
+        ));
+        manager.add_app(AppEntry::new(
+            "Paint".to_string(),
+            "paint.exe".to_string(),
+            0,
+            2,
+        ));
+
+        manager.sort_by_usage();
+        let names = manager
+            .apps()
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Paint", "Codec Tool", "Code"]);
+
+        // An exact name match still comes after a weaker match that works
+        manager.filter("code");
+        let found = manager
+            .filtered_indices()
+            .iter()
+            .map(|&i| manager.apps()[i].name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["Codec Tool", "Code"]);
+    }
+
     #[test]
     fn test_filter() {
         let mut manager = AppManager::new();
@@ -283,6 +914,210 @@ mod tests {
         assert_eq!(manager.get_filtered_app(1).unwrap().name, "Calendar");
     }
 
+    fn indexed_manager() -> AppManager {
+        let mut manager = AppManager::new();
+        manager.enable_search_index(0).unwrap();
+        for (i, name) in ["Calculator", "Calendar", "Notepad", "Visual Studio Code"]
+            .iter()
+            .enumerate()
+        {
+            manager.add_app(AppEntry::new(name.to_string(), format!("path{}", i), 0, 0));
+        }
+        manager
+    }
+
+    #[test]
+    fn test_filter_with_search_index() {
+        let mut manager = indexed_manager();
+
+        manager.filter("cal");
+        assert_eq!(manager.filtered_indices().len(), 2);
+
+        // Reordering the apps must not leave the index pointing at old positions
+        manager.increment_usage(2);
+        manager.sort_by_usage();
+        manager.filter("notepad");
+        assert_eq!(manager.get_filtered_app(0).unwrap().name, "Notepad");
+    }
+
+    #[test]
+    fn test_filter_with_search_index_falls_back_to_full_scan() {
+        let mut manager = indexed_manager();
+
+        // Not a substring of any name, so only the fuzzy scan finds it
+        manager.filter("vsc");
+        assert_eq!(
+            manager.get_filtered_app(0).unwrap().name,
+            "Visual Studio Code"
+        );
+
+        // Too short for the index
+        manager.filter("no");
+        assert_eq!(manager.get_filtered_app(0).unwrap().name, "Notepad");
+    }
+
+    fn shortcut(name: &str, target: &str, file: &str) -> AppEntry {
+        let mut app = AppEntry::new(name.to_string(), target.to_string(), 0, 0);
+        app.shortcut_path = Some(file.to_string());
+        app
+    }
+
+    #[test]
+    fn test_scan_diff_between() {
+        let old = vec![
+            shortcut("Code", "code.exe", "Code.lnk"),
+            shortcut("Paint", "paint.exe", "Paint.lnk"),
+            AppEntry::new_settings("Display".to_string(), "ms-settings:display".to_string(), -1),
+        ];
+        let mut renamed = shortcut("Visual Studio Code", "code.exe", "Code.lnk");
+        renamed.usage_count = 7;
+        let new = vec![
+            renamed.clone(),
+            shortcut("Steam", "steam.exe", "Steam.lnk"),
+            AppEntry::new_settings("Display".to_string(), "ms-settings:display".to_string(), -1),
+        ];
+
+        let diff = ScanDiff::between(&old, &new);
+        assert_eq!(
+            diff.added,
+            vec![shortcut("Steam", "steam.exe", "Steam.lnk")]
+        );
+        assert_eq!(diff.removed, vec!["Paint.lnk".to_string()]);
+        assert_eq!(diff.changed, vec![renamed]);
+        assert!(ScanDiff::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_scan_diff_ignores_usage_counts() {
+        let old = vec![shortcut("Code", "code.exe", "Code.lnk")];
+        let mut new = old.clone();
+        new[0].usage_count = 3;
+        assert!(ScanDiff::between(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_scan_diff_apply_to() {
+        let mut apps = vec![
+            shortcut("Code", "code.exe", "Code.lnk"),
+            shortcut("Paint", "paint.exe", "Paint.lnk"),
+        ];
+        apps[0].usage_count = 5;
+        apps[1].usage_count = 2;
+
+        let mut retargeted = shortcut("Paint", "mspaint.exe", "Paint.lnk");
+        retargeted.usage_count = 1;
+        let diff = ScanDiff {
+            added: vec![shortcut("Steam", "steam.exe", "Steam.lnk")],
+            removed: vec![],
+            changed: vec![shortcut("Code!", "code.exe", "Code.lnk"), retargeted],
+        };
+        diff.apply_to(&mut apps);
+
+        assert_eq!(apps.len(), 3);
+        // Same target: the usage count stays
+        assert_eq!((apps[0].name.as_str(), apps[0].usage_count), ("Code!", 5));
+        // New target: the count belongs to the new target
+        assert_eq!(
+            (apps[1].parse_name.as_str(), apps[1].usage_count),
+            ("mspaint.exe", 1)
+        );
+        assert_eq!(apps[2].name, "Steam");
+
+        ScanDiff {
+            removed: vec!["Code.lnk".to_string()],
+            ..ScanDiff::default()
+        }
+        .apply_to(&mut apps);
+        assert_eq!(apps.len(), 2);
+    }
+
+    fn scanned_manager() -> AppManager {
+        let mut manager = AppManager::new();
+        let mut code = shortcut("Code", "code.exe", "Code.lnk");
+        code.usage_count = 5;
+        manager.add_app(code);
+        manager.add_app(shortcut("Paint", "paint.exe", "Paint.lnk"));
+        manager.add_app(shortcut("Calculator", "calc.exe", "Calculator.lnk"));
+        manager.sort_by_usage();
+        manager
+    }
+
+    fn names(manager: &AppManager) -> Vec<&str> {
+        (0..manager.filtered_indices().len())
+            .map(|i| manager.get_filtered_app(i).unwrap().name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_diff_add() {
+        let mut manager = scanned_manager();
+        manager.filter("a");
+
+        manager.apply_diff(ScanDiff {
+            added: vec![
+                shortcut("Paint.NET", "paintdotnet.exe", "Paint.NET.lnk"),
+                // Same target as an existing entry: deduplicated
+                shortcut("Code (2)", "code.exe", "Other\\Code.lnk"),
+            ],
+            ..ScanDiff::default()
+        });
+
+        assert_eq!(manager.apps().len(), 4);
+        // The search stays applied
+        assert!(names(&manager).contains(&"Paint.NET"));
+        assert!(!names(&manager).contains(&"Code"));
+    }
+
+    #[test]
+    fn test_apply_diff_remove() {
+        let mut manager = scanned_manager();
+        manager.filter("");
+
+        manager.apply_diff(ScanDiff {
+            removed: vec!["Paint.lnk".to_string()],
+            ..ScanDiff::default()
+        });
+
+        assert_eq!(names(&manager), vec!["Code", "Calculator"]);
+        assert_eq!(manager.filtered_position("Paint.lnk"), None);
+        assert_eq!(manager.filtered_position("Calculator.lnk"), Some(1));
+    }
+
+    #[test]
+    fn test_apply_diff_rename() {
+        let mut manager = scanned_manager();
+        manager.filter("");
+
+        manager.apply_diff(ScanDiff {
+            changed: vec![shortcut("Visual Studio Code", "code.exe", "Code.lnk")],
+            ..ScanDiff::default()
+        });
+
+        let position = manager.filtered_position("Code.lnk").unwrap();
+        let code = manager.get_filtered_app(position).unwrap();
+        assert_eq!(code.name, "Visual Studio Code");
+        assert_eq!(code.usage_count, 5);
+        assert_eq!(manager.apps().len(), 3);
+    }
+
+    #[test]
+    fn test_apply_diff_target_change() {
+        let mut manager = scanned_manager();
+        manager.filter("");
+
+        manager.apply_diff(ScanDiff {
+            changed: vec![shortcut("Code", "code-insiders.exe", "Code.lnk")],
+            ..ScanDiff::default()
+        });
+
+        // The usage count belongs to the old target, so the entry re-sorts by name
+        let position = manager.filtered_position("Code.lnk").unwrap();
+        let code = manager.get_filtered_app(position).unwrap();
+        assert_eq!(code.parse_name, "code-insiders.exe");
+        assert_eq!(code.usage_count, 0);
+        assert_eq!(names(&manager), vec!["Calculator", "Code", "Paint"]);
+    }
+
     #[test]
     fn test_filter_empty() {
         let mut manager = AppManager::new();
@@ -329,13 +1164,20 @@ mod tests {
             0,
             3,
         ));
-        manager.add_app(AppEntry::new(
+        let duplicate = manager.add_app(AppEntry::new(
             "App2".to_string(),
             "path/to/app".to_string(), // Same parse_name
             1,
             5,
         ));
 
+        assert_eq!(
+            duplicate,
+            Some(Duplicate {
+                index: 0,
+                reason: DuplicateReason::SameTarget
+            })
+        );
         assert_eq!(manager.apps().len(), 1);
         assert_eq!(manager.apps()[0].name, "App1");
     }
@@ -356,13 +1198,14 @@ mod tests {
             1,
             5,
         ));
-        manager.add_app(AppEntry::new(
+        let duplicate = manager.add_app(AppEntry::new(
             "calculator".to_string(), // Same name, different case
             "path3".to_string(),
             2,
             1,
         ));
 
+        assert_eq!(duplicate.map(|d| d.reason), Some(DuplicateReason::SameName));
         assert_eq!(manager.apps().len(), 1);
         assert_eq!(manager.apps()[0].name, "Calculator");
     }
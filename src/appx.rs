@@ -0,0 +1,403 @@
+use crate::app_model::AppEntry;
+use crate::registry::RegistryReader;
+use crate::utils;
+use crate::xml::{self, Element};
+use std::path::PathBuf;
+use windows::{core::*, Win32::System::Com::*, Win32::UI::Shell::*};
+
+/// Start of the parse name that launches a packaged app through the shell,
+/// followed by `<package family name>!<app id>`
+pub const APPS_FOLDER_PREFIX: &str = "shell:AppsFolder\\";
+
+/// Where Windows records the packages installed for the current user
+const PACKAGES_KEY: &str = "HKEY_CURRENT_USER\\Software\\Classes\\Local Settings\\Software\\Microsoft\\Windows\\CurrentVersion\\AppModel\\Repository\\Packages";
+
+/// What a package's AppxManifest.xml says about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppxManifest {
+    /// `Identity/@Name`, e.g. `Microsoft.WindowsCalculator`
+    pub name: String,
+    pub publisher: String,
+    pub version: String,
+    /// `Properties/DisplayName`; may be an `ms-resource:` reference
+    pub display_name: Option<String>,
+    /// Framework and resource packages only support other packages
+    pub framework: bool,
+    pub applications: Vec<AppxApplication>,
+}
+
+/// One `Applications/Application` of a package
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppxApplication {
+    pub id: String,
+    /// Relative to the package's install folder
+    pub executable: Option<String>,
+    /// `VisualElements/@DisplayName`; may be an `ms-resource:` reference
+    pub display_name: Option<String>,
+    /// `VisualElements/@Description`; may be an `ms-resource:` reference
+    pub description: Option<String>,
+    /// Small logo, relative to the install folder and without its scale qualifier
+    pub logo: Option<String>,
+    /// False for `AppListEntry="none"`: apps Windows keeps out of the Start Menu
+    pub listed: bool,
+}
+
+impl AppxManifest {
+    /// Parse the text of an AppxManifest.xml
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let root = xml::parse(text)?;
+        if root.name != "Package" {
+            return Err(format!("Expected <Package>, found <{}>", root.name));
+        }
+
+        let identity = root.child("Identity").ok_or("Missing <Identity>")?;
+        let identity_attr = |name: &str| {
+            identity
+                .attr(name)
+                .map(str::to_string)
+                .ok_or_else(|| format!("<Identity> has no {}", name))
+        };
+
+        let properties = root.child("Properties");
+        let property = |name: &str| {
+            properties
+                .and_then(|properties| properties.child(name))
+                .map(|element| element.text.trim())
+                .filter(|text| !text.is_empty())
+        };
+        let framework = ["Framework", "ResourcePackage"]
+            .into_iter()
+            .any(|name| property(name).is_some_and(|value| value.eq_ignore_ascii_case("true")));
+
+        let applications = root
+            .child("Applications")
+            .map(|applications| {
+                applications
+                    .children_named("Application")
+                    .filter_map(AppxApplication::from_element)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            name: identity_attr("Name")?,
+            publisher: identity_attr("Publisher")?,
+            version: identity_attr("Version")?,
+            display_name: property("DisplayName").map(str::to_string),
+            framework,
+            applications,
+        })
+    }
+
+    /// Entries for the listed apps of this package, installed as `full_name`.
+    ///
+    /// `resolve` turns an indirect string source (see [`resource_source`]) into
+    /// text; a name it can't resolve falls back to the package's display name,
+    /// then to its identity name.
+    pub fn entries(
+        &self,
+        full_name: &str,
+        resolve: impl Fn(&str) -> Option<String>,
+    ) -> Vec<AppEntry> {
+        if self.framework {
+            return Vec::new();
+        }
+        let Some(family) = family_name(full_name) else {
+            return Vec::new();
+        };
+        let text = |value: &str| match resource_source(full_name, &self.name, value) {
+            Some(source) => resolve(&source).filter(|text| !text.is_empty()),
+            None => Some(value.to_string()),
+        };
+        let package_name = || {
+            self.display_name
+                .as_deref()
+                .and_then(text)
+                .unwrap_or_else(|| self.name.clone())
+        };
+
+        self.applications
+            .iter()
+            .filter(|app| app.listed)
+            .map(|app| {
+                let name = app
+                    .display_name
+                    .as_deref()
+                    .and_then(text)
+                    .unwrap_or_else(package_name);
+                let parse_name = format!("{}{}!{}", APPS_FOLDER_PREFIX, family, app.id);
+                let mut entry = AppEntry::new(name, parse_name, 0, 0);
+                entry.description = app.description.as_deref().and_then(text);
+                entry
+            })
+            .collect()
+    }
+}
+
+impl AppxApplication {
+    fn from_element(element: &Element) -> Option<Self> {
+        let visual = element.child("VisualElements");
+        let visual_attr = |name: &str| visual.and_then(|v| v.attr(name)).map(str::to_string);
+
+        Some(Self {
+            id: element.attr("Id")?.to_string(),
+            executable: element.attr("Executable").map(str::to_string),
+            display_name: visual_attr("DisplayName"),
+            description: visual_attr("Description"),
+            logo: visual_attr("Square44x44Logo"),
+            listed: visual_attr("AppListEntry").as_deref() != Some("none"),
+        })
+    }
+}
+
+/// Family name (`Name_PublisherId`) of a package full name
+/// (`Name_Version_Architecture_ResourceId_PublisherId`), which is also the
+/// name of its install folder
+pub fn family_name(full_name: &str) -> Option<String> {
+    let parts = full_name.split('_').collect::<Vec<_>>();
+    match parts.as_slice() {
+        [name, _, _, _, publisher_id] if !name.is_empty() && !publisher_id.is_empty() => {
+            Some(format!("{}_{}", name, publisher_id))
+        }
+        _ => None,
+    }
+}
+
+/// The `SHLoadIndirectString` source for `value` if it is an `ms-resource:`
+/// reference, or `None` if it is plain text.
+///
+/// Manifests write references in several shapes; unqualified ones name a
+/// string in the package's `Resources` map.
+pub fn resource_source(full_name: &str, package_name: &str, value: &str) -> Option<String> {
+    let key = value.strip_prefix("ms-resource:")?;
+    let uri = if key.starts_with("//") {
+        format!("ms-resource:{}", key)
+    } else if key.starts_with('/') {
+        format!("ms-resource://{}{}", package_name, key)
+    } else if key.contains('/') {
+        format!("ms-resource://{}/{}", package_name, key)
+    } else {
+        format!("ms-resource://{}/Resources/{}", package_name, key)
+    };
+    Some(format!("@{{{}?{}}}", full_name, uri))
+}
+
+/// Whether `parse_name` launches a packaged app
+pub fn is_packaged(parse_name: &str) -> bool {
+    parse_name
+        .get(..APPS_FOLDER_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(APPS_FOLDER_PREFIX))
+}
+
+/// A package installed for the current user
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledPackage {
+    pub full_name: String,
+    pub root: PathBuf,
+}
+
+/// Packages registered for the current user, with the folder each is installed in
+pub fn installed_packages(registry: &impl RegistryReader) -> Vec<InstalledPackage> {
+    registry
+        .subkeys(PACKAGES_KEY)
+        .into_iter()
+        .filter_map(|full_name| {
+            let root = registry.string(
+                &format!("{}\\{}", PACKAGES_KEY, full_name),
+                "PackageRootFolder",
+            )?;
+            Some(InstalledPackage {
+                full_name,
+                root: PathBuf::from(root),
+            })
+        })
+        .collect()
+}
+
+/// Resolve an indirect string such as `@{Package?ms-resource://...}`
+pub unsafe fn load_indirect_string(source: &str) -> Option<String> {
+    let source = utils::to_wide_string(source);
+    let mut text = [0u16; 512];
+    SHLoadIndirectString(PCWSTR(source.as_ptr()), &mut text, None).ok()?;
+    let text = String::from_utf16_lossy(&text);
+    Some(text.trim_end_matches('\0').to_string())
+}
+
+/// System image list index of a packaged app's icon, as Explorer shows it
+pub unsafe fn app_icon_index(parse_name: &str) -> Option<i32> {
+    let name = utils::to_wide_string(parse_name);
+    let mut pidl = std::ptr::null_mut();
+    SHParseDisplayName(PCWSTR(name.as_ptr()), None::<&IBindCtx>, &mut pidl, 0, None).ok()?;
+    let info = utils::get_file_info_pidl(pidl.cast());
+    CoTaskMemFree(Some(pidl.cast_const().cast()));
+    info.map(|info| info.iIcon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::RegFile;
+    use std::path::Path;
+
+    const CALCULATOR: &str = "Microsoft.WindowsCalculator_11.2307.4.0_x64__8wekyb3d8bbwe";
+    const TERMINAL: &str = "Microsoft.WindowsTerminal_1.18.2822.0_x64__8wekyb3d8bbwe";
+
+    fn fixture(name: &str) -> AppxManifest {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/appx")
+            .join(name);
+        AppxManifest::parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_calculator() {
+        let manifest = fixture("calculator.xml");
+        assert_eq!(manifest.name, "Microsoft.WindowsCalculator");
+        assert_eq!(manifest.version, "11.2307.4.0");
+        assert!(manifest.publisher.starts_with("CN=Microsoft Corporation"));
+        assert_eq!(
+            manifest.display_name.as_deref(),
+            Some("ms-resource:AppStoreName")
+        );
+        assert!(!manifest.framework);
+        assert_eq!(
+            manifest.applications,
+            vec![AppxApplication {
+                id: "App".to_string(),
+                executable: Some("CalculatorApp.exe".to_string()),
+                display_name: Some("ms-resource:AppName".to_string()),
+                description: Some("ms-resource:AppDescription".to_string()),
+                logo: Some("Assets\\CalculatorAppList.png".to_string()),
+                listed: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_entries_resolve_resources() {
+        let manifest = fixture("calculator.xml");
+        let resolve = |source: &str| {
+            (source
+                == "@{Microsoft.WindowsCalculator_11.2307.4.0_x64__8wekyb3d8bbwe?\
+                    ms-resource://Microsoft.WindowsCalculator/Resources/AppName}")
+                .then(|| "Calculator".to_string())
+        };
+
+        let entries = manifest.entries(CALCULATOR, resolve);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "Calculator");
+        assert_eq!(
+            entries[0].parse_name,
+            "shell:AppsFolder\\Microsoft.WindowsCalculator_8wekyb3d8bbwe!App"
+        );
+        // Unresolved references are left out rather than shown raw
+        assert_eq!(entries[0].description, None);
+    }
+
+    #[test]
+    fn test_unresolved_names_fall_back_to_the_package() {
+        let manifest = fixture("terminal.xml");
+        let entries = manifest.entries(TERMINAL, |_| None);
+
+        // The console host is kept out of the app list
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "Windows Terminal");
+        assert_eq!(
+            entries[0].parse_name,
+            "shell:AppsFolder\\Microsoft.WindowsTerminal_8wekyb3d8bbwe!App"
+        );
+        assert_eq!(
+            entries[0].description.as_deref(),
+            Some("The new Windows Terminal & console host")
+        );
+    }
+
+    #[test]
+    fn test_frameworks_have_no_entries() {
+        let manifest = fixture("vclibs.xml");
+        assert!(manifest.framework);
+        assert!(manifest.applications.is_empty());
+        assert!(manifest
+            .entries(
+                "Microsoft.VCLibs.140.00_14.0.30704.0_x64__8wekyb3d8bbwe",
+                |_| None
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_invalid_manifests_are_errors() {
+        assert!(AppxManifest::parse("<Package></Package>").is_err());
+        assert!(AppxManifest::parse("<Other/>").is_err());
+        assert!(AppxManifest::parse("<Package>").is_err());
+    }
+
+    #[test]
+    fn test_family_name() {
+        assert_eq!(
+            family_name(CALCULATOR).as_deref(),
+            Some("Microsoft.WindowsCalculator_8wekyb3d8bbwe")
+        );
+        assert_eq!(
+            family_name("Contoso.App_1.0.0.0_neutral_split.scale-200_abc123").as_deref(),
+            Some("Contoso.App_abc123")
+        );
+        assert_eq!(family_name("Contoso.App_abc123"), None);
+    }
+
+    #[test]
+    fn test_resource_source() {
+        let source = |value| resource_source("Pkg_1_x64__id", "Pkg", value);
+        assert_eq!(source("Calculator"), None);
+        assert_eq!(
+            source("ms-resource:AppName").as_deref(),
+            Some("@{Pkg_1_x64__id?ms-resource://Pkg/Resources/AppName}")
+        );
+        assert_eq!(
+            source("ms-resource:Strings/AppName").as_deref(),
+            Some("@{Pkg_1_x64__id?ms-resource://Pkg/Strings/AppName}")
+        );
+        assert_eq!(
+            source("ms-resource:/Resources/AppName").as_deref(),
+            Some("@{Pkg_1_x64__id?ms-resource://Pkg/Resources/AppName}")
+        );
+        assert_eq!(
+            source("ms-resource://Other/Resources/AppName").as_deref(),
+            Some("@{Pkg_1_x64__id?ms-resource://Other/Resources/AppName}")
+        );
+    }
+
+    #[test]
+    fn test_installed_packages() {
+        let registry = RegFile::parse(
+            r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\Classes\Local Settings\Software\Microsoft\Windows\CurrentVersion\AppModel\Repository\Packages\Microsoft.WindowsCalculator_11.2307.4.0_x64__8wekyb3d8bbwe]
+"PackageRootFolder"="C:\\Program Files\\WindowsApps\\Microsoft.WindowsCalculator_11.2307.4.0_x64__8wekyb3d8bbwe"
+"PackageID"="Microsoft.WindowsCalculator_11.2307.4.0_x64__8wekyb3d8bbwe"
+
+[HKEY_CURRENT_USER\Software\Classes\Local Settings\Software\Microsoft\Windows\CurrentVersion\AppModel\Repository\Packages\Half.Removed_1.0.0.0_x64__abc]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            installed_packages(&registry),
+            vec![InstalledPackage {
+                full_name: CALCULATOR.to_string(),
+                root: PathBuf::from(
+                    "C:\\Program Files\\WindowsApps\\Microsoft.WindowsCalculator_11.2307.4.0_x64__8wekyb3d8bbwe"
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_is_packaged() {
+        assert!(is_packaged(
+            "shell:AppsFolder\\Microsoft.WindowsCalculator_8wekyb3d8bbwe!App"
+        ));
+        assert!(is_packaged("Shell:appsFolder\\X!App"));
+        assert!(!is_packaged("C:\\Windows\\notepad.exe"));
+    }
+}
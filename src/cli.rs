@@ -0,0 +1,448 @@
+use crate::scan_report::{ReportFormat, ScanView};
+use crate::stats::{StatsFormat, DEFAULT_TOP};
+use crate::user_data::ImportMode;
+use std::path::PathBuf;
+
+/// Command-line help, shown for `oxistart help` and invalid arguments
+pub const USAGE: &str = "Usage: oxistart [--data-dir <directory>] [--portable] [command]
+
+Commands:
+  export [--out <file>]                        Write usage data to a JSON file (default: stdout)
+  import --in <file> [--mode merge|replace]    Load usage data from an export (default: merge)
+  sync [--dir <directory>]                     Share usage data with other machines through a
+                                               synced folder (the directory is remembered)
+  clear-history                                Forget every launch and usage count
+  retention [--max-age <days>|never] [--prune-missing on|off]
+                                               Show or change how long history is kept
+  incognito [on|off]                           Show or change whether launches are recorded
+  stats [--format table|json|csv] [--top <n>]  Report top apps, launch hours and result ranks
+  scan [--report|--broken] [--format text|json]
+                                               Scan for apps without changing the list and show
+                                               what was found, every file and decision, or the
+                                               shortcuts whose target is missing
+  help                                         Show this message";
+
+/// Options given on the command line
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Options {
+    /// Directory holding Oxistart's data, overriding the default location
+    pub data_dir: Option<PathBuf>,
+    /// Keep all data next to the executable
+    pub portable: bool,
+    /// Command to run instead of starting the launcher
+    pub command: Option<Command>,
+}
+
+/// A command run from the command line instead of the launcher UI
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Write user data as JSON, to stdout when no file is given
+    Export {
+        out: Option<PathBuf>,
+    },
+    /// Load user data from an export file
+    Import {
+        input: PathBuf,
+        mode: ImportMode,
+    },
+    /// Exchange launch logs with other machines through a shared directory
+    Sync {
+        dir: Option<PathBuf>,
+    },
+    /// Forget every launch and usage count
+    ClearHistory,
+    /// Show the retention policy, changing the given parts first
+    Retention {
+        /// `Some(None)` keeps history forever
+        max_age_days: Option<Option<u32>>,
+        prune_missing: Option<bool>,
+    },
+    /// Show incognito mode, turning it on or off first if asked
+    Incognito {
+        enabled: Option<bool>,
+    },
+    /// Print a usage statistics report
+    Stats {
+        format: StatsFormat,
+        top: usize,
+    },
+    /// Run a scan and show it as `view`
+    Scan {
+        view: ScanView,
+    },
+    Help,
+}
+
+/// Parse the command line (without the program name)
+pub fn parse_args<I>(args: I) -> Result<Options, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut options = Options::default();
+    let mut command: Option<String> = None;
+    let mut command_args = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--portable" {
+            options.portable = true;
+        } else if let Some(value) = flag_value("--data-dir", &arg, &mut args)? {
+            options.data_dir = Some(PathBuf::from(value));
+        } else if command.is_some() {
+            command_args.push(arg);
+        } else if !arg.starts_with('-') {
+            command = Some(arg);
+        } else {
+            return Err(format!("Unknown argument: {}", arg));
+        }
+    }
+
+    if let Some(name) = command {
+        options.command = Some(parse_command(&name, command_args)?);
+    }
+
+    Ok(options)
+}
+
+fn parse_command(name: &str, args: Vec<String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+
+    match name {
+        "export" => {
+            let mut out = None;
+            while let Some(arg) = args.next() {
+                if let Some(value) = flag_value("--out", &arg, &mut args)? {
+                    out = Some(PathBuf::from(value));
+                } else {
+                    return Err(format!("Unknown export argument: {}", arg));
+                }
+            }
+            Ok(Command::Export { out })
+        }
+        "import" => {
+            let mut input = None;
+            let mut mode = ImportMode::Merge;
+            while let Some(arg) = args.next() {
+                if let Some(value) = flag_value("--in", &arg, &mut args)? {
+                    input = Some(PathBuf::from(value));
+                } else if let Some(value) = flag_value("--mode", &arg, &mut args)? {
+                    mode = ImportMode::parse(&value)
+                        .ok_or_else(|| format!("Unknown import mode: {}", value))?;
+                } else {
+                    return Err(format!("Unknown import argument: {}", arg));
+                }
+            }
+            let input = input.ok_or_else(|| "import needs --in <file>".to_string())?;
+            Ok(Command::Import { input, mode })
+        }
+        "sync" => {
+            let mut dir = None;
+            while let Some(arg) = args.next() {
+                if let Some(value) = flag_value("--dir", &arg, &mut args)? {
+                    dir = Some(PathBuf::from(value));
+                } else {
+                    return Err(format!("Unknown sync argument: {}", arg));
+                }
+            }
+            Ok(Command::Sync { dir })
+        }
+        "clear-history" => match args.next() {
+            Some(arg) => Err(format!("Unknown clear-history argument: {}", arg)),
+            None => Ok(Command::ClearHistory),
+        },
+        "retention" => {
+            let mut max_age_days = None;
+            let mut prune_missing = None;
+            while let Some(arg) = args.next() {
+                if let Some(value) = flag_value("--max-age", &arg, &mut args)? {
+                    max_age_days = Some(match value.as_str() {
+                        "never" => None,
+                        days => Some(
+                            days.parse()
+                                .map_err(|_| format!("Invalid number of days: {}", days))?,
+                        ),
+                    });
+                } else if let Some(value) = flag_value("--prune-missing", &arg, &mut args)? {
+                    prune_missing = Some(parse_switch(&value)?);
+                } else {
+                    return Err(format!("Unknown retention argument: {}", arg));
+                }
+            }
+            Ok(Command::Retention {
+                max_age_days,
+                prune_missing,
+            })
+        }
+        "incognito" => {
+            let enabled = args.next().map(|value| parse_switch(&value)).transpose()?;
+            if let Some(arg) = args.next() {
+                return Err(format!("Unknown incognito argument: {}", arg));
+            }
+            Ok(Command::Incognito { enabled })
+        }
+        "stats" => {
+            let mut format = StatsFormat::Table;
+            let mut top = DEFAULT_TOP;
+            while let Some(arg) = args.next() {
+                if let Some(value) = flag_value("--format", &arg, &mut args)? {
+                    format = StatsFormat::parse(&value)
+                        .ok_or_else(|| format!("Unknown stats format: {}", value))?;
+                } else if let Some(value) = flag_value("--top", &arg, &mut args)? {
+                    top = value
+                        .parse()
+                        .map_err(|_| format!("Invalid number of apps: {}", value))?;
+                } else {
+                    return Err(format!("Unknown stats argument: {}", arg));
+                }
+            }
+            Ok(Command::Stats { format, top })
+        }
+        "scan" => {
+            let mut view: Option<fn(ReportFormat) -> ScanView> = None;
+            let mut format = None;
+            while let Some(arg) = args.next() {
+                if arg == "--report" || arg == "--broken" {
+                    if view.is_some() {
+                        return Err("Use either --report or --broken".to_string());
+                    }
+                    view = Some(if arg == "--report" {
+                        ScanView::Report
+                    } else {
+                        ScanView::Broken
+                    });
+                } else if let Some(value) = flag_value("--format", &arg, &mut args)? {
+                    format = Some(
+                        ReportFormat::parse(&value)
+                            .ok_or_else(|| format!("Unknown report format: {}", value))?,
+                    );
+                } else {
+                    return Err(format!("Unknown scan argument: {}", arg));
+                }
+            }
+            let view = match (view, format) {
+                (Some(view), format) => view(format.unwrap_or(ReportFormat::Text)),
+                (None, None) => ScanView::Summary,
+                (None, Some(_)) => return Err("--format needs --report or --broken".to_string()),
+            };
+            Ok(Command::Scan { view })
+        }
+        "help" => Ok(Command::Help),
+        _ => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+/// Parse `on` or `off`
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("Expected on or off, got: {}", value)),
+    }
+}
+
+/// If `arg` is `flag` (value in the next argument) or `flag=value`, return the value
+fn flag_value(
+    flag: &str,
+    arg: &str,
+    rest: &mut impl Iterator<Item = String>,
+) -> Result<Option<String>, String> {
+    if arg == flag {
+        return rest
+            .next()
+            .map(Some)
+            .ok_or_else(|| format!("{} needs a value", flag));
+    }
+
+    Ok(arg
+        .strip_prefix(flag)
+        .and_then(|tail| tail.strip_prefix('='))
+        .map(|value| value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_no_arguments() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+    }
+
+    #[test]
+    fn test_data_dir() {
+        let expected = Some(PathBuf::from("D:\\Data"));
+        assert_eq!(
+            parse(&["--data-dir", "D:\\Data"]).unwrap().data_dir,
+            expected
+        );
+        assert_eq!(parse(&["--data-dir=D:\\Data"]).unwrap().data_dir, expected);
+        assert!(parse(&["--data-dir"]).is_err());
+    }
+
+    #[test]
+    fn test_portable() {
+        assert!(parse(&["--portable"]).unwrap().portable);
+    }
+
+    #[test]
+    fn test_unknown_argument() {
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(
+            parse(&["export"]).unwrap().command,
+            Some(Command::Export { out: None })
+        );
+
+        let options = parse(&["--portable", "export", "--out", "data.json"]).unwrap();
+        assert!(options.portable);
+        assert_eq!(
+            options.command,
+            Some(Command::Export {
+                out: Some(PathBuf::from("data.json"))
+            })
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        assert_eq!(
+            parse(&["import", "--in=data.json"]).unwrap().command,
+            Some(Command::Import {
+                input: PathBuf::from("data.json"),
+                mode: ImportMode::Merge
+            })
+        );
+
+        // Global options may follow the command
+        let options = parse(&[
+            "import",
+            "--mode",
+            "replace",
+            "--in",
+            "data.json",
+            "--data-dir",
+            "D:\\Data",
+        ])
+        .unwrap();
+        assert_eq!(options.data_dir, Some(PathBuf::from("D:\\Data")));
+        assert_eq!(
+            options.command,
+            Some(Command::Import {
+                input: PathBuf::from("data.json"),
+                mode: ImportMode::Replace
+            })
+        );
+
+        assert!(parse(&["import"]).is_err());
+        assert!(parse(&["import", "--in", "x", "--mode", "sideways"]).is_err());
+    }
+
+    #[test]
+    fn test_sync() {
+        assert_eq!(
+            parse(&["sync"]).unwrap().command,
+            Some(Command::Sync { dir: None })
+        );
+        assert_eq!(
+            parse(&["sync", "--dir", "D:\\Sync"]).unwrap().command,
+            Some(Command::Sync {
+                dir: Some(PathBuf::from("D:\\Sync"))
+            })
+        );
+        assert!(parse(&["sync", "D:\\Sync"]).is_err());
+    }
+
+    #[test]
+    fn test_history_commands() {
+        assert_eq!(
+            parse(&["clear-history"]).unwrap().command,
+            Some(Command::ClearHistory)
+        );
+        assert_eq!(
+            parse(&["retention", "--max-age", "90", "--prune-missing=on"])
+                .unwrap()
+                .command,
+            Some(Command::Retention {
+                max_age_days: Some(Some(90)),
+                prune_missing: Some(true)
+            })
+        );
+        assert_eq!(
+            parse(&["retention", "--max-age", "never"]).unwrap().command,
+            Some(Command::Retention {
+                max_age_days: Some(None),
+                prune_missing: None
+            })
+        );
+        assert_eq!(
+            parse(&["incognito", "off"]).unwrap().command,
+            Some(Command::Incognito {
+                enabled: Some(false)
+            })
+        );
+        assert!(parse(&["retention", "--max-age", "soon"]).is_err());
+        assert!(parse(&["incognito", "maybe"]).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        assert_eq!(
+            parse(&["stats"]).unwrap().command,
+            Some(Command::Stats {
+                format: StatsFormat::Table,
+                top: DEFAULT_TOP
+            })
+        );
+        assert_eq!(
+            parse(&["stats", "--format=csv", "--top", "3"])
+                .unwrap()
+                .command,
+            Some(Command::Stats {
+                format: StatsFormat::Csv,
+                top: 3
+            })
+        );
+        assert!(parse(&["stats", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(
+            parse(&["scan"]).unwrap().command,
+            Some(Command::Scan {
+                view: ScanView::Summary
+            })
+        );
+        assert_eq!(
+            parse(&["scan", "--report"]).unwrap().command,
+            Some(Command::Scan {
+                view: ScanView::Report(ReportFormat::Text)
+            })
+        );
+        assert_eq!(
+            parse(&["scan", "--format=json", "--report"])
+                .unwrap()
+                .command,
+            Some(Command::Scan {
+                view: ScanView::Report(ReportFormat::Json)
+            })
+        );
+        assert_eq!(
+            parse(&["scan", "--broken"]).unwrap().command,
+            Some(Command::Scan {
+                view: ScanView::Broken(ReportFormat::Text)
+            })
+        );
+        assert!(parse(&["scan", "--format", "json"]).is_err());
+        assert!(parse(&["scan", "--report", "--broken"]).is_err());
+        assert!(parse(&["scan", "--report", "--format", "csv"]).is_err());
+    }
+}
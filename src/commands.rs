@@ -0,0 +1,298 @@
+use crate::cli::{Command, Options, USAGE};
+use crate::config::{self, Config};
+use crate::data_dir;
+use crate::db::{self, Database};
+use crate::filter_rules::FilterRules;
+use crate::known_folders;
+use crate::scan_report::{ScanReport, ScanView};
+use crate::scanner;
+use crate::stats::Stats;
+use crate::sync;
+use crate::user_data::UserData;
+use std::collections::HashMap;
+use std::path::Path;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+
+/// Run a command-line command and return the process exit code
+pub fn run(options: &Options, command: &Command) -> i32 {
+    if *command == Command::Help {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let dir = data_dir::data_dir(options);
+    let path = dir.join(db::DB_FILE_NAME);
+    let mut database = match Database::open(&path) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+
+    match execute(command, &mut database, &dir) {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+/// Execute `command` against `database` (in `data_dir`), returning the text to print
+fn execute(command: &Command, database: &mut Database, data_dir: &Path) -> Result<String, String> {
+    match command {
+        Command::Export { out } => {
+            let data = database
+                .export_user_data()
+                .map_err(|e| format!("Failed to read user data: {}", e))?;
+            let json = data.to_json();
+
+            match out {
+                Some(path) => {
+                    std::fs::write(path, json)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    Ok(format!(
+                        "Exported {} usage counts and {} launch events to {}",
+                        data.usage.len(),
+                        data.launch_events.len(),
+                        path.display()
+                    ))
+                }
+                None => Ok(json),
+            }
+        }
+        Command::Import { input, mode } => {
+            let text = std::fs::read_to_string(input)
+                .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+            let data = UserData::from_json(&text)?;
+            database
+                .import_user_data(&data, *mode)
+                .map_err(|e| format!("Failed to import user data: {}", e))?;
+            Ok(format!(
+                "Imported {} usage counts and {} launch events from {}",
+                data.usage.len(),
+                data.launch_events.len(),
+                input.display()
+            ))
+        }
+        Command::Sync { dir } => {
+            let dir = match dir {
+                Some(dir) => {
+                    sync::set_configured_dir(database, dir)?;
+                    dir.clone()
+                }
+                None => sync::configured_dir(database).ok_or_else(|| {
+                    "No sync directory yet; use sync --dir <directory>".to_string()
+                })?,
+            };
+            let report = sync::sync(database, &dir)?;
+            Ok(format!(
+                "Published {} launches and merged {} from {} other machines in {}",
+                report.published,
+                report.merged,
+                report.machines,
+                dir.display()
+            ))
+        }
+        Command::ClearHistory => {
+            database
+                .clear_history(db::now_timestamp())
+                .map_err(|e| format!("Failed to clear history: {}", e))?;
+            // Our log in the sync folder would otherwise keep every launched path
+            if let Some(dir) = sync::configured_dir(database) {
+                sync::remove_own_log(database, &dir)?;
+            }
+            Ok("History cleared".to_string())
+        }
+        Command::Retention {
+            max_age_days,
+            prune_missing,
+        } => {
+            let mut policy = database.retention_policy().map_err(|e| e.to_string())?;
+            if let Some(max_age_days) = max_age_days {
+                policy.max_age_days = *max_age_days;
+            }
+            if let Some(prune_missing) = prune_missing {
+                policy.prune_missing = *prune_missing;
+            }
+            database
+                .set_retention_policy(&policy)
+                .map_err(|e| e.to_string())?;
+
+            let report = database
+                .apply_retention(db::now_timestamp(), |path| path.exists())
+                .map_err(|e| format!("Failed to apply retention: {}", e))?;
+            let max_age = match policy.max_age_days {
+                Some(days) => format!("{} days", days),
+                None => "forever".to_string(),
+            };
+            Ok(format!(
+                "History is kept {}; entries whose file is gone are {}.\n\
+                 Removed {} expired launches and {} missing entries",
+                max_age,
+                if policy.prune_missing {
+                    "forgotten"
+                } else {
+                    "kept"
+                },
+                report.expired_events,
+                report.missing_entries
+            ))
+        }
+        Command::Incognito { enabled } => {
+            if let Some(enabled) = enabled {
+                database
+                    .set_incognito(*enabled)
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(format!(
+                "Incognito is {}",
+                if database.is_incognito() { "on" } else { "off" }
+            ))
+        }
+        Command::Stats { format, top } => {
+            let stats = Stats::collect(database, db::now_timestamp(), *top)
+                .map_err(|e| format!("Failed to read launch history: {}", e))?;
+            Ok(stats.render(*format).trim_end().to_string())
+        }
+        Command::Scan { view } => {
+            let config = Config::load(&data_dir.join(config::CONFIG_FILE_NAME))?;
+            let rules = FilterRules::with_overrides(&config.filters.rules)?;
+            let roots = scanner::scan_roots(&config.scan, known_folders::current());
+
+            // Read every file again rather than trusting the last scan, and
+            // compare with an empty list so the diff holds every entry
+            let output = unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                scanner::scan_apps(&roots, &rules, &[], &[], &HashMap::new())
+            };
+            let scan_report = ScanReport::new(&roots, &output);
+            Ok(match view {
+                ScanView::Summary => {
+                    format!("{}\n{}", scan_report.summary(), scan_report.timing())
+                }
+                ScanView::Report(format) => scan_report.render(*format).trim_end().to_string(),
+                ScanView::Broken(format) => {
+                    scan_report.render_broken(*format).trim_end().to_string()
+                }
+            })
+        }
+        Command::Help => Ok(USAGE.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{LaunchAction, LaunchEvent};
+    use crate::user_data::ImportMode;
+
+    #[test]
+    fn test_export_then_import_into_another_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("data.json");
+
+        let mut source = Database::open_in_memory().unwrap();
+        source
+            .record_launch(&LaunchEvent::now(
+                "a.exe",
+                LaunchAction::Open,
+                Some("a"),
+                Some(0),
+            ))
+            .unwrap();
+        execute(
+            &Command::Export {
+                out: Some(file.clone()),
+            },
+            &mut source,
+            Path::new(""),
+        )
+        .unwrap();
+
+        let mut target = Database::open_in_memory().unwrap();
+        execute(
+            &Command::Import {
+                input: file,
+                mode: ImportMode::Merge,
+            },
+            &mut target,
+            Path::new(""),
+        )
+        .unwrap();
+
+        assert_eq!(target.load_usage_map().unwrap().get("a.exe"), Some(&1));
+        assert_eq!(target.load_launch_events(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_export_to_stdout_returns_json() {
+        let mut database = Database::open_in_memory().unwrap();
+        let output = execute(&Command::Export { out: None }, &mut database, Path::new("")).unwrap();
+        assert!(UserData::from_json(&output).is_ok());
+    }
+
+    #[test]
+    fn test_sync_remembers_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut database = Database::open_in_memory().unwrap();
+
+        assert!(execute(&Command::Sync { dir: None }, &mut database, Path::new("")).is_err());
+        execute(
+            &Command::Sync {
+                dir: Some(dir.path().to_path_buf()),
+            },
+            &mut database,
+            Path::new(""),
+        )
+        .unwrap();
+        assert!(execute(&Command::Sync { dir: None }, &mut database, Path::new("")).is_ok());
+    }
+
+    #[test]
+    fn test_retention_and_incognito_commands() {
+        let mut database = Database::open_in_memory().unwrap();
+
+        execute(
+            &Command::Retention {
+                max_age_days: Some(Some(30)),
+                prune_missing: None,
+            },
+            &mut database,
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(database.retention_policy().unwrap().max_age_days, Some(30));
+
+        let output = execute(
+            &Command::Incognito {
+                enabled: Some(true),
+            },
+            &mut database,
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(output, "Incognito is on");
+        assert!(database.is_incognito());
+    }
+
+    #[test]
+    fn test_import_missing_file_fails() {
+        let mut database = Database::open_in_memory().unwrap();
+        let result = execute(
+            &Command::Import {
+                input: "does-not-exist.json".into(),
+                mode: ImportMode::Merge,
+            },
+            &mut database,
+            Path::new(""),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::filter_rules::FilterRule;
+use crate::glob;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Name of the settings file in the data directory
+pub const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Extensions scanned when a root doesn't list its own
+const DEFAULT_EXTENSIONS: [&str; 2] = ["lnk", "url"];
+
+/// User settings read from `config.json`; every section is optional
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub scan: ScanConfig,
+    pub filters: FilterConfig,
+}
+
+/// The `scan` section
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// Folders scanned in addition to the Start Menu
+    pub roots: Vec<ScanRoot>,
+}
+
+/// The `filters` section
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Rules checked before the defaults; one with a default rule's id replaces it
+    pub rules: Vec<FilterRule>,
+}
+
+/// A folder the scanner walks, and which files in it become entries
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScanRoot {
+    pub path: PathBuf,
+    /// How many folder levels below `path` to descend into; unlimited when absent
+    #[serde(default)]
+    pub depth: Option<usize>,
+    /// File extensions to pick up, without the dot
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    /// Globs (relative to `path`) a file must match one of; everything when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs (relative to `path`) of files and folders to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+fn default_extensions() -> Vec<String> {
+    DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect()
+}
+
+impl Config {
+    /// Read the config file at `path`; a missing file gives the defaults
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+}
+
+impl ScanRoot {
+    /// A root scanned for shortcuts at any depth, with no include or exclude rules
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            depth: None,
+            extensions: default_extensions(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Whether the file at `relative` (to the root) should be scanned
+    pub fn accepts_file(&self, relative: &Path) -> bool {
+        let has_extension = relative
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.iter().any(|x| x.eq_ignore_ascii_case(e)));
+        let relative = relative.to_string_lossy();
+
+        has_extension
+            && (self.include.is_empty()
+                || self
+                    .include
+                    .iter()
+                    .any(|pattern| glob::matches(pattern, &relative)))
+            && !self.is_excluded(&relative)
+    }
+
+    /// Whether the folder at `relative` (to the root) should be walked into
+    pub fn accepts_dir(&self, relative: &Path) -> bool {
+        let too_deep = self
+            .depth
+            .is_some_and(|depth| relative.components().count() > depth);
+        !too_deep && !self.is_excluded(&relative.to_string_lossy())
+    }
+
+    fn is_excluded(&self, relative: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| glob::matches(pattern, relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_file_gives_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::load(&dir.path().join(CONFIG_FILE_NAME)).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_load_scan_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        std::fs::write(
+            &path,
+            r#"{
+                "scan": {
+                    "roots": [
+                        { "path": "D:\\PortableApps", "depth": 2, "extensions": ["exe"],
+                          "exclude": ["*uninst*"] },
+                        { "path": "\\\\server\\apps" }
+                    ]
+                },
+                "filters": {
+                    "rules": [
+                        { "id": "installer", "action": "deny", "field": "name", "glob": "*", "disabled": true }
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let roots = &config.scan.roots;
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].depth, Some(2));
+        assert_eq!(roots[0].extensions, vec!["exe".to_string()]);
+        assert_eq!(roots[1], ScanRoot::new(PathBuf::from("\\\\server\\apps")));
+        assert!(config.filters.rules[0].disabled);
+    }
+
+    #[test]
+    fn test_invalid_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        std::fs::write(&path, "{ \"scan\": { \"roots\": [{}] } }").unwrap();
+        assert!(Config::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_root_rules() {
+        let root = ScanRoot {
+            depth: Some(1),
+            include: vec!["**/*.lnk".to_string(), "Top.url".to_string()],
+            exclude: vec!["Old".to_string(), "*uninstall*".to_string()],
+            ..ScanRoot::new(PathBuf::from("C:\\Apps"))
+        };
+
+        assert!(root.accepts_file(Path::new("Tool.LNK")));
+        assert!(root.accepts_file(Path::new("Top.url")));
+        assert!(!root.accepts_file(Path::new("Other.url")));
+        assert!(!root.accepts_file(Path::new("Uninstall Tool.lnk")));
+        assert!(!root.accepts_file(Path::new("notes.txt")));
+
+        assert!(root.accepts_dir(Path::new("Vendor")));
+        assert!(!root.accepts_dir(Path::new("Old")));
+        assert!(!root.accepts_dir(&Path::new("Vendor").join("Sub")));
+    }
+}
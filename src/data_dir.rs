@@ -0,0 +1,112 @@
+use crate::cli::Options;
+use std::path::{Path, PathBuf};
+
+/// Folder created under the per-user application data directory
+const APP_DIR_NAME: &str = "Oxistart";
+
+/// A file with this name next to the executable switches on portable mode
+pub const PORTABLE_MARKER: &str = "oxistart.portable";
+
+/// Resolve the directory Oxistart keeps its data in.
+///
+/// In order of precedence: `--data-dir`, portable mode (`--portable` or a
+/// marker file next to the executable), `%LOCALAPPDATA%\Oxistart`, and finally
+/// the executable's own directory.
+pub fn resolve(
+    options: &Options,
+    local_app_data: Option<PathBuf>,
+    exe_dir: Option<PathBuf>,
+) -> PathBuf {
+    if let Some(ref dir) = options.data_dir {
+        return dir.clone();
+    }
+
+    let portable = options.portable
+        || exe_dir
+            .as_deref()
+            .map(|dir| dir.join(PORTABLE_MARKER).exists())
+            .unwrap_or(false);
+
+    if !portable {
+        if let Some(base) = local_app_data {
+            return base.join(APP_DIR_NAME);
+        }
+    }
+
+    exe_dir.unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Data directory for this process, based on its environment
+pub fn data_dir(options: &Options) -> PathBuf {
+    let local_app_data = crate::known_folders::current().local_app_data.clone();
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    resolve(options, local_app_data, exe_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_to_local_app_data() {
+        let dir = resolve(
+            &Options::default(),
+            Some(PathBuf::from("C:\\Users\\me\\AppData\\Local")),
+            Some(PathBuf::from("C:\\Tools")),
+        );
+        assert_eq!(
+            dir,
+            PathBuf::from("C:\\Users\\me\\AppData\\Local").join("Oxistart")
+        );
+    }
+
+    #[test]
+    fn test_data_dir_option_wins() {
+        let options = Options {
+            data_dir: Some(PathBuf::from("D:\\Sync\\Oxistart")),
+            portable: true,
+            ..Options::default()
+        };
+        let dir = resolve(
+            &options,
+            Some(PathBuf::from("C:\\Users\\me\\AppData\\Local")),
+            Some(PathBuf::from("C:\\Tools")),
+        );
+        assert_eq!(dir, PathBuf::from("D:\\Sync\\Oxistart"));
+    }
+
+    #[test]
+    fn test_portable_flag_uses_exe_dir() {
+        let options = Options {
+            portable: true,
+            ..Options::default()
+        };
+        let dir = resolve(
+            &options,
+            Some(PathBuf::from("C:\\Users\\me\\AppData\\Local")),
+            Some(PathBuf::from("E:\\PortableApps\\Oxistart")),
+        );
+        assert_eq!(dir, PathBuf::from("E:\\PortableApps\\Oxistart"));
+    }
+
+    #[test]
+    fn test_portable_marker_uses_exe_dir() {
+        let exe_dir = tempfile::tempdir().unwrap();
+        std::fs::write(exe_dir.path().join(PORTABLE_MARKER), "").unwrap();
+
+        let dir = resolve(
+            &Options::default(),
+            Some(PathBuf::from("C:\\Users\\me\\AppData\\Local")),
+            Some(exe_dir.path().to_path_buf()),
+        );
+        assert_eq!(dir, exe_dir.path());
+    }
+
+    #[test]
+    fn test_falls_back_to_exe_dir_without_local_app_data() {
+        let dir = resolve(&Options::default(), None, Some(PathBuf::from("C:\\Tools")));
+        assert_eq!(dir, PathBuf::from("C:\\Tools"));
+    }
+}
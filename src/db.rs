@@ -1,259 +1,1054 @@
 use crate::app_model::{AppEntry, AppEntryType};
-use rusqlite::{params, Connection, Result as SqlResult};
+use crate::scanner::ScannedFile;
+use crate::user_data::{Alias, ExportedEvent, ImportMode, UsageCount, UserData};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
 
-/// Initialize the database with the necessary schema
-pub fn init_db() -> SqlResult<()> {
-    let conn = Connection::open("history.db")?;
-
-    // Legacy table for usage tracking
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_usage (
-            path TEXT PRIMARY KEY,
-            count INTEGER
-        )",
-        [],
-    )?;
-
-    // New table for caching app list
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_cache (
-            parse_name TEXT PRIMARY KEY,
-            name TEXT NOT NULL,
-            icon_index INTEGER NOT NULL,
-            entry_type TEXT NOT NULL
-        )",
-        [],
-    )?;
+pub mod migrations;
+pub mod search_index;
 
-    Ok(())
+/// What the user did with an entry when it was launched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchAction {
+    Open,
+    Admin,
+    Location,
 }
 
-/// Load usage statistics from the database
-pub fn load_usage_map() -> HashMap<String, i32> {
-    let mut usage_map = HashMap::new();
-
-    if let Ok(conn) = Connection::open("history.db") {
-        if let Ok(mut stmt) = conn.prepare("SELECT path, count FROM app_usage") {
-            if let Ok(rows) = stmt.query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
-            }) {
-                for row in rows.flatten() {
-                    usage_map.insert(row.0, row.1);
-                }
-            }
+impl LaunchAction {
+    /// Name stored in the `action` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LaunchAction::Open => "open",
+            LaunchAction::Admin => "admin",
+            LaunchAction::Location => "location",
         }
     }
 
-    usage_map
+    /// Parse the name stored in the `action` column
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(LaunchAction::Open),
+            "admin" => Some(LaunchAction::Admin),
+            "location" => Some(LaunchAction::Location),
+            _ => None,
+        }
+    }
 }
 
-/// Increment usage count for an application
-pub fn increment_usage(path: &str) -> SqlResult<()> {
-    let conn = Connection::open("history.db")?;
-    conn.execute(
-        "INSERT INTO app_usage (path, count) VALUES (?1, 1)
-         ON CONFLICT(path) DO UPDATE SET count = count + 1",
-        params![path],
-    )?;
-    Ok(())
+/// A single entry of the launch history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LaunchEvent {
+    /// Seconds since the Unix epoch (0 for counts migrated from `app_usage`)
+    pub timestamp: i64,
+    /// The launched entry's `parse_name`
+    pub entry_id: String,
+    pub action: LaunchAction,
+    /// Text typed in the search box when the entry was launched
+    pub query: Option<String>,
+    /// Position of the entry in the result list (0 = first)
+    pub rank: Option<i32>,
 }
 
-/// Save all apps to the cache database
-pub fn save_app_cache(apps: &[AppEntry]) -> SqlResult<()> {
-    let conn = Connection::open("history.db")?;
+impl LaunchEvent {
+    /// Create an event stamped with the current time
+    pub fn now(
+        entry_id: &str,
+        action: LaunchAction,
+        query: Option<&str>,
+        rank: Option<i32>,
+    ) -> Self {
+        Self {
+            timestamp: now_timestamp(),
+            entry_id: entry_id.to_string(),
+            action,
+            query: query.filter(|q| !q.is_empty()).map(|q| q.to_string()),
+            rank,
+        }
+    }
+}
 
-    // Clear existing cache
-    conn.execute("DELETE FROM app_cache", [])?;
+/// Current time in seconds since the Unix epoch
+pub fn now_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
 
-    // Insert all apps
-    let mut stmt = conn.prepare(
-        "INSERT INTO app_cache (parse_name, name, icon_index, entry_type) VALUES (?1, ?2, ?3, ?4)",
-    )?;
+/// File name of the database inside the data directory
+pub const DB_FILE_NAME: &str = "history.db";
 
-    for app in apps {
-        let entry_type = match app.entry_type {
-            AppEntryType::Application => "Application",
-            AppEntryType::Settings => "Settings",
-        };
-        stmt.execute(params![
-            app.parse_name,
-            app.name,
-            app.icon_index,
-            entry_type
+/// `meta` keys for the history settings
+const RETENTION_DAYS_KEY: &str = "retention_days";
+const PRUNE_MISSING_KEY: &str = "prune_missing";
+const HISTORY_CLEARED_AT_KEY: &str = "history_cleared_at";
+const INCOGNITO_KEY: &str = "incognito";
+
+/// `meta` key holding this machine's id, which names its sync log and marks
+/// the launches it exports
+const MACHINE_ID_KEY: &str = "machine_id";
+
+/// Length of a day, for retention and statistics periods
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// How much launch history is kept
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Forget launches older than this many days (`None` keeps them forever)
+    pub max_age_days: Option<u32>,
+    /// Forget entries whose file no longer exists
+    pub prune_missing: bool,
+}
+
+/// What `Database::apply_retention` removed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Launch events older than the maximum age
+    pub expired_events: usize,
+    /// Entries whose target file is gone
+    pub missing_entries: usize,
+}
+
+/// Handle to Oxistart's SQLite database, opened once and shared by the app
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Open (or create) the database at `path`, bringing its schema up to date
+    pub fn open(path: &Path) -> SqlResult<Self> {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Open a private database that lives only as long as the handle
+    pub fn open_in_memory() -> SqlResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> SqlResult<Self> {
+        configure(&conn)?;
+        migrations::migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+
+    /// Load usage statistics from the database
+    pub fn load_usage_map(&self) -> SqlResult<HashMap<String, i32>> {
+        let mut stmt = self.conn.prepare("SELECT path, count FROM app_usage")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?;
+        rows.collect()
+    }
+
+    /// Record a launch: bumps the usage count and appends to the launch history.
+    /// Nothing is written in incognito mode.
+    pub fn record_launch(&mut self, event: &LaunchEvent) -> SqlResult<()> {
+        if self.is_incognito() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        increment_usage_in(&tx, &event.entry_id)?;
+        tx.prepare_cached(
+            "INSERT INTO launch_events (timestamp, entry_id, action, query, rank)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?
+        .execute(params![
+            event.timestamp,
+            event.entry_id,
+            event.action.as_str(),
+            event.query,
+            event.rank
         ])?;
+        tx.commit()
     }
 
-    Ok(())
-}
+    /// Load launch events, oldest first, optionally only those at or after `since`
+    #[cfg(test)]
+    pub fn load_launch_events(&self, since: Option<i64>) -> SqlResult<Vec<LaunchEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp, entry_id, action, query, rank FROM launch_events
+             WHERE timestamp >= ?1 ORDER BY timestamp, id",
+        )?;
+        let rows = stmt.query_map(params![since.unwrap_or(i64::MIN)], |row| {
+            let action: String = row.get(2)?;
+            Ok(LaunchEvent {
+                timestamp: row.get(0)?,
+                entry_id: row.get(1)?,
+                action: LaunchAction::parse(&action).unwrap_or(LaunchAction::Open),
+                query: row.get(3)?,
+                rank: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
 
-/// Load all apps from the cache database
-pub fn load_app_cache() -> Vec<AppEntry> {
-    let mut apps = Vec::new();
-    let usage_map = load_usage_map();
+    /// Number of launches per entry at or after `since`
+    pub fn launch_counts_since(&self, since: i64) -> SqlResult<HashMap<String, i32>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT entry_id, COUNT(*) FROM launch_events WHERE timestamp >= ?1 GROUP BY entry_id",
+        )?;
+        let rows = stmt.query_map(params![since], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?;
+        rows.collect()
+    }
 
-    if let Ok(conn) = Connection::open("history.db") {
-        if let Ok(mut stmt) =
-            conn.prepare("SELECT parse_name, name, icon_index, entry_type FROM app_cache")
-        {
-            if let Ok(rows) = stmt.query_map([], |row| {
-                let parse_name: String = row.get(0)?;
-                let name: String = row.get(1)?;
-                let icon_index: i32 = row.get(2)?;
-                let entry_type: String = row.get(3)?;
-                Ok((parse_name, name, icon_index, entry_type))
-            }) {
-                for row in rows.flatten() {
-                    let (parse_name, name, icon_index, entry_type) = row;
-                    let usage_count = *usage_map.get(&parse_name).unwrap_or(&0);
-
-                    let app = if entry_type == "Settings" {
-                        AppEntry::new_settings(name, parse_name, icon_index)
-                    } else {
-                        AppEntry::new(name, parse_name, icon_index, usage_count)
-                    };
-                    apps.push(app);
-                }
+    /// Number of launches per action
+    pub fn launch_counts_by_action(&self) -> SqlResult<HashMap<LaunchAction, i32>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT action, COUNT(*) FROM launch_events GROUP BY action")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?;
+        let mut counts = HashMap::new();
+        for (action, count) in rows.collect::<SqlResult<Vec<_>>>()? {
+            if let Some(action) = LaunchAction::parse(&action) {
+                *counts.entry(action).or_insert(0) += count;
             }
         }
+        Ok(counts)
     }
 
-    apps
-}
+    /// Average result rank of launches made from a search at or after `since`,
+    /// if any were recorded
+    pub fn average_launch_rank(&self, since: i64) -> SqlResult<Option<f64>> {
+        self.conn.query_row(
+            "SELECT AVG(rank) FROM launch_events
+             WHERE rank IS NOT NULL AND query IS NOT NULL AND timestamp >= ?1",
+            [since],
+            |row| row.get(0),
+        )
+    }
 
-/// Check if the app cache exists and has entries
-#[allow(dead_code)]
-pub fn has_app_cache() -> bool {
-    if let Ok(conn) = Connection::open("history.db") {
-        if let Ok(count) =
-            conn.query_row::<i32, _, _>("SELECT COUNT(*) FROM app_cache", [], |row| row.get(0))
-        {
-            return count > 0;
+    /// Number of dated launches in each hour of the day (local time)
+    pub fn launch_counts_by_hour(&self) -> SqlResult<[i32; 24]> {
+        let mut stmt = self.conn.prepare(
+            "SELECT CAST(strftime('%H', timestamp, 'unixepoch', 'localtime') AS INTEGER), COUNT(*)
+             FROM launch_events WHERE timestamp > 0 GROUP BY 1",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, usize>(0)?, row.get::<_, i32>(1)?))
+        })?;
+        let mut hours = [0; 24];
+        for row in rows {
+            let (hour, count) = row?;
+            if let Some(slot) = hours.get_mut(hour) {
+                *slot = count;
+            }
         }
+        Ok(hours)
     }
-    false
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+    /// Replace the cached app list, tagged with the scanner version that produced it.
+    ///
+    /// The whole replacement is one transaction: if the process dies or a row
+    /// fails part way through, the previous cache is left as it was.
+    pub fn save_app_cache(&mut self, apps: &[AppEntry], scanner_version: u32) -> SqlResult<()> {
+        let tx = self.conn.transaction()?;
 
-    #[test]
-    fn test_init_db() {
-        let test_db = "test_history.db";
-        // Clean up any existing test database
-        let _ = fs::remove_file(test_db);
-
-        // Create a test database
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_usage (
-                path TEXT PRIMARY KEY,
-                count INTEGER
-            )",
-            [],
-        )
-        .unwrap();
+        // Clear existing cache
+        tx.execute("DELETE FROM app_cache", [])?;
 
-        // Verify table exists
-        let result: Result<i32, _> = conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='app_usage'",
-            [],
-            |row| row.get(0),
-        );
+        // Insert all apps
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO app_cache (parse_name, name, arguments, icon_index, entry_type,
+                                        icon_path, shortcut_path, source_mtime, broken, folder,
+                                        description, working_dir, icon_resource, show_cmd, hotkey,
+                                        uninstall, keywords, uninstall_name)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                         ?17, ?18)",
+            )?;
 
-        assert_eq!(result.unwrap(), 1);
+            for app in apps {
+                let entry_type = match app.entry_type {
+                    AppEntryType::Application => "Application",
+                    AppEntryType::Settings => "Settings",
+                };
+                stmt.execute(params![
+                    app.parse_name,
+                    app.name,
+                    app.arguments,
+                    app.icon_index,
+                    entry_type,
+                    app.icon_path,
+                    app.shortcut_path,
+                    app.source_mtime,
+                    app.broken,
+                    app.folder,
+                    app.description,
+                    app.working_dir,
+                    app.icon_resource,
+                    app.show_cmd,
+                    app.hotkey,
+                    app.uninstall,
+                    app.keywords,
+                    app.uninstall_name
+                ])?;
+            }
 
-        // Clean up
-        let _ = fs::remove_file(test_db);
+            let mut meta = tx
+                .prepare_cached("INSERT OR REPLACE INTO cache_meta (key, value) VALUES (?1, ?2)")?;
+            meta.execute(params![
+                "schema_version",
+                migrations::latest_version().to_string()
+            ])?;
+            meta.execute(params!["scanner_version", scanner_version.to_string()])?;
+        }
+
+        tx.commit()
     }
 
-    #[test]
-    fn test_increment_usage() {
-        let test_db = "test_increment.db";
-        let _ = fs::remove_file(test_db);
-
-        // Initialize database
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_usage (
-                path TEXT PRIMARY KEY,
-                count INTEGER
-            )",
-            [],
-        )
-        .unwrap();
-        drop(conn);
-
-        // Test increment
-        let test_path = "test_app";
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute(
-            "INSERT INTO app_usage (path, count) VALUES (?1, 1)
-             ON CONFLICT(path) DO UPDATE SET count = count + 1",
-            params![test_path],
+    /// Load all apps from the cache database.
+    ///
+    /// Returns nothing if the cache was written by a different schema or scanner
+    /// version, so stale entries are never shown.
+    pub fn load_app_cache(&self, scanner_version: u32) -> SqlResult<Vec<AppEntry>> {
+        if !self.cache_is_current(scanner_version)? {
+            return Ok(Vec::new());
+        }
+
+        let usage_map = self.load_usage_map()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT parse_name, name, arguments, icon_index, entry_type,
+                    icon_path, shortcut_path, source_mtime, broken, folder,
+                    description, working_dir, icon_resource, show_cmd, hotkey, uninstall,
+                    keywords, uninstall_name
+             FROM app_cache",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let parse_name: String = row.get(0)?;
+            let name: String = row.get(1)?;
+            let entry_type: String = row.get(4)?;
+
+            let mut app = if entry_type == "Settings" {
+                AppEntry::new_settings(name, parse_name, row.get(3)?)
+            } else {
+                let usage_count = *usage_map.get(&parse_name).unwrap_or(&0);
+                AppEntry::new_with_args(name, parse_name, row.get(2)?, row.get(3)?, usage_count)
+            };
+            app.icon_path = row.get(5)?;
+            app.shortcut_path = row.get(6)?;
+            app.source_mtime = row.get(7)?;
+            app.broken = row.get(8)?;
+            app.folder = row.get(9)?;
+            app.description = row.get(10)?;
+            app.working_dir = row.get(11)?;
+            app.icon_resource = row.get(12)?;
+            app.show_cmd = row.get(13)?;
+            app.hotkey = row.get(14)?;
+            app.uninstall = row.get(15)?;
+            app.keywords = row.get(16)?;
+            app.uninstall_name = row.get(17)?;
+            Ok(app)
+        })?;
+        rows.collect()
+    }
+
+    /// Whether the cache was written by this schema and scanner version
+    fn cache_is_current(&self, scanner_version: u32) -> SqlResult<bool> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT value FROM cache_meta WHERE key = ?1")?;
+        let mut read = |key: &str| -> SqlResult<Option<String>> {
+            stmt.query_row(params![key], |row| row.get(0)).optional()
+        };
+
+        Ok(
+            read("schema_version")? == Some(migrations::latest_version().to_string())
+                && read("scanner_version")? == Some(scanner_version.to_string()),
         )
-        .unwrap();
+    }
+
+    /// Check if the app cache exists and has entries
+    #[allow(dead_code)]
+    pub fn has_app_cache(&self) -> bool {
+        self.conn
+            .query_row::<i32, _, _>("SELECT COUNT(*) FROM app_cache", [], |row| row.get(0))
+            .map(|count| count > 0)
+            .unwrap_or(false)
+    }
 
-        let count: i32 = conn
+    /// Replace the record of Start Menu files seen by the last scan
+    pub fn save_scan_files(
+        &mut self,
+        files: &[ScannedFile],
+        scanner_version: u32,
+    ) -> SqlResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM scan_files", [])?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO scan_files
+                    (path, size, mtime, name, parse_name, arguments, icon_path, folder,
+                     description, working_dir, icon_resource, show_cmd, hotkey, keywords)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            )?;
+            for file in files {
+                let entry = file.entry.as_ref();
+                stmt.execute(params![
+                    file.path,
+                    file.size,
+                    file.mtime,
+                    entry.map(|app| &app.name),
+                    entry.map(|app| &app.parse_name),
+                    entry.and_then(|app| app.arguments.as_ref()),
+                    entry.and_then(|app| app.icon_path.as_ref()),
+                    entry.and_then(|app| app.folder.as_ref()),
+                    entry.and_then(|app| app.description.as_ref()),
+                    entry.and_then(|app| app.working_dir.as_ref()),
+                    entry.map_or(0, |app| app.icon_resource),
+                    entry.and_then(|app| app.show_cmd),
+                    entry.and_then(|app| app.hotkey),
+                    entry.and_then(|app| app.keywords.as_ref())
+                ])?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO cache_meta (key, value) VALUES ('scan_files_version', ?1)",
+            [scanner_version.to_string()],
+        )?;
+        tx.commit()
+    }
+
+    /// Load the files seen by the last scan, or nothing if another scanner version wrote them.
+    /// Usage counts and icon indices are left at zero; the scan looks the icons up again.
+    pub fn load_scan_files(&self, scanner_version: u32) -> SqlResult<Vec<ScannedFile>> {
+        let version: Option<String> = self
+            .conn
             .query_row(
-                "SELECT count FROM app_usage WHERE path = ?1",
-                params![test_path],
+                "SELECT value FROM cache_meta WHERE key = 'scan_files_version'",
+                [],
                 |row| row.get(0),
             )
-            .unwrap();
+            .optional()?;
+        if version != Some(scanner_version.to_string()) {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT path, size, mtime, name, parse_name, arguments, icon_path, folder,
+                    description, working_dir, icon_resource, show_cmd, hotkey, keywords
+             FROM scan_files",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let path: String = row.get(0)?;
+            let mtime: i64 = row.get(2)?;
+            let name: Option<String> = row.get(3)?;
+            let parse_name: Option<String> = row.get(4)?;
+
+            let entry = match (name, parse_name) {
+                (Some(name), Some(parse_name)) => {
+                    let mut app = AppEntry::new_with_args(name, parse_name, row.get(5)?, 0, 0);
+                    app.icon_path = row.get(6)?;
+                    app.folder = row.get(7)?;
+                    app.description = row.get(8)?;
+                    app.working_dir = row.get(9)?;
+                    app.icon_resource = row.get(10)?;
+                    app.show_cmd = row.get(11)?;
+                    app.hotkey = row.get(12)?;
+                    app.keywords = row.get(13)?;
+                    app.shortcut_path = Some(path.clone());
+                    app.source_mtime = Some(mtime);
+                    Some(app)
+                }
+                _ => None,
+            };
+            Ok(ScannedFile {
+                path,
+                size: row.get(1)?,
+                mtime,
+                entry,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+/// Connection settings applied before anything else touches the database
+fn configure(conn: &Connection) -> SqlResult<()> {
+    // Wait for a concurrent writer (e.g. a CLI command) instead of failing
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    // WAL lets the UI read while the scan thread writes; in-memory databases keep "memory"
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    // Safe with WAL: a crash may lose the last commit but never corrupts the file
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    conn.pragma_update(None, "temp_store", "MEMORY")?;
+    Ok(())
+}
+
+impl Database {
+    /// Collect everything the user has taught Oxistart, for `oxistart export`
+    pub fn export_user_data(&self) -> SqlResult<UserData> {
+        let mut data = UserData::new(now_timestamp());
+
+        let mut usage = self
+            .load_usage_map()?
+            .into_iter()
+            .map(|(entry_id, count)| UsageCount { entry_id, count })
+            .collect::<Vec<_>>();
+        usage.sort_by(|a, b| a.entry_id.cmp(&b.entry_id));
+        data.usage = usage;
+
+        // Launches made here are exported under this machine's id and their
+        // sync sequence number, so importing them elsewhere and syncing
+        // recognizes them as the same launches
+        let own_id = self.machine_id()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, entry_id, action, query, rank, origin, origin_seq
+             FROM launch_events ORDER BY timestamp, id",
+        )?;
+        data.launch_events = stmt
+            .query_map([], |row| {
+                let action: String = row.get(3)?;
+                let origin: Option<String> = row.get(6)?;
+                let (origin, origin_seq) = match origin {
+                    Some(origin) => (origin, row.get(7)?),
+                    None => (own_id.clone(), row.get(0)?),
+                };
+                Ok(ExportedEvent {
+                    event: LaunchEvent {
+                        timestamp: row.get(1)?,
+                        entry_id: row.get(2)?,
+                        action: LaunchAction::parse(&action).unwrap_or(LaunchAction::Open),
+                        query: row.get(4)?,
+                        rank: row.get(5)?,
+                    },
+                    origin: Some(origin),
+                    origin_seq: Some(origin_seq),
+                })
+            })?
+            .collect::<SqlResult<_>>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT entry_id FROM pins ORDER BY position, entry_id")?;
+        data.pins = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<_>>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT alias, entry_id FROM aliases ORDER BY alias")?;
+        data.aliases = stmt
+            .query_map([], |row| {
+                Ok(Alias {
+                    alias: row.get(0)?,
+                    entry_id: row.get(1)?,
+                })
+            })?
+            .collect::<SqlResult<_>>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT entry_id FROM hidden ORDER BY entry_id")?;
+        data.hidden = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<_>>()?;
 
-        assert_eq!(count, 1);
+        Ok(data)
+    }
+
+    /// Load exported user data.
+    ///
+    /// `Replace` discards the local data first. `Merge` adds usage counts
+    /// together and takes the union of launch events, pins, aliases and hidden
+    /// entries; local aliases win over imported ones with the same name.
+    /// Launches already here, whether imported or synced before, are not
+    /// counted again.
+    pub fn import_user_data(&mut self, data: &UserData, mode: ImportMode) -> SqlResult<()> {
+        let own_id = self.machine_id()?;
+        let tx = self.conn.transaction()?;
+
+        if mode == ImportMode::Replace {
+            tx.execute_batch(
+                "DELETE FROM app_usage;
+                 DELETE FROM launch_events;
+                 DELETE FROM pins;
+                 DELETE FROM aliases;
+                 DELETE FROM hidden;",
+            )?;
+        }
+
+        let known = import_launch_events(&tx, &data.launch_events, &own_id)?;
+
+        for usage in &data.usage {
+            let count = usage.count - known.get(&usage.entry_id).copied().unwrap_or(0);
+            if count <= 0 {
+                continue;
+            }
+            tx.prepare_cached(
+                "INSERT INTO app_usage (path, count) VALUES (?1, ?2)
+                 ON CONFLICT(path) DO UPDATE SET count = count + excluded.count",
+            )?
+            .execute(params![usage.entry_id, count])?;
+        }
+
+        for entry_id in &data.pins {
+            tx.prepare_cached(
+                "INSERT OR IGNORE INTO pins (entry_id, position)
+                 VALUES (?1, (SELECT COALESCE(MAX(position), -1) + 1 FROM pins))",
+            )?
+            .execute(params![entry_id])?;
+        }
+
+        for alias in &data.aliases {
+            tx.prepare_cached("INSERT OR IGNORE INTO aliases (alias, entry_id) VALUES (?1, ?2)")?
+                .execute(params![alias.alias, alias.entry_id])?;
+        }
+
+        for entry_id in &data.hidden {
+            tx.prepare_cached("INSERT OR IGNORE INTO hidden (entry_id) VALUES (?1)")?
+                .execute(params![entry_id])?;
+        }
+
+        tx.commit()
+    }
+}
+
+/// Add another machine's event unless it is here already or was pruned
+const INSERT_FOREIGN_EVENT: &str = "INSERT OR IGNORE INTO launch_events
+        (timestamp, entry_id, action, query, rank, origin, origin_seq)
+    SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+    WHERE NOT EXISTS (SELECT 1 FROM pruned_events WHERE origin = ?6 AND origin_seq = ?7)";
+
+/// Columns that identify an imported launch event of unknown origin
+type EventKey<'a> = (i64, &'a str, &'a str, Option<&'a str>, Option<i32>);
+
+/// Add launch events as a union with the existing history, returning how many
+/// imported events of each entry were already there (and so already counted).
+///
+/// Events with an origin are keyed on it like synced ones: this machine's own
+/// go back under their original id as local events, other machines' are
+/// stored as theirs so they are never published as launches made here.
+/// Events of unknown origin (version 1 exports) are compared by their columns:
+/// one that appears n times in the import ends up at least n times locally.
+fn import_launch_events(
+    tx: &Transaction,
+    events: &[ExportedEvent],
+    own_id: &str,
+) -> SqlResult<HashMap<String, i32>> {
+    let mut known: HashMap<String, i32> = HashMap::new();
+    let mut wanted: HashMap<EventKey, usize> = HashMap::new();
+    for exported in events {
+        let event = &exported.event;
+        let (Some(origin), Some(seq)) = (&exported.origin, exported.origin_seq) else {
+            let key = (
+                event.timestamp,
+                event.entry_id.as_str(),
+                event.action.as_str(),
+                event.query.as_deref(),
+                event.rank,
+            );
+            *wanted.entry(key).or_insert(0) += 1;
+            continue;
+        };
+
+        let (sql, origin) = if origin == own_id {
+            (
+                "INSERT OR IGNORE INTO launch_events
+                    (id, timestamp, entry_id, action, query, rank, origin, origin_seq)
+                 VALUES (?7, ?1, ?2, ?3, ?4, ?5, ?6, NULL)",
+                None,
+            )
+        } else {
+            (INSERT_FOREIGN_EVENT, Some(origin))
+        };
+        let inserted = tx.prepare_cached(sql)?.execute(params![
+            event.timestamp,
+            event.entry_id,
+            event.action.as_str(),
+            event.query,
+            event.rank,
+            origin,
+            seq
+        ])?;
+        if inserted == 0 {
+            *known.entry(event.entry_id.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let mut ordered = wanted.into_iter().collect::<Vec<_>>();
+    ordered.sort();
+
+    for ((timestamp, entry_id, action, query, rank), count) in ordered {
+        let existing: usize = tx
+            .prepare_cached(
+                "SELECT COUNT(*) FROM launch_events
+                 WHERE timestamp = ?1 AND entry_id = ?2 AND action = ?3
+                   AND query IS ?4 AND rank IS ?5",
+            )?
+            .query_row(params![timestamp, entry_id, action, query, rank], |row| {
+                row.get(0)
+            })?;
+
+        for _ in existing..count {
+            tx.prepare_cached(
+                "INSERT INTO launch_events (timestamp, entry_id, action, query, rank)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?
+            .execute(params![timestamp, entry_id, action, query, rank])?;
+        }
+        *known.entry(entry_id.to_string()).or_insert(0) += existing.min(count) as i32;
+    }
+
+    Ok(known)
+}
+
+impl Database {
+    /// Id of this machine, created on first use
+    pub fn machine_id(&self) -> SqlResult<String> {
+        if let Some(id) = self.meta_value(MACHINE_ID_KEY)? {
+            return Ok(id);
+        }
+
+        // RandomState is seeded from the OS, which is all the uniqueness we need
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        hasher.write_u32(std::process::id());
+        let id = format!("{:016x}", hasher.finish());
+
+        self.set_meta_value(MACHINE_ID_KEY, &id)?;
+        Ok(id)
+    }
+
+    /// Read a value from the `meta` table
+    pub fn meta_value(&self, key: &str) -> SqlResult<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+    }
+
+    /// Store a value in the `meta` table, replacing any previous one
+    pub fn set_meta_value(&self, key: &str, value: &str) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Events launched on this machine with a sequence number above `seq`,
+    /// paired with that number, oldest first
+    pub fn load_local_events_after(&self, seq: i64) -> SqlResult<Vec<(i64, LaunchEvent)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, entry_id, action, query, rank FROM launch_events
+             WHERE origin IS NULL AND id > ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([seq], |row| {
+            let action: String = row.get(3)?;
+            Ok((
+                row.get(0)?,
+                LaunchEvent {
+                    timestamp: row.get(1)?,
+                    entry_id: row.get(2)?,
+                    action: LaunchAction::parse(&action).unwrap_or(LaunchAction::Open),
+                    query: row.get(4)?,
+                    rank: row.get(5)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// Add events from another machine's log and bump usage counts for them.
+    ///
+    /// Events are keyed on `(origin, seq)`, so merging the same log again
+    /// changes nothing, nor does merging events pruned here before. Returns
+    /// how many events were new.
+    pub fn merge_foreign_events(
+        &mut self,
+        origin: &str,
+        events: &[(i64, LaunchEvent)],
+    ) -> SqlResult<usize> {
+        let tx = self.conn.transaction()?;
+        let mut merged = 0;
+
+        for (seq, event) in events {
+            let inserted = tx.prepare_cached(INSERT_FOREIGN_EVENT)?.execute(params![
+                event.timestamp,
+                event.entry_id,
+                event.action.as_str(),
+                event.query,
+                event.rank,
+                origin,
+                seq
+            ])?;
+            if inserted > 0 {
+                increment_usage_in(&tx, &event.entry_id)?;
+                merged += 1;
+            }
+        }
+
+        tx.commit()?;
+        Ok(merged)
+    }
+}
 
-        // Increment again
-        conn.execute(
-            "INSERT INTO app_usage (path, count) VALUES (?1, 1)
-             ON CONFLICT(path) DO UPDATE SET count = count + 1",
-            params![test_path],
+impl Database {
+    /// The retention settings stored in the database
+    pub fn retention_policy(&self) -> SqlResult<RetentionPolicy> {
+        Ok(RetentionPolicy {
+            max_age_days: self
+                .meta_value(RETENTION_DAYS_KEY)?
+                .and_then(|days| days.parse().ok()),
+            prune_missing: self.meta_value(PRUNE_MISSING_KEY)?.as_deref() == Some("1"),
+        })
+    }
+
+    /// Store new retention settings; they take effect on the next `apply_retention`
+    pub fn set_retention_policy(&self, policy: &RetentionPolicy) -> SqlResult<()> {
+        match policy.max_age_days {
+            Some(days) => self.set_meta_value(RETENTION_DAYS_KEY, &days.to_string())?,
+            None => self.remove_meta_value(RETENTION_DAYS_KEY)?,
+        }
+        self.set_meta_value(
+            PRUNE_MISSING_KEY,
+            if policy.prune_missing { "1" } else { "0" },
         )
-        .unwrap();
+    }
+
+    /// Whether launches are currently kept out of the history
+    pub fn is_incognito(&self) -> bool {
+        self.meta_value(INCOGNITO_KEY).ok().flatten().as_deref() == Some("1")
+    }
+
+    /// Turn incognito mode on or off; it stays on across restarts until turned off
+    pub fn set_incognito(&self, incognito: bool) -> SqlResult<()> {
+        if incognito {
+            self.set_meta_value(INCOGNITO_KEY, "1")
+        } else {
+            self.remove_meta_value(INCOGNITO_KEY)
+        }
+    }
+
+    /// Oldest timestamp the history may contain at `now`: launches before the
+    /// last clear or beyond the maximum age are not kept (or merged back in by sync)
+    pub fn history_floor(&self, now: i64) -> SqlResult<i64> {
+        let cleared_at = self
+            .meta_value(HISTORY_CLEARED_AT_KEY)?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(i64::MIN);
+        let max_age = self
+            .retention_policy()?
+            .max_age_days
+            .map(|days| now - i64::from(days) * SECONDS_PER_DAY)
+            .unwrap_or(i64::MIN);
+        Ok(cleared_at.max(max_age))
+    }
+
+    /// Forget launches older than the maximum age and, if enabled, entries whose
+    /// file `exists` says is gone. Usage counts drop along with their events.
+    pub fn apply_retention(
+        &mut self,
+        now: i64,
+        exists: impl Fn(&Path) -> bool,
+    ) -> SqlResult<PruneReport> {
+        let policy = self.retention_policy()?;
+        let floor = self.history_floor(now)?;
+        let tx = self.conn.transaction()?;
+        let mut report = PruneReport::default();
+
+        if floor > i64::MIN {
+            tx.execute(
+                "UPDATE app_usage SET count = count - (
+                    SELECT COUNT(*) FROM launch_events
+                    WHERE entry_id = app_usage.path AND timestamp < ?1
+                )",
+                [floor],
+            )?;
+            report.expired_events =
+                tx.execute("DELETE FROM launch_events WHERE timestamp < ?1", [floor])?;
+            tx.execute("DELETE FROM app_usage WHERE count <= 0", [])?;
+        }
+
+        if policy.prune_missing {
+            let entries = tx
+                .prepare("SELECT path FROM app_usage UNION SELECT entry_id FROM launch_events")?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<SqlResult<Vec<_>>>()?;
+
+            for entry_id in entries {
+                if is_file_path(&entry_id) && !exists(Path::new(&entry_id)) {
+                    // Other machines still have these in their logs
+                    tx.execute(
+                        "INSERT OR IGNORE INTO pruned_events (origin, origin_seq)
+                         SELECT origin, origin_seq FROM launch_events
+                         WHERE entry_id = ?1 AND origin IS NOT NULL",
+                        [&entry_id],
+                    )?;
+                    tx.execute("DELETE FROM app_usage WHERE path = ?1", [&entry_id])?;
+                    tx.execute("DELETE FROM launch_events WHERE entry_id = ?1", [&entry_id])?;
+                    report.missing_entries += 1;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Give entries a new id wherever one is stored (usage, history, pins,
+    /// aliases, hidden), e.g. once a stored target can be expanded. `rename`
+    /// returns the new id, or `None` to keep it; returns how many ids changed.
+    pub fn rename_entry_ids(
+        &mut self,
+        rename: impl Fn(&str) -> Option<String>,
+    ) -> SqlResult<usize> {
+        let tx = self.conn.transaction()?;
+        let renames = {
+            let mut stmt = tx.prepare(
+                "SELECT path FROM app_usage
+                 UNION SELECT entry_id FROM launch_events
+                 UNION SELECT entry_id FROM pins
+                 UNION SELECT entry_id FROM aliases
+                 UNION SELECT entry_id FROM hidden",
+            )?;
+            let ids = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            ids.into_iter()
+                .filter_map(|id| rename(&id).filter(|new| *new != id).map(|new| (id, new)))
+                .collect::<Vec<_>>()
+        };
+
+        for (old, new) in &renames {
+            // Counts of two ids that turn out to be the same entry add up
+            tx.execute(
+                "INSERT INTO app_usage (path, count)
+                 SELECT ?2, count FROM app_usage WHERE path = ?1
+                 ON CONFLICT(path) DO UPDATE SET count = count + excluded.count",
+                params![old, new],
+            )?;
+            tx.execute("DELETE FROM app_usage WHERE path = ?1", params![old])?;
+            tx.execute(
+                "UPDATE launch_events SET entry_id = ?2 WHERE entry_id = ?1",
+                params![old, new],
+            )?;
+            tx.execute(
+                "UPDATE OR IGNORE pins SET entry_id = ?2 WHERE entry_id = ?1",
+                params![old, new],
+            )?;
+            tx.execute("DELETE FROM pins WHERE entry_id = ?1", params![old])?;
+            tx.execute(
+                "UPDATE aliases SET entry_id = ?2 WHERE entry_id = ?1",
+                params![old, new],
+            )?;
+            tx.execute(
+                "UPDATE OR IGNORE hidden SET entry_id = ?2 WHERE entry_id = ?1",
+                params![old, new],
+            )?;
+            tx.execute("DELETE FROM hidden WHERE entry_id = ?1", params![old])?;
+        }
+
+        tx.commit()?;
+        Ok(renames.len())
+    }
 
-        let count: i32 = conn
+    /// Forget every launch and usage count. Pins, aliases and hidden entries stay.
+    pub fn clear_history(&mut self, now: i64) -> SqlResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute_batch(
+            "DELETE FROM app_usage;
+             DELETE FROM launch_events;",
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![HISTORY_CLEARED_AT_KEY, now.to_string()],
+        )?;
+        tx.commit()
+    }
+
+    fn remove_meta_value(&self, key: &str) -> SqlResult<()> {
+        self.conn
+            .execute("DELETE FROM meta WHERE key = ?1", [key])?;
+        Ok(())
+    }
+}
+
+/// Whether an entry id names a file on disk (as opposed to a URL or shell item)
+fn is_file_path(entry_id: &str) -> bool {
+    let bytes = entry_id.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    drive || entry_id.starts_with("\\\\")
+}
+
+fn increment_usage_in(conn: &Connection, path: &str) -> SqlResult<()> {
+    conn.prepare_cached(
+        "INSERT INTO app_usage (path, count) VALUES (?1, 1)
+         ON CONFLICT(path) DO UPDATE SET count = count + 1",
+    )?
+    .execute(params![path])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_count(db: &Database, name: &str) -> i32 {
+        db.conn
             .query_row(
-                "SELECT count FROM app_usage WHERE path = ?1",
-                params![test_path],
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
+                params![name],
                 |row| row.get(0),
             )
-            .unwrap();
+            .unwrap()
+    }
 
-        assert_eq!(count, 2);
+    #[test]
+    fn test_open_creates_schema() {
+        let db = Database::open_in_memory().unwrap();
 
-        // Clean up
-        let _ = fs::remove_file(test_db);
+        assert_eq!(table_count(&db, "app_usage"), 1);
+        assert_eq!(table_count(&db, "app_cache"), 1);
+        assert_eq!(table_count(&db, "launch_events"), 1);
     }
 
     #[test]
-    fn test_app_cache_save_and_load() {
-        let test_db = "test_cache.db";
-        let _ = fs::remove_file(test_db);
-
-        // Initialize database with app_cache table
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_usage (
-                path TEXT PRIMARY KEY,
-                count INTEGER
-            )",
-            [],
-        )
-        .unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_cache (
-                parse_name TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                icon_index INTEGER NOT NULL,
-                entry_type TEXT NOT NULL
-            )",
-            [],
-        )
+    fn test_open_file_in_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(DB_FILE_NAME);
+
+        let mut db = Database::open(&path).unwrap();
+        db.record_launch(&LaunchEvent::now(
+            "test_app",
+            LaunchAction::Open,
+            None,
+            None,
+        ))
         .unwrap();
-        drop(conn);
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.load_usage_map().unwrap().get("test_app"), Some(&1));
+    }
+
+    #[test]
+    fn test_app_cache_save_and_load() {
+        let mut db = Database::open_in_memory().unwrap();
 
         // Create test apps
         let apps = vec![
@@ -276,64 +1071,17 @@ mod tests {
             ),
         ];
 
-        // Save to cache (using test db directly)
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute("DELETE FROM app_cache", []).unwrap();
-
-        let mut stmt = conn.prepare(
-            "INSERT INTO app_cache (parse_name, name, icon_index, entry_type) VALUES (?1, ?2, ?3, ?4)",
-        ).unwrap();
-
-        for app in &apps {
-            let entry_type = match app.entry_type {
-                AppEntryType::Application => "Application",
-                AppEntryType::Settings => "Settings",
-            };
-            stmt.execute(params![
-                app.parse_name,
-                app.name,
-                app.icon_index,
-                entry_type
-            ])
-            .unwrap();
-        }
-        drop(stmt);
-        drop(conn);
-
-        // Load from cache
-        let conn = Connection::open(test_db).unwrap();
-        let mut loaded_apps = Vec::new();
-
-        let mut stmt = conn
-            .prepare("SELECT parse_name, name, icon_index, entry_type FROM app_cache")
-            .unwrap();
-        let rows = stmt
-            .query_map([], |row| {
-                let parse_name: String = row.get(0)?;
-                let name: String = row.get(1)?;
-                let icon_index: i32 = row.get(2)?;
-                let entry_type: String = row.get(3)?;
-                Ok((parse_name, name, icon_index, entry_type))
-            })
-            .unwrap();
-
-        for row in rows.flatten() {
-            let (parse_name, name, icon_index, entry_type) = row;
-            let app = if entry_type == "Settings" {
-                AppEntry::new_settings(name, parse_name, icon_index)
-            } else {
-                AppEntry::new(name, parse_name, icon_index, 0)
-            };
-            loaded_apps.push(app);
-        }
+        db.save_app_cache(&apps, 1).unwrap();
+        let loaded_apps = db.load_app_cache(1).unwrap();
 
         // Verify loaded apps
         assert_eq!(loaded_apps.len(), 3);
 
-        // Find and verify each app
+        // Find and verify each app; usage comes from app_usage, not the cache
         let app1 = loaded_apps.iter().find(|a| a.name == "Test App 1").unwrap();
         assert_eq!(app1.parse_name, "path/to/app1.exe");
         assert_eq!(app1.icon_index, 1);
+        assert_eq!(app1.usage_count, 0);
         assert_eq!(app1.entry_type, AppEntryType::Application);
 
         let app2 = loaded_apps.iter().find(|a| a.name == "Test App 2").unwrap();
@@ -348,72 +1096,624 @@ mod tests {
         assert_eq!(settings.parse_name, "ms-settings:display");
         assert_eq!(settings.icon_index, 100);
         assert_eq!(settings.entry_type, AppEntryType::Settings);
-
-        // Clean up
-        let _ = fs::remove_file(test_db);
     }
 
     #[test]
     fn test_has_app_cache_empty() {
-        let test_db = "test_has_cache_empty.db";
-        let _ = fs::remove_file(test_db);
-
-        // Initialize database with empty app_cache table
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_cache (
-                parse_name TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                icon_index INTEGER NOT NULL,
-                entry_type TEXT NOT NULL
-            )",
-            [],
+        let db = Database::open_in_memory().unwrap();
+        assert!(!db.has_app_cache());
+    }
+
+    #[test]
+    fn test_has_app_cache_with_entries() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_app_cache(
+            &[AppEntry::new(
+                "Test App".to_string(),
+                "test_path".to_string(),
+                1,
+                0,
+            )],
+            1,
         )
         .unwrap();
 
-        // Check if cache is empty
-        let count: i32 = conn
-            .query_row("SELECT COUNT(*) FROM app_cache", [], |row| row.get(0))
-            .unwrap();
+        assert!(db.has_app_cache());
+    }
+
+    #[test]
+    fn test_record_launch_updates_count_and_history() {
+        let mut db = Database::open_in_memory().unwrap();
+
+        let event = LaunchEvent {
+            timestamp: 1_700_000_000,
+            entry_id: "C:\\app.exe".to_string(),
+            action: LaunchAction::Admin,
+            query: Some("app".to_string()),
+            rank: Some(2),
+        };
+        db.record_launch(&event).unwrap();
+        db.record_launch(&event).unwrap();
 
-        assert_eq!(count, 0);
+        assert_eq!(db.load_usage_map().unwrap().get(&event.entry_id), Some(&2));
 
-        // Clean up
-        let _ = fs::remove_file(test_db);
+        let events = db.load_launch_events(None).unwrap();
+        assert_eq!(events, vec![event.clone(), event]);
     }
 
     #[test]
-    fn test_has_app_cache_with_entries() {
-        let test_db = "test_has_cache_entries.db";
-        let _ = fs::remove_file(test_db);
-
-        // Initialize database and add an entry
-        let conn = Connection::open(test_db).unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_cache (
-                parse_name TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                icon_index INTEGER NOT NULL,
-                entry_type TEXT NOT NULL
-            )",
-            [],
+    fn test_existing_counts_migrate_into_launch_events() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app_usage (path TEXT PRIMARY KEY, count INTEGER);
+             INSERT INTO app_usage (path, count) VALUES ('a', 3), ('b', 1);",
         )
         .unwrap();
 
-        conn.execute(
-            "INSERT INTO app_cache (parse_name, name, icon_index, entry_type) VALUES (?1, ?2, ?3, ?4)",
-            params!["test_path", "Test App", 1, "Application"],
-        )
+        let db = Database::from_connection(conn).unwrap();
+
+        let counts = db.launch_counts_since(i64::MIN).unwrap();
+        assert_eq!(counts.get("a"), Some(&3));
+        assert_eq!(counts.get("b"), Some(&1));
+
+        let events = db.load_launch_events(None).unwrap();
+        assert!(events
+            .iter()
+            .all(|e| e.timestamp == 0 && e.action == LaunchAction::Open && e.query.is_none()));
+    }
+
+    #[test]
+    fn test_launch_aggregations() {
+        let mut db = Database::open_in_memory().unwrap();
+
+        let events = [
+            (100, "a", LaunchAction::Open, Some("a"), Some(0)),
+            (200, "a", LaunchAction::Location, Some("ap"), Some(3)),
+            (300, "b", LaunchAction::Open, None, Some(5)),
+        ];
+        for (timestamp, entry_id, action, query, rank) in events {
+            let event = LaunchEvent {
+                timestamp,
+                entry_id: entry_id.to_string(),
+                action,
+                query: query.map(|q| q.to_string()),
+                rank,
+            };
+            db.record_launch(&event).unwrap();
+        }
+
+        let recent = db.launch_counts_since(150).unwrap();
+        assert_eq!(recent.get("a"), Some(&1));
+        assert_eq!(recent.get("b"), Some(&1));
+
+        let by_action = db.launch_counts_by_action().unwrap();
+        assert_eq!(by_action.get(&LaunchAction::Open), Some(&2));
+        assert_eq!(by_action.get(&LaunchAction::Location), Some(&1));
+        assert_eq!(by_action.get(&LaunchAction::Admin), None);
+
+        // Only launches made from a search count towards the average rank
+        assert_eq!(db.average_launch_rank(i64::MIN).unwrap(), Some(1.5));
+        assert_eq!(db.average_launch_rank(150).unwrap(), Some(3.0));
+        assert_eq!(db.average_launch_rank(400).unwrap(), None);
+
+        // Hours depend on the local time zone, but every dated launch lands somewhere
+        assert_eq!(db.launch_counts_by_hour().unwrap().iter().sum::<i32>(), 3);
+
+        assert_eq!(db.load_launch_events(Some(200)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_launch_event_now_drops_empty_query() {
+        let event = LaunchEvent::now("a", LaunchAction::Open, Some(""), Some(0));
+        assert_eq!(event.query, None);
+        assert!(event.timestamp > 0);
+    }
+
+    fn sample_cache() -> Vec<AppEntry> {
+        let mut chrome = AppEntry::new_with_args(
+            "Chrome (Work)".to_string(),
+            "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe".to_string(),
+            Some("--profile-directory=\"Profile 1\"".to_string()),
+            7,
+            0,
+        );
+        chrome.icon_path = Some("C:\\Users\\me\\chrome_profile.ico".to_string());
+        chrome.shortcut_path = Some("C:\\Start Menu\\Chrome (Work).lnk".to_string());
+        chrome.source_mtime = Some(1_700_000_123);
+        chrome.broken = true;
+        chrome.folder = Some("Google\\Profiles".to_string());
+        chrome.description = Some("Access the Internet".to_string());
+        chrome.working_dir = Some("C:\\Program Files\\Google\\Chrome\\Application".to_string());
+        chrome.icon_resource = 3;
+        chrome.show_cmd = Some(3);
+        chrome.hotkey = Some(0x0643);
+        chrome.uninstall =
+            Some("\"C:\\Program Files\\Google\\Chrome\\setup.exe\" --uninstall".to_string());
+        chrome.uninstall_name = Some("Google Chrome".to_string());
+        chrome.keywords = Some("Google Chrome, Google LLC".to_string());
+
+        vec![
+            chrome,
+            AppEntry::new_settings(
+                "Display settings".to_string(),
+                "ms-settings:display".to_string(),
+                -1,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_app_cache_round_trips_every_field() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.record_launch(&LaunchEvent::now(
+            &sample_cache()[0].parse_name,
+            LaunchAction::Open,
+            None,
+            None,
+        ))
         .unwrap();
 
-        // Check if cache has entries
-        let count: i32 = conn
-            .query_row("SELECT COUNT(*) FROM app_cache", [], |row| row.get(0))
+        let apps = sample_cache();
+        db.save_app_cache(&apps, 1).unwrap();
+
+        let loaded = db.load_app_cache(1).unwrap();
+        assert_eq!(loaded.len(), 2);
+
+        let chrome = loaded.iter().find(|a| a.name == "Chrome (Work)").unwrap();
+        assert_eq!(
+            chrome,
+            &AppEntry {
+                usage_count: 1,
+                ..apps[0].clone()
+            }
+        );
+
+        let settings = loaded
+            .iter()
+            .find(|a| a.name == "Display settings")
+            .unwrap();
+        assert_eq!(settings, &apps[1]);
+    }
+
+    #[test]
+    fn test_app_cache_invalidated_by_scanner_version() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_app_cache(&sample_cache(), 1).unwrap();
+
+        assert!(db.load_app_cache(2).unwrap().is_empty());
+        assert_eq!(db.load_app_cache(1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_app_cache_invalidated_by_schema_version() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_app_cache(&sample_cache(), 1).unwrap();
+
+        db.conn
+            .execute(
+                "UPDATE cache_meta SET value = '0' WHERE key = 'schema_version'",
+                [],
+            )
             .unwrap();
+        assert!(db.load_app_cache(1).unwrap().is_empty());
+    }
 
-        assert!(count > 0);
+    #[test]
+    fn test_app_cache_without_metadata_is_ignored() {
+        let db = Database::open_in_memory().unwrap();
+        db.conn
+            .execute(
+                "INSERT INTO app_cache (parse_name, name, icon_index, entry_type)
+                 VALUES ('a.exe', 'A', 0, 'Application')",
+                [],
+            )
+            .unwrap();
+
+        assert!(db.load_app_cache(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_file_database_uses_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join(DB_FILE_NAME)).unwrap();
+
+        let mode: String = db
+            .conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_failed_cache_write_keeps_previous_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILE_NAME);
+        let mut db = Database::open(&path).unwrap();
+        db.save_app_cache(&sample_cache(), 1).unwrap();
+
+        // The duplicate parse_name makes the write fail after rows were already inserted
+        let mut broken = vec![AppEntry::new(
+            "New".to_string(),
+            "new.exe".to_string(),
+            0,
+            0,
+        )];
+        broken.push(broken[0].clone());
+        assert!(db.save_app_cache(&broken, 2).is_err());
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        let loaded = db.load_app_cache(1).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.iter().all(|a| a.parse_name != "new.exe"));
+    }
 
-        // Clean up
-        let _ = fs::remove_file(test_db);
+    #[test]
+    fn test_killed_cache_write_keeps_previous_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILE_NAME);
+        let mut db = Database::open(&path).unwrap();
+        db.save_app_cache(&sample_cache(), 1).unwrap();
+
+        // Stop half way through a replacement without committing, as a killed process would
+        let tx = db.conn.transaction().unwrap();
+        tx.execute("DELETE FROM app_cache", []).unwrap();
+        std::mem::forget(tx);
+        std::mem::forget(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.load_app_cache(1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_scan_files_round_trip() {
+        let mut db = Database::open_in_memory().unwrap();
+        let mut app = AppEntry::new_with_args(
+            "Code".to_string(),
+            "C:\\Code\\code.exe".to_string(),
+            Some("--new-window".to_string()),
+            7,
+            0,
+        );
+        app.icon_path = Some("C:\\Code\\code.ico".to_string());
+        app.shortcut_path = Some("C:\\Start Menu\\Code.lnk".to_string());
+        app.source_mtime = Some(1_700_000_000);
+        app.folder = Some("Programming".to_string());
+        app.description = Some("Code editing. Redefined.".to_string());
+        app.working_dir = Some("C:\\Code".to_string());
+        app.icon_resource = 1;
+        app.show_cmd = Some(7);
+        app.hotkey = Some(0x0443);
+        app.keywords = Some("Visual Studio Code, Microsoft Corporation".to_string());
+
+        let files = vec![
+            ScannedFile {
+                path: "C:\\Start Menu\\Code.lnk".to_string(),
+                size: 1234,
+                mtime: 1_700_000_000,
+                entry: Some(app),
+            },
+            ScannedFile {
+                path: "C:\\Start Menu\\Uninstall.lnk".to_string(),
+                size: 99,
+                mtime: 1_600_000_000,
+                entry: None,
+            },
+        ];
+        db.save_scan_files(&files, 1).unwrap();
+
+        let mut loaded = db.load_scan_files(1).unwrap();
+        loaded.sort_by(|a, b| a.path.cmp(&b.path));
+        // System image list indices belong to the process that asked for them
+        assert_eq!(loaded[0].entry.as_ref().unwrap().icon_index, 0);
+        let mut expected = files.clone();
+        expected[0].entry.as_mut().unwrap().icon_index = 0;
+        assert_eq!(loaded, expected);
+
+        // A different scanner version means everything gets reprocessed
+        assert!(db.load_scan_files(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_incognito_stops_recording() {
+        let mut db = Database::open_in_memory().unwrap();
+        assert!(!db.is_incognito());
+
+        db.set_incognito(true).unwrap();
+        db.record_launch(&LaunchEvent::now("a.exe", LaunchAction::Open, None, None))
+            .unwrap();
+        assert!(db.load_usage_map().unwrap().is_empty());
+        assert!(db.load_launch_events(None).unwrap().is_empty());
+
+        db.set_incognito(false).unwrap();
+        db.record_launch(&LaunchEvent::now("a.exe", LaunchAction::Open, None, None))
+            .unwrap();
+        assert_eq!(db.load_usage_map().unwrap().get("a.exe"), Some(&1));
+    }
+
+    fn launch_at(db: &mut Database, entry_id: &str, timestamp: i64) {
+        let mut event = LaunchEvent::now(entry_id, LaunchAction::Open, None, None);
+        event.timestamp = timestamp;
+        db.record_launch(&event).unwrap();
+    }
+
+    #[test]
+    fn test_retention_drops_old_events_and_their_counts() {
+        let mut db = Database::open_in_memory().unwrap();
+        let now = 100 * SECONDS_PER_DAY;
+        launch_at(&mut db, "old.exe", now - 40 * SECONDS_PER_DAY);
+        launch_at(&mut db, "both.exe", now - 40 * SECONDS_PER_DAY);
+        launch_at(&mut db, "both.exe", now - SECONDS_PER_DAY);
+
+        // Keeping everything by default
+        assert_eq!(
+            db.apply_retention(now, |_| true).unwrap(),
+            PruneReport::default()
+        );
+
+        db.set_retention_policy(&RetentionPolicy {
+            max_age_days: Some(30),
+            prune_missing: false,
+        })
+        .unwrap();
+        let report = db.apply_retention(now, |_| true).unwrap();
+        assert_eq!(report.expired_events, 2);
+
+        let usage = db.load_usage_map().unwrap();
+        assert_eq!(usage.get("old.exe"), None);
+        assert_eq!(usage.get("both.exe"), Some(&1));
+        assert_eq!(db.load_launch_events(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_retention_prunes_missing_files_only() {
+        let mut db = Database::open_in_memory().unwrap();
+        launch_at(&mut db, "C:\\Gone\\app.exe", 10);
+        launch_at(&mut db, "C:\\Here\\app.exe", 10);
+        launch_at(&mut db, "steam://rungameid/1", 10);
+
+        db.set_retention_policy(&RetentionPolicy {
+            max_age_days: None,
+            prune_missing: true,
+        })
+        .unwrap();
+        let report = db
+            .apply_retention(20, |path| !path.to_string_lossy().starts_with("C:\\Gone"))
+            .unwrap();
+        assert_eq!(report.missing_entries, 1);
+
+        let usage = db.load_usage_map().unwrap();
+        assert_eq!(usage.len(), 2);
+        assert!(!usage.contains_key("C:\\Gone\\app.exe"));
+    }
+
+    #[test]
+    fn test_retention_policy_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.retention_policy().unwrap(), RetentionPolicy::default());
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(90),
+            prune_missing: true,
+        };
+        db.set_retention_policy(&policy).unwrap();
+        assert_eq!(db.retention_policy().unwrap(), policy);
+
+        db.set_retention_policy(&RetentionPolicy::default())
+            .unwrap();
+        assert_eq!(db.retention_policy().unwrap(), RetentionPolicy::default());
+    }
+
+    #[test]
+    fn test_clear_history_keeps_user_lists() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.import_user_data(&sample_user_data(), ImportMode::Merge)
+            .unwrap();
+
+        db.clear_history(1_000).unwrap();
+        let data = db.export_user_data().unwrap();
+        assert!(data.usage.is_empty());
+        assert!(data.launch_events.is_empty());
+        assert!(!data.pins.is_empty());
+        assert_eq!(db.history_floor(1_000).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn test_rename_entry_ids() {
+        let mut db = Database::open_in_memory().unwrap();
+        let mut data = sample_user_data();
+        data.usage.push(UsageCount {
+            entry_id: "%Apps%\\a.exe".to_string(),
+            count: 3,
+        });
+        data.launch_events[1].event.entry_id = "%Apps%\\a.exe".to_string();
+        data.pins.push("%Apps%\\a.exe".to_string());
+        data.hidden.push("%Apps%\\junk.exe".to_string());
+        db.import_user_data(&data, ImportMode::Replace).unwrap();
+
+        let renamed = db
+            .rename_entry_ids(|id| id.strip_prefix("%Apps%\\").map(str::to_string))
+            .unwrap();
+        assert_eq!(renamed, 2);
+
+        let data = db.export_user_data().unwrap();
+        assert_eq!(
+            data.usage,
+            vec![UsageCount {
+                entry_id: "a.exe".to_string(),
+                count: 5
+            }]
+        );
+        assert!(data
+            .launch_events
+            .iter()
+            .all(|e| e.event.entry_id == "a.exe"));
+        assert_eq!(data.pins, vec!["a.exe".to_string(), "b.exe".to_string()]);
+        assert_eq!(data.hidden, vec!["junk.exe".to_string()]);
+        assert_eq!(db.rename_entry_ids(|_| None).unwrap(), 0);
+    }
+
+    fn sample_user_data() -> UserData {
+        let mut data = UserData::new(1_700_000_000);
+        data.usage = vec![UsageCount {
+            entry_id: "a.exe".to_string(),
+            count: 2,
+        }];
+        data.launch_events = vec![
+            LaunchEvent {
+                timestamp: 10,
+                entry_id: "a.exe".to_string(),
+                action: LaunchAction::Open,
+                query: Some("a".to_string()),
+                rank: Some(0),
+            }
+            .into(),
+            LaunchEvent {
+                timestamp: 20,
+                entry_id: "a.exe".to_string(),
+                action: LaunchAction::Admin,
+                query: None,
+                rank: None,
+            }
+            .into(),
+        ];
+        data.pins = vec!["a.exe".to_string(), "b.exe".to_string()];
+        data.aliases = vec![Alias {
+            alias: "aa".to_string(),
+            entry_id: "a.exe".to_string(),
+        }];
+        data.hidden = vec!["junk.exe".to_string()];
+        data
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let mut source = Database::open_in_memory().unwrap();
+        source
+            .import_user_data(&sample_user_data(), ImportMode::Replace)
+            .unwrap();
+
+        let exported = source.export_user_data().unwrap();
+        let expected = sample_user_data();
+        assert_eq!(exported.usage, expected.usage);
+        let events = |data: &UserData| {
+            data.launch_events
+                .iter()
+                .map(|e| e.event.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(events(&exported), events(&expected));
+        // Events of unknown origin become this machine's
+        let own_id = source.machine_id().unwrap();
+        assert!(exported
+            .launch_events
+            .iter()
+            .all(|e| e.origin.as_deref() == Some(own_id.as_str())));
+        assert_eq!(exported.pins, expected.pins);
+        assert_eq!(exported.aliases, expected.aliases);
+        assert_eq!(exported.hidden, expected.hidden);
+
+        let mut target = Database::open_in_memory().unwrap();
+        target
+            .import_user_data(&exported, ImportMode::Merge)
+            .unwrap();
+        let reexported = target.export_user_data().unwrap();
+        assert_eq!(reexported.usage, exported.usage);
+        assert_eq!(reexported.launch_events, exported.launch_events);
+    }
+
+    #[test]
+    fn test_import_merge_sums_counts_and_unions_lists() {
+        let mut db = Database::open_in_memory().unwrap();
+        let local = LaunchEvent {
+            timestamp: 5,
+            entry_id: "a.exe".to_string(),
+            action: LaunchAction::Open,
+            query: None,
+            rank: None,
+        };
+        db.record_launch(&local).unwrap();
+
+        let mut local_lists = UserData::new(0);
+        local_lists.pins = vec!["c.exe".to_string(), "a.exe".to_string()];
+        local_lists.aliases = vec![Alias {
+            alias: "aa".to_string(),
+            entry_id: "c.exe".to_string(),
+        }];
+        db.import_user_data(&local_lists, ImportMode::Merge)
+            .unwrap();
+
+        db.import_user_data(&sample_user_data(), ImportMode::Merge)
+            .unwrap();
+        let merged = db.export_user_data().unwrap();
+
+        assert_eq!(merged.usage[0].count, 3);
+        assert_eq!(merged.launch_events.len(), 3);
+        assert_eq!(merged.launch_events[0].event, local);
+        // Existing pins keep their order, new ones are appended
+        assert_eq!(merged.pins, vec!["c.exe", "a.exe", "b.exe"]);
+        // The local alias wins
+        assert_eq!(merged.aliases[0].entry_id, "c.exe");
+        assert_eq!(merged.hidden, vec!["junk.exe"]);
+
+        // Importing the same file again adds no events and no usage
+        db.import_user_data(&sample_user_data(), ImportMode::Merge)
+            .unwrap();
+        let again = db.export_user_data().unwrap();
+        assert_eq!(again.launch_events.len(), 3);
+        assert_eq!(again.usage[0].count, 3);
+    }
+
+    #[test]
+    fn test_import_own_and_foreign_events_by_origin() {
+        let mut laptop = Database::open_in_memory().unwrap();
+        laptop
+            .record_launch(&LaunchEvent::now("a.exe", LaunchAction::Open, None, None))
+            .unwrap();
+        let exported = laptop.export_user_data().unwrap();
+        assert_eq!(exported.launch_events[0].origin_seq, Some(1));
+
+        // Back on the laptop the launch is already there
+        laptop
+            .import_user_data(&exported, ImportMode::Merge)
+            .unwrap();
+        assert_eq!(laptop.export_user_data().unwrap().usage[0].count, 1);
+
+        // After a replace it returns as a local launch with its old id
+        laptop
+            .import_user_data(&exported, ImportMode::Replace)
+            .unwrap();
+        assert_eq!(laptop.load_local_events_after(0).unwrap().len(), 1);
+
+        // Elsewhere it is the laptop's launch, never published as local
+        let mut desktop = Database::open_in_memory().unwrap();
+        desktop
+            .import_user_data(&exported, ImportMode::Merge)
+            .unwrap();
+        desktop
+            .import_user_data(&exported, ImportMode::Merge)
+            .unwrap();
+        assert!(desktop.load_local_events_after(0).unwrap().is_empty());
+        let data = desktop.export_user_data().unwrap();
+        assert_eq!(data.usage[0].count, 1);
+        assert_eq!(data.launch_events, exported.launch_events);
+    }
+
+    #[test]
+    fn test_import_replace_discards_local_data() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.record_launch(&LaunchEvent::now("old.exe", LaunchAction::Open, None, None))
+            .unwrap();
+
+        db.import_user_data(&sample_user_data(), ImportMode::Replace)
+            .unwrap();
+        let data = db.export_user_data().unwrap();
+
+        assert_eq!(data.usage.len(), 1);
+        assert_eq!(data.usage[0].entry_id, "a.exe");
+        assert!(data
+            .launch_events
+            .iter()
+            .all(|e| e.event.entry_id == "a.exe"));
     }
 }
@@ -0,0 +1,512 @@
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, Transaction};
+use std::path::{Path, PathBuf};
+
+/// A single schema change, applied once when `user_version` is below `version`
+pub struct Migration {
+    pub version: u32,
+    pub apply: fn(&Transaction) -> SqlResult<()>,
+}
+
+/// All migrations, in the order they must be applied
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        apply: create_base_tables,
+    },
+    Migration {
+        version: 2,
+        apply: create_launch_events,
+    },
+    Migration {
+        version: 3,
+        apply: create_full_app_cache,
+    },
+    Migration {
+        version: 4,
+        apply: create_user_lists,
+    },
+    Migration {
+        version: 5,
+        apply: add_sync_origins,
+    },
+    Migration {
+        version: 6,
+        apply: create_scan_files,
+    },
+    Migration {
+        version: 7,
+        apply: add_broken_flag,
+    },
+    Migration {
+        version: 8,
+        apply: add_entry_folders,
+    },
+    Migration {
+        version: 9,
+        apply: add_shortcut_details,
+    },
+    Migration {
+        version: 10,
+        apply: add_uninstall_command,
+    },
+    Migration {
+        version: 11,
+        apply: add_keywords,
+    },
+    Migration {
+        version: 12,
+        apply: add_uninstall_name,
+    },
+    Migration {
+        version: 13,
+        apply: create_pruned_events,
+    },
+    Migration {
+        version: 14,
+        apply: drop_scan_file_icon_indices,
+    },
+];
+
+/// Schema version this build of Oxistart expects
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Read the schema version stored in the database header
+pub fn current_version(conn: &Connection) -> SqlResult<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Bring the database up to the latest schema version.
+///
+/// Each step runs in its own transaction together with the `user_version` bump,
+/// so an interrupted upgrade resumes from the last completed step. When an
+/// existing file-backed database is about to change, a copy is written next to
+/// it first. Databases from a newer Oxistart are left untouched.
+pub fn migrate(conn: &mut Connection) -> SqlResult<u32> {
+    migrate_with(conn, MIGRATIONS)
+}
+
+fn migrate_with(conn: &mut Connection, migrations: &[Migration]) -> SqlResult<u32> {
+    let mut version = current_version(conn)?;
+    let target = migrations.last().map(|m| m.version).unwrap_or(0);
+    if version >= target {
+        return Ok(version);
+    }
+
+    if let Some(backup_path) = backup_path_for(conn, version) {
+        if has_user_tables(conn)? {
+            backup(conn, &backup_path)?;
+        }
+    }
+
+    let start = version;
+    for migration in migrations.iter().filter(|m| m.version > start) {
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+/// Where the pre-migration copy of a file-backed database goes
+fn backup_path_for(conn: &Connection, version: u32) -> Option<PathBuf> {
+    let path = conn.path().filter(|p| !p.is_empty())?;
+    Some(PathBuf::from(format!("{}.v{}.bak", path, version)))
+}
+
+fn has_user_tables(conn: &Connection) -> SqlResult<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Write a consistent copy of the database to `path`, replacing any older copy
+fn backup(conn: &Connection, path: &Path) -> SqlResult<()> {
+    let _ = std::fs::remove_file(path);
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+    Ok(())
+}
+
+fn table_exists(tx: &Transaction, name: &str) -> SqlResult<bool> {
+    Ok(tx
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [name],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Version 1: the tables that existed before versioning was introduced
+fn create_base_tables(tx: &Transaction) -> SqlResult<()> {
+    // Legacy table for usage tracking
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS app_usage (
+            path TEXT PRIMARY KEY,
+            count INTEGER
+        )",
+        [],
+    )?;
+
+    // Table for caching app list
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS app_cache (
+            parse_name TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            icon_index INTEGER NOT NULL,
+            entry_type TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Version 2: launch history, seeded from the existing usage counts
+fn create_launch_events(tx: &Transaction) -> SqlResult<()> {
+    // Databases written before versioning may already have the table
+    if table_exists(tx, "launch_events")? {
+        return Ok(());
+    }
+
+    tx.execute_batch(
+        "CREATE TABLE launch_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            entry_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            query TEXT,
+            rank INTEGER
+        );
+        CREATE INDEX launch_events_entry ON launch_events (entry_id);
+        CREATE INDEX launch_events_timestamp ON launch_events (timestamp);",
+    )?;
+
+    // Carry existing counts over as undated events so aggregates keep their history
+    tx.execute(
+        "WITH RECURSIVE seq(n) AS (
+            SELECT 1
+            UNION ALL
+            SELECT n + 1 FROM seq WHERE n < (SELECT MAX(count) FROM app_usage)
+        )
+        INSERT INTO launch_events (timestamp, entry_id, action, query, rank)
+        SELECT 0, app_usage.path, 'open', NULL, NULL
+        FROM app_usage JOIN seq ON seq.n <= app_usage.count
+        ORDER BY app_usage.path",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Version 3: app cache that keeps every `AppEntry` field, plus the versions it was written with
+fn create_full_app_cache(tx: &Transaction) -> SqlResult<()> {
+    // The old cache lacks arguments and cannot be trusted; the next scan refills it
+    tx.execute_batch(
+        "DROP TABLE IF EXISTS app_cache;
+        CREATE TABLE app_cache (
+            parse_name TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            arguments TEXT,
+            icon_index INTEGER NOT NULL,
+            entry_type TEXT NOT NULL,
+            icon_path TEXT,
+            shortcut_path TEXT,
+            source_mtime INTEGER
+        );
+        CREATE TABLE cache_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+}
+
+/// Version 4: user-curated pins, aliases and hidden entries
+fn create_user_lists(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE pins (
+            entry_id TEXT PRIMARY KEY,
+            position INTEGER NOT NULL
+        );
+        CREATE TABLE aliases (
+            alias TEXT PRIMARY KEY,
+            entry_id TEXT NOT NULL
+        );
+        CREATE TABLE hidden (
+            entry_id TEXT PRIMARY KEY
+        );",
+    )
+}
+
+/// Version 5: where each launch event came from, so synced logs merge only once
+fn add_sync_origins(tx: &Transaction) -> SqlResult<()> {
+    // Local events keep a NULL origin; NULLs never collide in the unique index
+    tx.execute_batch(
+        "ALTER TABLE launch_events ADD COLUMN origin TEXT;
+        ALTER TABLE launch_events ADD COLUMN origin_seq INTEGER;
+        CREATE UNIQUE INDEX launch_events_origin ON launch_events (origin, origin_seq);
+        CREATE TABLE meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+}
+
+/// Version 6: every Start Menu file seen by the last scan, so unchanged ones can be skipped
+fn create_scan_files(tx: &Transaction) -> SqlResult<()> {
+    // The entry columns are NULL for files that produced no entry (e.g. uninstallers)
+    tx.execute_batch(
+        "CREATE TABLE scan_files (
+            path TEXT PRIMARY KEY,
+            size INTEGER NOT NULL,
+            mtime INTEGER NOT NULL,
+            name TEXT,
+            parse_name TEXT,
+            arguments TEXT,
+            icon_index INTEGER,
+            icon_path TEXT
+        );",
+    )
+}
+
+/// Version 7: whether a cached entry's target was missing at the last scan
+fn add_broken_flag(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch("ALTER TABLE app_cache ADD COLUMN broken INTEGER NOT NULL DEFAULT 0;")
+}
+
+/// Version 8: the Start Menu folder of each scanned file and cached entry
+fn add_entry_folders(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE app_cache ADD COLUMN folder TEXT;
+        ALTER TABLE scan_files ADD COLUMN folder TEXT;",
+    )
+}
+
+/// Version 9: description, working directory, icon resource, show command and
+/// hotkey read from each shortcut
+fn add_shortcut_details(tx: &Transaction) -> SqlResult<()> {
+    for table in ["app_cache", "scan_files"] {
+        tx.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN description TEXT;
+            ALTER TABLE {table} ADD COLUMN working_dir TEXT;
+            ALTER TABLE {table} ADD COLUMN icon_resource INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE {table} ADD COLUMN show_cmd INTEGER;
+            ALTER TABLE {table} ADD COLUMN hotkey INTEGER;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Version 10: the command that uninstalls a cached entry's program
+fn add_uninstall_command(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch("ALTER TABLE app_cache ADD COLUMN uninstall TEXT;")
+}
+
+/// Version 11: search keywords taken from each target's version resource
+fn add_keywords(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE app_cache ADD COLUMN keywords TEXT;
+        ALTER TABLE scan_files ADD COLUMN keywords TEXT;",
+    )
+}
+
+/// Version 12: the name of the program a cached entry's uninstall command removes
+fn add_uninstall_name(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch("ALTER TABLE app_cache ADD COLUMN uninstall_name TEXT;")
+}
+
+/// Version 13: synced events forgotten because their file is gone, so
+/// syncing again doesn't bring them back
+fn create_pruned_events(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE pruned_events (
+            origin TEXT NOT NULL,
+            origin_seq INTEGER NOT NULL,
+            PRIMARY KEY (origin, origin_seq)
+        );",
+    )
+}
+
+/// Version 14: scanned files stop recording their system image list index,
+/// which only means something to the process that asked for it
+fn drop_scan_file_icon_indices(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch("ALTER TABLE scan_files DROP COLUMN icon_index;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schema and data as written by Oxistart before `user_version` was used
+    const LEGACY_FIXTURE: &str = "
+        CREATE TABLE app_usage (
+            path TEXT PRIMARY KEY,
+            count INTEGER
+        );
+        CREATE TABLE app_cache (
+            parse_name TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            icon_index INTEGER NOT NULL,
+            entry_type TEXT NOT NULL
+        );
+        INSERT INTO app_usage (path, count) VALUES
+            ('C:\\Program Files\\App\\app.exe', 3),
+            ('steam://rungameid/1', 1);
+        INSERT INTO app_cache (parse_name, name, icon_index, entry_type) VALUES
+            ('C:\\Program Files\\App\\app.exe', 'App', 4, 'Application'),
+            ('ms-settings:display', 'Display settings', -1, 'Settings');
+    ";
+
+    fn legacy_fixture(dir: &Path) -> PathBuf {
+        let path = dir.join("history.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(LEGACY_FIXTURE).unwrap();
+        path
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_fresh_database_reaches_latest_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM launch_events"), 0);
+    }
+
+    #[test]
+    fn test_upgrade_legacy_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = legacy_fixture(dir.path());
+
+        let mut conn = Connection::open(&path).unwrap();
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+
+        // Usage data survives; the old app cache is discarded for the next scan to refill
+        assert_eq!(count(&conn, "SELECT SUM(count) FROM app_usage"), 4);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM app_cache"), 0);
+
+        // Usage counts were carried into the launch history
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM launch_events WHERE entry_id = 'C:\\Program Files\\App\\app.exe'"
+            ),
+            3
+        );
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM launch_events"), 4);
+    }
+
+    #[test]
+    fn test_upgrade_writes_backup_of_old_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = legacy_fixture(dir.path());
+
+        let mut conn = Connection::open(&path).unwrap();
+        migrate(&mut conn).unwrap();
+
+        let backup_path = dir.path().join("history.db.v0.bak");
+        let backup = Connection::open(&backup_path).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 0);
+        assert_eq!(count(&backup, "SELECT SUM(count) FROM app_usage"), 4);
+        assert_eq!(
+            count(
+                &backup,
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'launch_events'"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_new_database_file_gets_no_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.db");
+
+        let mut conn = Connection::open(&path).unwrap();
+        migrate(&mut conn).unwrap();
+
+        assert!(!dir.path().join("history.db.v0.bak").exists());
+    }
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = legacy_fixture(dir.path());
+
+        let mut conn = Connection::open(&path).unwrap();
+        migrate(&mut conn).unwrap();
+        drop(conn);
+
+        let mut conn = Connection::open(&path).unwrap();
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM launch_events"), 4);
+    }
+
+    #[test]
+    fn test_failed_step_rolls_back_and_keeps_previous_version() {
+        fn broken(tx: &Transaction) -> SqlResult<()> {
+            tx.execute("CREATE TABLE half_done (id INTEGER)", [])?;
+            tx.execute("INSERT INTO missing_table VALUES (1)", [])?;
+            Ok(())
+        }
+
+        let steps = [
+            Migration {
+                version: 1,
+                apply: create_base_tables,
+            },
+            Migration {
+                version: 2,
+                apply: broken,
+            },
+        ];
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(migrate_with(&mut conn, &steps).is_err());
+
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'half_done'"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_newer_database_is_left_alone() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version() + 1);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'app_usage'"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_migration_versions_are_ordered() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::app_model::AppEntry;
+use rusqlite::{params, Connection, Result as SqlResult};
+
+/// Shortest search term the trigram tokenizer can match
+const MIN_TERM_CHARS: usize = 3;
+
+/// Full-text index over entry names, folders, descriptions and keywords, used to
+/// narrow a search down to a few candidates before fuzzy scoring.
+///
+/// The index lives in its own in-memory database: it is rebuilt from the
+/// entry list whenever that changes, so there is nothing worth persisting.
+pub struct SearchIndex {
+    conn: Connection,
+}
+
+impl SearchIndex {
+    /// Create an empty index
+    pub fn new() -> SqlResult<Self> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE entries USING fts5(name, folder, description, keywords, tokenize = 'trigram')",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Replace the indexed entries; each row id is the entry's position in `apps`
+    pub fn rebuild(&mut self, apps: &[AppEntry]) -> SqlResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM entries", [])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO entries (rowid, name, folder, description, keywords)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (i, app) in apps.iter().enumerate() {
+                stmt.execute(params![
+                    i as i64,
+                    app.name,
+                    app.folder,
+                    app.description,
+                    app.keywords
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Positions of up to `limit` entries whose name, folder, description or
+    /// keywords contain any word of `search`, best matches first.
+    ///
+    /// Returns `None` when no word is long enough for the trigram index, in
+    /// which case the caller has to look at every entry itself.
+    pub fn candidates(&self, search: &str, limit: usize) -> SqlResult<Option<Vec<usize>>> {
+        let Some(query) = match_query(search) else {
+            return Ok(None);
+        };
+
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT rowid FROM entries WHERE entries MATCH ?1 ORDER BY rank LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![query, limit as i64], |row| {
+            row.get::<_, i64>(0).map(|id| id as usize)
+        })?;
+        rows.collect::<SqlResult<Vec<_>>>().map(Some)
+    }
+}
+
+/// Build an FTS5 query matching any word of `search` as a substring.
+///
+/// Every word is quoted, so characters that mean something to FTS5 are
+/// searched for literally.
+fn match_query(search: &str) -> Option<String> {
+    let terms = search
+        .split_whitespace()
+        .filter(|word| word.chars().count() >= MIN_TERM_CHARS)
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(names: &[&str]) -> SearchIndex {
+        let apps = names
+            .iter()
+            .map(|name| AppEntry::new(name.to_string(), name.to_string(), 0, 0))
+            .collect::<Vec<_>>();
+        let mut index = SearchIndex::new().unwrap();
+        index.rebuild(&apps).unwrap();
+        index
+    }
+
+    fn sorted(mut ids: Vec<usize>) -> Vec<usize> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_substring_matches_any_case() {
+        let index = index(&["Visual Studio Code", "Notepad", "Studio One", "Paint"]);
+        let found = index.candidates("STUDIO", 10).unwrap().unwrap();
+        assert_eq!(sorted(found), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_any_word_matches() {
+        let index = index(&["Visual Studio Code", "Notepad", "Paint"]);
+        let found = index.candidates("note paint", 10).unwrap().unwrap();
+        assert_eq!(sorted(found), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_folders_match() {
+        let mut prompt = AppEntry::new("Developer Prompt".to_string(), "p".to_string(), 0, 0);
+        prompt.folder = Some("Visual Studio 2022\\Tools".to_string());
+        let notepad = AppEntry::new("Notepad".to_string(), "n".to_string(), 0, 0);
+        let mut index = SearchIndex::new().unwrap();
+        index.rebuild(&[prompt, notepad]).unwrap();
+
+        assert_eq!(index.candidates("studio", 10).unwrap(), Some(vec![0]));
+    }
+
+    #[test]
+    fn test_descriptions_match() {
+        let mut editor = AppEntry::new("Notepad++".to_string(), "n".to_string(), 0, 0);
+        editor.description = Some("Source code editor".to_string());
+        let paint = AppEntry::new("Paint".to_string(), "p".to_string(), 0, 0);
+        let mut index = SearchIndex::new().unwrap();
+        index.rebuild(&[editor, paint]).unwrap();
+
+        assert_eq!(index.candidates("editor", 10).unwrap(), Some(vec![0]));
+    }
+
+    #[test]
+    fn test_keywords_match() {
+        let paint = AppEntry::new("Paint".to_string(), "p".to_string(), 0, 0);
+        let mut shell = AppEntry::new("pwsh".to_string(), "s".to_string(), 0, 0);
+        shell.keywords = Some("PowerShell 7, Microsoft Corporation".to_string());
+        let mut index = SearchIndex::new().unwrap();
+        index.rebuild(&[paint, shell]).unwrap();
+
+        assert_eq!(index.candidates("powershell", 10).unwrap(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_short_terms_need_a_full_scan() {
+        let index = index(&["Notepad"]);
+        assert_eq!(index.candidates("no", 10).unwrap(), None);
+        assert_eq!(index.candidates("", 10).unwrap(), None);
+    }
+
+    #[test]
+    fn test_fts_syntax_is_literal() {
+        let index = index(&["C++ \"Tools\"", "AND OR NOT"]);
+        assert_eq!(index.candidates("\"Tools\"", 10).unwrap(), Some(vec![0]));
+        assert_eq!(index.candidates("AND", 10).unwrap(), Some(vec![1]));
+        assert_eq!(index.candidates("C++", 10).unwrap(), Some(vec![0]));
+    }
+
+    #[test]
+    fn test_limit_and_rebuild() {
+        let mut index = index(&["App One", "App Two", "App Three"]);
+        assert_eq!(index.candidates("app", 2).unwrap().unwrap().len(), 2);
+
+        index
+            .rebuild(&[AppEntry::new("Other".to_string(), "o".to_string(), 0, 0)])
+            .unwrap();
+        assert_eq!(index.candidates("app", 10).unwrap(), Some(vec![]));
+    }
+}
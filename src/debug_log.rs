@@ -0,0 +1,34 @@
+use once_cell::sync::OnceCell;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the debug log inside the data directory
+pub const LOG_FILE_NAME: &str = "oxistart_debug.log";
+
+static LOG_PATH: OnceCell<PathBuf> = OnceCell::new();
+
+/// Send the log to `data_dir`; nothing is written before this is called
+pub fn init(data_dir: &Path) {
+    let _ = std::fs::create_dir_all(data_dir);
+    let _ = LOG_PATH.set(data_dir.join(LOG_FILE_NAME));
+}
+
+/// Where the log is written, once `init` has been called
+pub fn path() -> Option<&'static Path> {
+    LOG_PATH.get().map(PathBuf::as_path)
+}
+
+/// Write a message to the debug log file
+pub fn write_debug_log(message: &str) {
+    let Some(path) = path() else {
+        return;
+    };
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let _ = writeln!(file, "[{}] {}", timestamp, message);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::known_folders::Environment;
+
+/// Expand `%NAME%` references in `value` from `env`, like
+/// `ExpandEnvironmentStrings` does.
+///
+/// Names are looked up case-insensitively. References to unknown variables,
+/// a lone `%` and `%%` are left as they are. Quotes are kept, so a quoted
+/// argument such as `"%APPDATA%\App\config.ini"` stays one argument.
+pub fn expand(value: &str, env: &impl Environment) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let Some(end) = after.find('%') else {
+            // No closing `%`: the rest is literal
+            out.push_str(&rest[start..]);
+            return out;
+        };
+
+        let name = &after[..end];
+        match lookup(name, env) {
+            Some(expanded) => {
+                out.push_str(&expanded);
+                rest = &after[end + 1..];
+            }
+            None => {
+                // Keep the `%` and try again from the next one, which may open
+                // a valid reference (`50% of %APPDATA%`)
+                out.push('%');
+                rest = after;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Expand a path read from a shortcut (target, working directory or icon) and
+/// drop the quotes around it, if any, so it can be checked on disk
+pub fn expand_path(value: &str, env: &impl Environment) -> String {
+    let expanded = expand(value.trim(), env);
+    let unquoted = expanded
+        .strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+        .unwrap_or(&expanded);
+    unquoted.to_string()
+}
+
+fn lookup(name: &str, env: &impl Environment) -> Option<String> {
+    // Variable names can't be empty or contain `=`; quotes mean the `%`s
+    // belong to separate, quoted arguments
+    if name.is_empty() || name.contains(['=', '"']) {
+        return None;
+    }
+    env.var(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::known_folders::FakeEnvironment;
+
+    fn env() -> FakeEnvironment {
+        FakeEnvironment::new(&[
+            ("ProgramFiles", "D:\\Program Files"),
+            ("SystemRoot", "D:\\Windows"),
+            ("APPDATA", "D:\\Users\\me\\AppData\\Roaming"),
+        ])
+    }
+
+    #[test]
+    fn test_expands_known_variables() {
+        assert_eq!(
+            expand("%ProgramFiles%\\App\\app.exe", &env()),
+            "D:\\Program Files\\App\\app.exe"
+        );
+        assert_eq!(
+            expand("%SystemRoot%\\system32\\%SystemRoot%", &env()),
+            "D:\\Windows\\system32\\D:\\Windows"
+        );
+        assert_eq!(expand("no variables", &env()), "no variables");
+    }
+
+    #[test]
+    fn test_names_are_case_insensitive() {
+        assert_eq!(
+            expand("%PROGRAMFILES%\\a;%programfiles%\\b", &env()),
+            "D:\\Program Files\\a;D:\\Program Files\\b"
+        );
+    }
+
+    #[test]
+    fn test_unknown_variables_and_stray_percents_stay() {
+        assert_eq!(expand("%NOPE%\\app.exe", &env()), "%NOPE%\\app.exe");
+        assert_eq!(expand("100%", &env()), "100%");
+        assert_eq!(expand("%%", &env()), "%%");
+        assert_eq!(
+            expand("50% of %APPDATA%", &env()),
+            "50% of D:\\Users\\me\\AppData\\Roaming"
+        );
+    }
+
+    #[test]
+    fn test_quotes_are_kept_in_arguments() {
+        assert_eq!(
+            expand("--config \"%APPDATA%\\App\\a b.ini\" --x", &env()),
+            "--config \"D:\\Users\\me\\AppData\\Roaming\\App\\a b.ini\" --x"
+        );
+        // The `%`s of two quoted arguments are not one reference
+        assert_eq!(expand("\"50%\" \"%x\"", &env()), "\"50%\" \"%x\"");
+    }
+
+    #[test]
+    fn test_expand_path_unquotes() {
+        assert_eq!(
+            expand_path(" \"%SystemRoot%\\system32\\shell32.dll\" ", &env()),
+            "D:\\Windows\\system32\\shell32.dll"
+        );
+        assert_eq!(expand_path("\"", &env()), "\"");
+    }
+}
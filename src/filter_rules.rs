@@ -0,0 +1,351 @@
+use crate::app_model::AppEntry;
+use crate::glob;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Rules shipped with Oxistart; `config.json` can override any of them by id
+const DEFAULT_RULES: &str = include_str!("default_filters.json");
+
+/// What a matching rule does with an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Allow,
+    Deny,
+}
+
+/// Which part of an entry a rule looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleField {
+    /// Display name, e.g. `Uninstall Foo`
+    Name,
+    /// What the entry opens: a file path or a URL
+    Target,
+    /// Extension of the target, without the dot
+    Extension,
+}
+
+/// A filter rule as written in `default_filters.json` or `config.json`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FilterRule {
+    /// Names the rule in logs and reports; a config rule with the id of a
+    /// default rule replaces it
+    pub id: String,
+    pub action: RuleAction,
+    pub field: RuleField,
+    /// Glob the field must match (see `glob::matches`)
+    #[serde(default)]
+    pub glob: Option<String>,
+    /// Regular expression the field must contain a match of
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// Only match entries read from a file with this extension, e.g. `url`
+    #[serde(default)]
+    pub source_extension: Option<String>,
+    /// Switch the rule off, e.g. to drop a default rule
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// How a rule matches its field
+#[derive(Debug)]
+enum Pattern {
+    Glob(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    id: String,
+    action: RuleAction,
+    field: RuleField,
+    pattern: Pattern,
+    source_extension: Option<String>,
+}
+
+/// Whether an entry is kept, and the rule that decided it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub keep: bool,
+    /// `None` when no rule matched and the entry is kept by default
+    pub rule: Option<String>,
+}
+
+/// The active filter rules.
+///
+/// An entry is dropped when a deny rule matches it and no allow rule does, so
+/// an allow rule is an exception to any deny rule. Config rules are checked
+/// before the defaults, which decides the rule reported when several match.
+#[derive(Debug)]
+pub struct FilterRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl FilterRules {
+    /// The default rules with `overrides` (from `config.json`) applied
+    pub fn with_overrides(overrides: &[FilterRule]) -> Result<Self, String> {
+        let defaults: Vec<FilterRule> = serde_json::from_str(DEFAULT_RULES)
+            .map_err(|e| format!("Invalid default filter rules: {}", e))?;
+        Self::new(&defaults, overrides)
+    }
+
+    /// Combine `defaults` and `overrides`; an override replaces the default
+    /// rule with the same id
+    pub fn new(defaults: &[FilterRule], overrides: &[FilterRule]) -> Result<Self, String> {
+        let replaced = |rule: &&FilterRule| !overrides.iter().any(|o| o.id == rule.id);
+        let rules = overrides
+            .iter()
+            .chain(defaults.iter().filter(replaced))
+            .filter(|rule| !rule.disabled)
+            .map(compile)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Decide whether `app` is listed
+    pub fn evaluate(&self, app: &AppEntry) -> Verdict {
+        let matching = |action: RuleAction| {
+            self.rules
+                .iter()
+                .find(|rule| rule.action == action && rule.matches(app))
+                .map(|rule| rule.id.clone())
+        };
+
+        if let Some(rule) = matching(RuleAction::Allow) {
+            return Verdict {
+                keep: true,
+                rule: Some(rule),
+            };
+        }
+        match matching(RuleAction::Deny) {
+            Some(rule) => Verdict {
+                keep: false,
+                rule: Some(rule),
+            },
+            None => Verdict {
+                keep: true,
+                rule: None,
+            },
+        }
+    }
+}
+
+impl Default for FilterRules {
+    fn default() -> Self {
+        Self::with_overrides(&[]).expect("default filter rules are valid")
+    }
+}
+
+impl CompiledRule {
+    fn matches(&self, app: &AppEntry) -> bool {
+        if let Some(wanted) = &self.source_extension {
+            let source = app.shortcut_path.as_deref().unwrap_or_default();
+            let extension = std::path::Path::new(source)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            if !extension.eq_ignore_ascii_case(wanted) {
+                return false;
+            }
+        }
+        let value = match self.field {
+            RuleField::Name => app.name.as_str(),
+            RuleField::Target => app.parse_name.as_str(),
+            RuleField::Extension => std::path::Path::new(&app.parse_name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default(),
+        };
+        match &self.pattern {
+            Pattern::Glob(pattern) => glob::matches(pattern, value),
+            Pattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+fn compile(rule: &FilterRule) -> Result<CompiledRule, String> {
+    let pattern = match (&rule.glob, &rule.regex) {
+        (Some(glob), None) => Pattern::Glob(glob.clone()),
+        (None, Some(regex)) => Pattern::Regex(
+            Regex::new(regex).map_err(|e| format!("Filter rule {}: {}", rule.id, e))?,
+        ),
+        _ => {
+            return Err(format!(
+                "Filter rule {} needs either a glob or a regex",
+                rule.id
+            ))
+        }
+    };
+    Ok(CompiledRule {
+        id: rule.id.clone(),
+        action: rule.action,
+        field: rule.field,
+        pattern,
+        source_extension: rule.source_extension.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(name: &str, target: &str) -> AppEntry {
+        AppEntry::new(name.to_string(), target.to_string(), 0, 0)
+    }
+
+    fn rule(id: &str, action: RuleAction, field: RuleField, glob: &str) -> FilterRule {
+        FilterRule {
+            id: id.to_string(),
+            action,
+            field,
+            glob: Some(glob.to_string()),
+            regex: None,
+            source_extension: None,
+            disabled: false,
+        }
+    }
+
+    fn dropped_by(rules: &FilterRules, app: &AppEntry) -> Option<String> {
+        let verdict = rules.evaluate(app);
+        (!verdict.keep).then(|| verdict.rule.unwrap())
+    }
+
+    #[test]
+    fn test_defaults() {
+        let rules = FilterRules::default();
+        let drops = [
+            ("Uninstall Foo", "C:\\Foo\\unins000.exe", "uninstaller"),
+            ("Remove Bar", "C:\\Bar\\uninstall.exe", "uninstaller"),
+            ("Uninstall Foo", "C:\\Foo\\remove.exe", "uninstaller-name"),
+            (
+                "Uninstall Foo",
+                "C:\\Foo\\Uninstall Foo.exe",
+                "uninstaller-name",
+            ),
+            (
+                "Repair Foo",
+                "C:\\Windows\\System32\\msiexec.exe",
+                "windows-installer",
+            ),
+            ("Foo Setup", "C:\\Foo\\setup.exe", "installer"),
+            ("Bar", "C:\\Downloads\\BarSetup-x64.exe", "installer"),
+            (
+                "Baz Setup",
+                "shell:AppsFolder\\Baz.Installer_abc!App",
+                "installer-name",
+            ),
+            ("Mail", "Microsoft.AutoGenerated.{1234}", "store-app-stub"),
+            (
+                "Terminal",
+                "C:\\Program Files\\WindowsApps\\Terminal\\wt.exe",
+                "store-app-folder",
+            ),
+            ("Nothing", "", "empty-target"),
+        ];
+        for (name, target, rule) in drops {
+            assert_eq!(
+                dropped_by(&rules, &app(name, target)),
+                Some(rule.to_string())
+            );
+        }
+
+        // Web links are dropped when they come from a .url file, not a shortcut
+        let mut docs = app("Docs", "https://example.com");
+        docs.shortcut_path = Some("C:\\Start Menu\\Docs.url".to_string());
+        assert_eq!(dropped_by(&rules, &docs), Some("web-link".to_string()));
+        docs.shortcut_path = Some("C:\\Start Menu\\Docs.lnk".to_string());
+        assert_eq!(dropped_by(&rules, &docs), None);
+        let mut uninstall_link = app("Uninstall Foo", "https://foo.example/uninstall");
+        uninstall_link.shortcut_path = Some("C:\\Start Menu\\Uninstall Foo.url".to_string());
+        assert!(!rules.evaluate(&uninstall_link).keep);
+        assert_eq!(
+            rules.evaluate(&app("Steam Game", "steam://rungameid/1")),
+            Verdict {
+                keep: true,
+                rule: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_defaults_keep_apps_named_like_installers() {
+        let rules = FilterRules::default();
+        let kept = [
+            ("Setup Assistant", "C:\\Setup Assistant\\assistant.exe"),
+            ("Uninstall Tool", "C:\\Uninstall Tool\\UninstallTool.exe"),
+            ("Game Setup Editor", "C:\\Games\\editor.exe"),
+        ];
+        for (name, target) in kept {
+            assert_eq!(dropped_by(&rules, &app(name, target)), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_allow_beats_deny() {
+        let overrides = [rule(
+            "driver-setup",
+            RuleAction::Allow,
+            RuleField::Name,
+            "Driver Setup",
+        )];
+        let rules = FilterRules::with_overrides(&overrides).unwrap();
+
+        assert_eq!(
+            rules.evaluate(&app("Driver Setup", "C:\\Driver\\setup.exe")),
+            Verdict {
+                keep: true,
+                rule: Some("driver-setup".to_string())
+            }
+        );
+        assert_eq!(
+            dropped_by(&rules, &app("Foo Setup", "C:\\Foo\\setup.exe")),
+            Some("installer".to_string())
+        );
+    }
+
+    #[test]
+    fn test_overrides_replace_or_disable_defaults() {
+        let mut no_installer = rule("installer", RuleAction::Deny, RuleField::Name, "");
+        no_installer.disabled = true;
+        let overrides = [
+            no_installer,
+            rule("help-files", RuleAction::Deny, RuleField::Extension, "chm"),
+        ];
+        let rules = FilterRules::with_overrides(&overrides).unwrap();
+
+        assert!(rules.evaluate(&app("Foo", "C:\\Foo\\setup.exe")).keep);
+        assert_eq!(
+            dropped_by(&rules, &app("Foo Help", "C:\\Foo\\Help.CHM")),
+            Some("help-files".to_string())
+        );
+    }
+
+    #[test]
+    fn test_regex_rules() {
+        let overrides = [FilterRule {
+            id: "readme".to_string(),
+            action: RuleAction::Deny,
+            field: RuleField::Name,
+            glob: None,
+            regex: Some("(?i)^read ?me$".to_string()),
+            source_extension: None,
+            disabled: false,
+        }];
+        let rules = FilterRules::with_overrides(&overrides).unwrap();
+        assert!(!rules.evaluate(&app("ReadMe", "C:\\Foo\\readme.txt")).keep);
+        assert!(rules.evaluate(&app("ReadMe Viewer", "C:\\rv.exe")).keep);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_errors() {
+        let mut bad_regex = rule("bad", RuleAction::Deny, RuleField::Name, "");
+        bad_regex.glob = None;
+        bad_regex.regex = Some("(".to_string());
+        assert!(FilterRules::with_overrides(&[bad_regex]).is_err());
+
+        let mut both = rule("both", RuleAction::Deny, RuleField::Name, "*");
+        both.regex = Some(".*".to_string());
+        assert!(FilterRules::with_overrides(&[both]).is_err());
+    }
+}
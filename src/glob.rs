@@ -0,0 +1,83 @@
+/// Match `path` (relative, with `/` or `\` separators) against a glob `pattern`.
+///
+/// `*` matches anything within one path component, `?` a single character and
+/// `**` any number of whole components. A pattern without a separator is
+/// matched against the last component only, so `*uninstall*` excludes an
+/// uninstaller at any depth. Matching ignores case, like the Windows file system.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern = normalize(pattern);
+    let path = normalize(path);
+
+    if !pattern.contains('/') {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        return match_chars(
+            &pattern.chars().collect::<Vec<_>>(),
+            &name.chars().collect::<Vec<_>>(),
+        );
+    }
+
+    match_chars(
+        &pattern.chars().collect::<Vec<_>>(),
+        &path.chars().collect::<Vec<_>>(),
+    )
+}
+
+fn normalize(value: &str) -> String {
+    value.replace('\\', "/").trim_matches('/').to_lowercase()
+}
+
+fn match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        // `**/` also matches no folder at all
+        ['*', '*', '/', rest @ ..] => {
+            match_chars(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == '/')
+                    .any(|i| match_chars(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| match_chars(rest, &text[i..])),
+        ['*', rest @ ..] => {
+            let component = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=component).any(|i| match_chars(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => matches!(text, [c, tail @ ..] if *c != '/' && match_chars(rest, tail)),
+        [p, rest @ ..] => matches!(text, [c, tail @ ..] if c == p && match_chars(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_star_stays_within_a_component() {
+        assert!(matches("Tools/*.exe", "Tools/app.exe"));
+        assert!(!matches("Tools/*.exe", "Tools/Sub/app.exe"));
+        assert!(matches("?ode.lnk", "Code.lnk"));
+        assert!(!matches("?ode.lnk", "ode.lnk"));
+    }
+
+    #[test]
+    fn test_double_star_spans_folders() {
+        assert!(matches("**/bin/*.exe", "bin/app.exe"));
+        assert!(matches("**/bin/*.exe", "App/1.0/bin/app.exe"));
+        assert!(matches("Games/**", "Games/Steam/Game.url"));
+        assert!(!matches("Games/**", "Tools/Game.url"));
+    }
+
+    #[test]
+    fn test_name_only_patterns_match_at_any_depth() {
+        assert!(matches("*uninstall*", "Vendor\\Uninstall Vendor.lnk"));
+        assert!(matches("*.url", "a/b/c.url"));
+        assert!(!matches("*.url", "a/b/c.lnk"));
+    }
+
+    #[test]
+    fn test_case_and_separators_are_ignored() {
+        assert!(matches(
+            "PortableApps\\*\\*.EXE",
+            "portableapps/Tool/tool.exe"
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+
+/// Where environment variables come from, so folder lookups can be tested
+/// with a made-up environment
+pub trait Environment {
+    /// Value of the variable `name`, if it is set and not empty. Names are
+    /// case-insensitive, as on Windows.
+    fn var(&self, name: &str) -> Option<String>;
+}
+
+/// The environment of this process
+pub struct ProcessEnvironment;
+
+impl Environment for ProcessEnvironment {
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok().filter(|value| !value.is_empty())
+    }
+}
+
+/// Per-user and machine-wide folders, resolved from the environment instead
+/// of assuming `C:\Users\<name>` and `C:\Program Files`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KnownFolders {
+    /// `%APPDATA%` (roaming application data)
+    pub app_data: Option<PathBuf>,
+    /// `%LOCALAPPDATA%`
+    pub local_app_data: Option<PathBuf>,
+    /// `%ProgramData%`
+    pub program_data: Option<PathBuf>,
+    /// 64-bit Program Files (`%ProgramW6432%`, which a 32-bit process also sees)
+    pub program_files: Option<PathBuf>,
+    /// 32-bit Program Files (`%ProgramFiles(x86)%`); the only one on 32-bit Windows
+    pub program_files_x86: Option<PathBuf>,
+}
+
+static CURRENT: Lazy<KnownFolders> = Lazy::new(|| KnownFolders::resolve(&ProcessEnvironment));
+
+/// Folders of the current user on this machine
+pub fn current() -> &'static KnownFolders {
+    &CURRENT
+}
+
+impl KnownFolders {
+    /// Look the folders up in `env`, deriving what's missing from the user
+    /// profile and system drive where Windows would put it
+    pub fn resolve(env: &impl Environment) -> Self {
+        let path = |name: &str| env.var(name).map(PathBuf::from);
+        let profile = path("USERPROFILE");
+        let system_drive = env
+            .var("SystemDrive")
+            .map(|drive| PathBuf::from(drive + "\\"));
+
+        let program_files = path("ProgramW6432").or_else(|| path("ProgramFiles"));
+        let program_files_x86 =
+            path("ProgramFiles(x86)").filter(|dir| Some(dir) != program_files.as_ref());
+
+        Self {
+            app_data: path("APPDATA")
+                .or_else(|| profile.as_ref().map(|p| p.join("AppData").join("Roaming"))),
+            local_app_data: path("LOCALAPPDATA")
+                .or_else(|| profile.as_ref().map(|p| p.join("AppData").join("Local"))),
+            program_data: path("ProgramData")
+                .or_else(|| path("ALLUSERSPROFILE"))
+                .or_else(|| system_drive.as_ref().map(|d| d.join("ProgramData"))),
+            program_files: program_files
+                .or_else(|| system_drive.as_ref().map(|d| d.join("Program Files"))),
+            program_files_x86,
+        }
+    }
+
+    /// The per-user and all-users Start Menu `Programs` folders
+    pub fn start_menu_dirs(&self) -> Vec<PathBuf> {
+        [&self.app_data, &self.program_data]
+            .into_iter()
+            .flatten()
+            .map(|base| start_menu_programs(base))
+            .collect()
+    }
+
+    /// Program Files folders, 32-bit first since that's where most launchers
+    /// (Steam, older installers) still end up
+    pub fn program_files_dirs(&self) -> Vec<PathBuf> {
+        [&self.program_files_x86, &self.program_files]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Folders programs commonly install into: Program Files and the user's app data
+    pub fn install_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = self.program_files_dirs();
+        dirs.extend(
+            [&self.local_app_data, &self.app_data]
+                .into_iter()
+                .flatten()
+                .cloned(),
+        );
+        dirs
+    }
+
+    /// Every folder that is set; folders shared by many programs rather
+    /// than owned by one
+    pub fn roots(&self) -> Vec<&Path> {
+        [
+            &self.app_data,
+            &self.local_app_data,
+            &self.program_data,
+            &self.program_files,
+            &self.program_files_x86,
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::as_path)
+        .collect()
+    }
+}
+
+fn start_menu_programs(base: &Path) -> PathBuf {
+    base.join("Microsoft")
+        .join("Windows")
+        .join("Start Menu")
+        .join("Programs")
+}
+
+/// An environment holding only the given variables, for tests
+#[cfg(test)]
+pub struct FakeEnvironment(std::collections::HashMap<String, String>);
+
+#[cfg(test)]
+impl FakeEnvironment {
+    pub fn new(vars: &[(&str, &str)]) -> Self {
+        Self(
+            vars.iter()
+                .map(|(name, value)| (name.to_lowercase(), value.to_string()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl Environment for FakeEnvironment {
+    fn var(&self, name: &str) -> Option<String> {
+        self.0.get(&name.to_lowercase()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirected_profile_on_another_drive() {
+        let env = FakeEnvironment::new(&[
+            ("APPDATA", "\\\\server\\profiles\\me\\Roaming"),
+            ("LOCALAPPDATA", "E:\\Users\\me\\AppData\\Local"),
+            ("ProgramData", "E:\\ProgramData"),
+            ("ProgramFiles", "E:\\Program Files (x86)"),
+            ("ProgramW6432", "E:\\Program Files"),
+            ("ProgramFiles(x86)", "E:\\Program Files (x86)"),
+        ]);
+        let folders = KnownFolders::resolve(&env);
+
+        assert_eq!(
+            folders.start_menu_dirs(),
+            vec![
+                start_menu_programs(Path::new("\\\\server\\profiles\\me\\Roaming")),
+                start_menu_programs(Path::new("E:\\ProgramData")),
+            ]
+        );
+        // A 32-bit process sees ProgramFiles pointing at the x86 folder
+        assert_eq!(
+            folders.program_files_dirs(),
+            vec![
+                PathBuf::from("E:\\Program Files (x86)"),
+                PathBuf::from("E:\\Program Files"),
+            ]
+        );
+        assert_eq!(folders.install_dirs().len(), 4);
+    }
+
+    #[test]
+    fn test_fallbacks_from_profile_and_system_drive() {
+        let env = FakeEnvironment::new(&[("USERPROFILE", "D:\\Users\\me"), ("SystemDrive", "D:")]);
+        let folders = KnownFolders::resolve(&env);
+
+        assert_eq!(
+            folders.app_data,
+            Some(Path::new("D:\\Users\\me").join("AppData").join("Roaming"))
+        );
+        assert_eq!(
+            folders.local_app_data,
+            Some(Path::new("D:\\Users\\me").join("AppData").join("Local"))
+        );
+        assert_eq!(
+            folders.program_data,
+            Some(Path::new("D:\\").join("ProgramData"))
+        );
+        assert_eq!(
+            folders.program_files,
+            Some(Path::new("D:\\").join("Program Files"))
+        );
+        assert_eq!(folders.program_files_x86, None);
+    }
+
+    #[test]
+    fn test_empty_environment_resolves_nothing() {
+        let folders = KnownFolders::resolve(&FakeEnvironment::new(&[]));
+        assert_eq!(folders, KnownFolders::default());
+        assert!(folders.start_menu_dirs().is_empty());
+    }
+}
@@ -1,18 +1,38 @@
 #![windows_subsystem = "windows"]
 
 mod app_model;
+mod appx;
 mod calculator;
+mod cli;
+mod commands;
+mod config;
+mod data_dir;
 mod db;
+mod debug_log;
+mod env_vars;
+mod filter_rules;
+mod glob;
 mod hooks;
+mod known_folders;
+mod parallel;
+mod registry;
+mod scan_report;
 mod scanner;
 mod settings;
+mod stats;
+mod sync;
 mod ui;
+mod uninstall;
+mod user_data;
 mod utils;
-
-use app_model::{AppEntry, AppEntryType, AppManager};
-use once_cell::sync::Lazy;
-use std::fs::OpenOptions;
-use std::io::Write;
+mod version_info;
+mod watcher;
+mod xml;
+
+use app_model::{AppEntry, AppEntryType, AppManager, ScanDiff};
+use db::Database;
+use debug_log::write_debug_log;
+use once_cell::sync::{Lazy, OnceCell};
 use std::sync::Mutex;
 use std::thread;
 use windows::{
@@ -28,6 +48,7 @@ static mut H_LIST: HWND = HWND(0);
 static mut KEYBOARD_HOOK: HHOOK = HHOOK(0);
 static mut MOUSE_HOOK: HHOOK = HHOOK(0);
 static APP_MANAGER: Lazy<Mutex<AppManager>> = Lazy::new(|| Mutex::new(AppManager::new()));
+static DATABASE: OnceCell<Mutex<Database>> = OnceCell::new();
 
 // Win key hold tracking
 static mut WIN_KEY_PRESS_TIME: Option<std::time::Instant> = None;
@@ -42,6 +63,9 @@ const ID_TRAY_EXIT: usize = 1001;
 const ID_EDIT: i32 = 1002;
 const ID_LIST: i32 = 1003;
 const ID_CALC_RESULT: i32 = 1004;
+const ID_TRAY_INCOGNITO: usize = 1005;
+// Below this many entries a plain fuzzy scan is faster than asking the search index
+const SEARCH_INDEX_MIN_APPS: usize = 2000;
 static mut IS_DARK_MODE: bool = false;
 static mut H_FONT: HFONT = HFONT(0);
 static mut H_CALC_FONT: HFONT = HFONT(0);
@@ -54,29 +78,18 @@ const CALC_LABEL_HEIGHT: i32 = 28;
 const LIST_TOP: i32 = PADDING + SEARCH_HEIGHT + 5 + CALC_LABEL_HEIGHT + 5;
 const ROW_HEIGHT: i32 = 36; // Height for list rows with larger icons
 
-/// Write a message to the debug log file
-fn write_debug_log(message: &str) {
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("oxistart_debug.log")
-    {
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let _ = writeln!(file, "[{}] {}", timestamp, message);
-    }
-}
-
 /// Show a user-visible error message when app launch fails
 unsafe fn show_launch_error(app_name: &str, error_code: u32) {
     use windows::Win32::UI::WindowsAndMessaging::*;
 
     let title = w!("Oxistart - Launch Failed");
+    let log = debug_log::path().map_or_else(
+        || debug_log::LOG_FILE_NAME.to_string(),
+        |path| path.display().to_string(),
+    );
     let message = format!(
-        "Failed to launch '{}'\n\nError code: {}\n\nCheck oxistart_debug.log for details.",
-        app_name, error_code
+        "Failed to launch '{}'\n\nError code: {}\n\nCheck {} for details.",
+        app_name, error_code, log
     );
     let message_wide = utils::to_wide_string(&message);
 
@@ -88,6 +101,40 @@ unsafe fn show_launch_error(app_name: &str, error_code: u32) {
     );
 }
 
+/// Open `file` like `ShellExecuteW` with the "open" verb, also handing the
+/// started program the shortcut's hotkey (see `AppEntry::shell_hotkey`) as
+/// Explorer does. Like `ShellExecuteW`, returns a value above 32 on success.
+unsafe fn shell_open(
+    file: PCWSTR,
+    parameters: PCWSTR,
+    directory: PCWSTR,
+    show: SHOW_WINDOW_CMD,
+    hotkey: Option<u32>,
+) -> HINSTANCE {
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOASYNC,
+        lpVerb: w!("open"),
+        lpFile: file,
+        lpParameters: parameters,
+        lpDirectory: directory,
+        nShow: show.0,
+        ..Default::default()
+    };
+    if let Some(hotkey) = hotkey {
+        info.fMask |= SEE_MASK_HOTKEY;
+        info.dwHotKey = hotkey;
+    }
+    match ShellExecuteExW(&mut info) {
+        Ok(()) => HINSTANCE(33),
+        // hInstApp holds an SE_ERR_* code, else the Win32 error explains it
+        Err(e) if info.hInstApp.0 == 0 || info.hInstApp.0 > 32 => {
+            HINSTANCE((e.code().0 & 0xFFFF) as isize)
+        }
+        Err(_) => info.hInstApp,
+    }
+}
+
 /// Resolve Microsoft AutoGenerated parse names
 /// These are special Windows-generated names for Store apps and system apps
 fn resolve_microsoft_auto_generated_path(auto_generated_path: &str) -> Option<String> {
@@ -138,14 +185,14 @@ fn resolve_microsoft_auto_generated_path(auto_generated_path: &str) -> Option<St
             write_debug_log(&format!("Extracted GUID: {}", guid));
 
             // Try to find in WindowsApps directory
-            let windows_apps_paths = [
-                format!("C:\\Program Files\\WindowsApps\\{}", guid),
-                format!("C:\\Program Files (x86)\\WindowsApps\\{}", guid),
-            ];
+            let windows_apps_paths = known_folders::current()
+                .program_files_dirs()
+                .into_iter()
+                .map(|dir| dir.join("WindowsApps").join(guid));
 
             for path in windows_apps_paths {
-                if std::path::Path::new(&path).exists() {
-                    write_debug_log(&format!("Found WindowsApps path: {}", path));
+                if path.exists() {
+                    write_debug_log(&format!("Found WindowsApps path: {}", path.display()));
                     // Look for an exe file in this directory
                     if let Ok(entries) = std::fs::read_dir(&path) {
                         for entry in entries.flatten() {
@@ -179,42 +226,35 @@ fn convert_registry_path_to_file_path(registry_path: &str) -> Option<String> {
     if let Some(guid_end) = registry_path.find('}') {
         let path_part = &registry_path[guid_end + 1..];
 
-        // Get username from environment
-        let username = std::env::var("USERNAME").unwrap_or_else(|_| "Default".to_string());
-
-        // Try common installation directories
-        let possible_paths = [
-            // Program Files
-            format!("C:\\Program Files\\{}", path_part),
-            format!("C:\\Program Files (x86)\\{}", path_part),
-            // Local App Data
-            format!("C:\\Users\\{}\\AppData\\Local\\{}", username, path_part),
-            // Roaming App Data
-            format!("C:\\Users\\{}\\AppData\\Roaming\\{}", username, path_part),
-            // Direct path if it's already a full path
-            path_part.to_string(),
-        ];
+        let folders = known_folders::current();
+
+        // Try common installation directories (Program Files, Local and
+        // Roaming App Data), then the path itself in case it's already a full path
+        let relative = path_part.trim_start_matches('\\');
+        let mut possible_paths = folders
+            .install_dirs()
+            .into_iter()
+            .map(|dir| dir.join(relative))
+            .collect::<Vec<_>>();
+        possible_paths.push(std::path::PathBuf::from(path_part));
 
         // Check which path exists
         for path in possible_paths {
-            if std::path::Path::new(&path).exists() {
-                write_debug_log(&format!("Found existing path: {}", path));
-                return Some(path);
+            if path.exists() {
+                write_debug_log(&format!("Found existing path: {}", path.display()));
+                return Some(path.to_string_lossy().to_string());
             }
         }
 
         // If no path exists, try to construct a reasonable path
         // For VLC, it's usually in Program Files
         if path_part.contains("VideoLAN\\VLC") {
-            let vlc_path = format!("C:\\Program Files\\VideoLAN\\VLC\\vlc.exe");
-            if std::path::Path::new(&vlc_path).exists() {
-                write_debug_log(&format!("Found VLC at default location: {}", vlc_path));
-                return Some(vlc_path);
-            }
-            let vlc_path_x86 = format!("C:\\Program Files (x86)\\VideoLAN\\VLC\\vlc.exe");
-            if std::path::Path::new(&vlc_path_x86).exists() {
-                write_debug_log(&format!("Found VLC at x86 location: {}", vlc_path_x86));
-                return Some(vlc_path_x86);
+            for dir in folders.program_files_dirs() {
+                let vlc_path = dir.join("VideoLAN").join("VLC").join("vlc.exe");
+                if vlc_path.exists() {
+                    write_debug_log(&format!("Found VLC at: {}", vlc_path.display()));
+                    return Some(vlc_path.to_string_lossy().to_string());
+                }
             }
         }
     }
@@ -222,11 +262,197 @@ fn convert_registry_path_to_file_path(registry_path: &str) -> Option<String> {
     None
 }
 
+/// Show a user-visible error for an invalid command line
+unsafe fn show_usage_error(message: &str) {
+    let text = format!("{}\n\n{}", message, cli::USAGE);
+    let text_wide = utils::to_wide_string(&text);
+    MessageBoxW(
+        None,
+        PCWSTR(text_wide.as_ptr()),
+        w!("Oxistart"),
+        MB_OK | MB_ICONERROR,
+    );
+}
+
+/// Open the database in the data directory, falling back to a throwaway
+/// in-memory one so the launcher keeps working if the file can't be used
+fn open_database(options: &cli::Options) -> Database {
+    let path = data_dir::data_dir(options).join(db::DB_FILE_NAME);
+    match Database::open(&path) {
+        Ok(db) => {
+            write_debug_log(&format!("Opened database: {}", path.display()));
+            db
+        }
+        Err(e) => {
+            write_debug_log(&format!(
+                "Failed to open database {}: {:?}",
+                path.display(),
+                e
+            ));
+            Database::open_in_memory().expect("in-memory database")
+        }
+    }
+}
+
+/// Run `f` against the shared database
+fn with_database<T>(f: impl FnOnce(&mut Database) -> T) -> Option<T> {
+    DATABASE.get().map(|db| f(&mut db.lock().unwrap()))
+}
+
 // Custom message for background scan completion
 const WM_APP_SCAN_COMPLETE: u32 = WM_USER + 2;
 
+/// Held while this machine's sync log is written, so a launch published
+/// during the startup sync never appends the same launches twice
+static SYNC_LOCK: Mutex<()> = Mutex::new(());
+
+/// Sync with the configured directory, holding the database only to read
+/// what to publish and to merge what was read, never during the file work
+fn startup_sync() {
+    let _sync = SYNC_LOCK.lock().unwrap();
+    let Some(plan) =
+        with_database(|db| sync::configured_dir(db).map(|dir| sync::plan_sync(db, &dir))).flatten()
+    else {
+        return;
+    };
+    let result = plan.and_then(|plan| plan.exchange()).and_then(|exchange| {
+        with_database(|db| exchange.apply(db))
+            .unwrap_or_else(|| Err("Database is not open".to_string()))
+    });
+    match result {
+        Ok(report) => write_debug_log(&format!("Sync: {:?}", report)),
+        Err(e) => write_debug_log(&format!("Sync failed: {}", e)),
+    }
+}
+
+/// Append new launches to this machine's sync log on a background thread, so
+/// a slow or offline sync directory never holds up the launcher
+fn publish_launches() {
+    thread::spawn(|| {
+        let _sync = SYNC_LOCK.lock().unwrap();
+        let Some(Ok(publication)) =
+            with_database(|db| sync::configured_dir(db).map(|dir| sync::prepare_publish(db, &dir)))
+                .flatten()
+        else {
+            return;
+        };
+        if publication.is_empty() {
+            return;
+        }
+        match publication.write() {
+            Ok(()) => {
+                if let Some(Err(e)) = with_database(|db| publication.finish(db)) {
+                    write_debug_log(&format!("Publishing launches failed: {}", e));
+                }
+            }
+            Err(e) => write_debug_log(&format!("Publishing launches failed: {}", e)),
+        }
+    });
+}
+
+/// Held while a scan reads and updates the app list, so the startup scan and
+/// watcher rescans never work from each other's half-applied results
+static SCAN_LOCK: Mutex<()> = Mutex::new(());
+
+/// Run `scan` against the current app list and stored file records and have
+/// the window apply the diff it returns
+unsafe fn run_scan(
+    window_handle: isize,
+    scan: impl FnOnce(
+        &[AppEntry],
+        &[scanner::ScannedFile],
+        &std::collections::HashMap<String, i32>,
+    ) -> scanner::ScanOutput,
+) {
+    let _scan_guard = SCAN_LOCK.lock().unwrap();
+
+    let usage_map = with_database(|db| db.load_usage_map())
+        .and_then(|map| map.ok())
+        .unwrap_or_default();
+    let current = APP_MANAGER.lock().unwrap().apps().to_vec();
+    let previous_files = with_database(|db| db.load_scan_files(scanner::SCANNER_VERSION))
+        .and_then(|files| files.ok())
+        .unwrap_or_default();
+    let output = scan(&current, &previous_files, &usage_map);
+    let _ = with_database(|db| db.save_scan_files(&output.files, scanner::SCANNER_VERSION));
+    write_debug_log(&format!(
+        "Rescanned {} of {} Start Menu files: {} added, {} removed, {} changed, {} filtered",
+        output.reprocessed,
+        output.files.len(),
+        output.diff.added.len(),
+        output.diff.removed.len(),
+        output.diff.changed.len(),
+        output.dropped.len()
+    ));
+    let timings = &output.timings;
+    write_debug_log(&format!(
+        "Scan took {:?}: walk {:?}, parse {:?} on {} workers, settings {:?}, packages {:?}, \
+         programs {:?}, finish {:?}",
+        timings.total(),
+        timings.walk,
+        timings.parse,
+        timings.workers,
+        timings.settings,
+        timings.packages,
+        timings.programs,
+        timings.finish
+    ));
+
+    // The window applies the diff itself so it can keep the search and selection;
+    // `output` outlives the call because SendMessageW waits for the handler
+    SendMessageW(
+        HWND(window_handle),
+        WM_APP_SCAN_COMPLETE,
+        WPARAM(0),
+        LPARAM(&output.diff as *const ScanDiff as isize),
+    );
+
+    // Save to cache
+    let scanned_apps = APP_MANAGER.lock().unwrap().apps().to_vec();
+    let _ = with_database(|db| db.save_app_cache(&scanned_apps, scanner::SCANNER_VERSION));
+    write_debug_log(&format!("Saved {} apps to cache", scanned_apps.len()));
+}
+
+/// Apply `diff` to the app list and refresh the list view, keeping the current
+/// search and the selected app (if it is still there)
+unsafe fn apply_scan_diff(diff: ScanDiff) {
+    let mut manager = APP_MANAGER.lock().unwrap();
+
+    let sel = SendMessageW(
+        H_LIST,
+        LVM_GETNEXTITEM,
+        WPARAM(usize::MAX),
+        LPARAM(LVNI_SELECTED as isize),
+    );
+    let selected_key = (sel.0 != -1)
+        .then(|| manager.app_at_row(sel.0 as usize))
+        .flatten()
+        .map(|app| app.key().to_string());
+
+    manager.apply_diff(diff);
+    ui::update_listview(H_LIST, &manager);
+    if let Some(row) = selected_key.and_then(|key| manager.filtered_position(&key)) {
+        ui::select_item(H_LIST, row);
+    }
+}
+
 fn main() -> Result<()> {
     unsafe {
+        let options = match cli::parse_args(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(message) => {
+                show_usage_error(&message);
+                return Ok(());
+            }
+        };
+
+        debug_log::init(&data_dir::data_dir(&options));
+
+        if let Some(ref command) = options.command {
+            utils::attach_parent_console();
+            std::process::exit(commands::run(&options, command));
+        }
+
         CoInitializeEx(None, COINIT_APARTMENTTHREADED).ok();
 
         IS_DARK_MODE = utils::is_dark_mode();
@@ -234,12 +460,17 @@ fn main() -> Result<()> {
 
         ui::init_common_controls();
 
-        let _ = db::init_db();
+        let _ = DATABASE.set(Mutex::new(open_database(&options)));
 
         // First, load from cache for fast startup
         {
             let mut manager = APP_MANAGER.lock().unwrap();
-            let cached_apps = db::load_app_cache();
+            if let Err(e) = manager.enable_search_index(SEARCH_INDEX_MIN_APPS) {
+                write_debug_log(&format!("Search index unavailable: {:?}", e));
+            }
+            let cached_apps = with_database(|db| db.load_app_cache(scanner::SCANNER_VERSION))
+                .and_then(|apps| apps.ok())
+                .unwrap_or_default();
             if !cached_apps.is_empty() {
                 write_debug_log(&format!("Loaded {} apps from cache", cached_apps.len()));
                 for app in cached_apps {
@@ -289,41 +520,68 @@ fn main() -> Result<()> {
         KEYBOARD_HOOK = hooks::setup_keyboard_hook(instance.into(), Some(keyboard_hook))?;
         MOUSE_HOOK = hooks::setup_mouse_hook(instance.into(), Some(mouse_hook))?;
 
+        let config_path = data_dir::data_dir(&options).join(config::CONFIG_FILE_NAME);
+        let config = config::Config::load(&config_path).unwrap_or_else(|e| {
+            write_debug_log(&e);
+            config::Config::default()
+        });
+        let roots = scanner::scan_roots(&config.scan, known_folders::current());
+        let rules = filter_rules::FilterRules::with_overrides(&config.filters.rules)
+            .unwrap_or_else(|e| {
+                write_debug_log(&e);
+                filter_rules::FilterRules::default()
+            });
+
         // Start background scan
         let window_handle = MY_WINDOW.0;
         thread::spawn(move || {
             // Initialize COM for this thread
             let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
 
-            // Scan apps in background
-            let mut temp_manager = AppManager::new();
-            scanner::scan_apps(&mut temp_manager);
-
-            // Store scanned apps in a global location
-            let scanned_apps: Vec<AppEntry> = temp_manager.apps().to_vec();
-
-            // Save to cache
-            let _ = db::save_app_cache(&scanned_apps);
-            write_debug_log(&format!("Saved {} apps to cache", scanned_apps.len()));
+            // Pick up launches made on other machines before ranking
+            startup_sync();
+
+            // Older scans stored targets with unexpanded %VARIABLES%; move their
+            // history over to the expanded paths the scanner now produces
+            match with_database(|db| {
+                db.rename_entry_ids(|id| {
+                    Some(env_vars::expand(id, &known_folders::ProcessEnvironment))
+                })
+            }) {
+                Some(Ok(0)) | None => {}
+                Some(Ok(count)) => write_debug_log(&format!("Expanded {} entry ids", count)),
+                Some(Err(e)) => write_debug_log(&format!("Failed to expand entry ids: {:?}", e)),
+            }
 
-            // Update the main manager
+            // Enforce the retention policy before the counts are used for ranking
+            if let Some(Err(e)) =
+                with_database(|db| db.apply_retention(db::now_timestamp(), |path| path.exists()))
             {
-                let mut manager = APP_MANAGER.lock().unwrap();
-                manager.clear();
-                for app in scanned_apps {
-                    manager.add_app(app);
-                }
-                manager.sort_by_usage();
-                manager.filter("");
+                write_debug_log(&format!("Failed to apply retention: {:?}", e));
             }
 
-            // Notify main window to refresh UI
-            let _ = PostMessageW(
-                HWND(window_handle),
-                WM_APP_SCAN_COMPLETE,
-                WPARAM(0),
-                LPARAM(0),
-            );
+            // Scan apps in background
+            run_scan(window_handle, |current, previous_files, usage_map| {
+                scanner::scan_apps(&roots, &rules, current, previous_files, usage_map)
+            });
+
+            // Pick up apps installed or removed while Oxistart runs
+            let watched = roots.iter().map(|root| root.path.clone()).collect();
+            watcher::watch(watched, move |folders| {
+                write_debug_log(&format!("Start Menu changed: {:?}", folders));
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                run_scan(window_handle, |current, previous_files, usage_map| {
+                    scanner::rescan_subtrees(
+                        &roots,
+                        &rules,
+                        &folders,
+                        current,
+                        previous_files,
+                        usage_map,
+                    )
+                });
+                CoUninitialize();
+            });
 
             CoUninitialize();
         });
@@ -342,6 +600,14 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Read the current contents of the search box
+unsafe fn get_search_text() -> String {
+    let len = GetWindowTextLengthW(H_EDIT);
+    let mut buffer = vec![0u16; (len + 1) as usize];
+    GetWindowTextW(H_EDIT, &mut buffer);
+    String::from_utf16_lossy(&buffer[..len as usize])
+}
+
 unsafe fn update_filter(search: &str) {
     // Check if it's a mathematical expression
     if calculator::is_math_expression(search) {
@@ -447,7 +713,7 @@ unsafe extern "system" fn wnd_proc(
             SendMessageW(H_LIST, WM_SETFONT, WPARAM(H_FONT.0 as usize), LPARAM(1));
 
             // Use large image list for better visibility
-            let sys_img_list = ui::get_system_image_list();
+            let sys_img_list = utils::get_system_image_list();
             if sys_img_list != 0 {
                 SendMessageW(
                     H_LIST,
@@ -525,15 +791,15 @@ unsafe extern "system" fn wnd_proc(
             let id = wparam.0 & 0xFFFF;
             let code = (wparam.0 >> 16) & 0xFFFF;
             if id == ID_EDIT as usize && code == EN_CHANGE as usize {
-                let len = GetWindowTextLengthW(H_EDIT);
-                let mut buffer = vec![0u16; (len + 1) as usize];
-                GetWindowTextW(H_EDIT, &mut buffer);
-                let text = String::from_utf16_lossy(&buffer[..len as usize]);
+                let text = get_search_text();
                 update_filter(&text);
             }
             if id == ID_TRAY_EXIT {
                 PostQuitMessage(0);
             }
+            if id == ID_TRAY_INCOGNITO {
+                let _ = with_database(|db| db.set_incognito(!db.is_incognito()));
+            }
             LRESULT(0)
         }
         WM_NOTIFY => {
@@ -575,9 +841,10 @@ unsafe extern "system" fn wnd_proc(
             }
         }
         WM_APP_SCAN_COMPLETE => {
-            // Background scan completed, refresh the listview
+            // Background scan completed; lparam points at its diff
             write_debug_log("Background scan completed, refreshing UI");
-            update_filter("");
+            let diff = &*(lparam.0 as *const ScanDiff);
+            apply_scan_diff(diff.clone());
             LRESULT(0)
         }
         WM_APP_TRAY => {
@@ -586,6 +853,19 @@ unsafe extern "system" fn wnd_proc(
                 let _ = GetCursorPos(&mut pt);
                 SetForegroundWindow(hwnd);
                 let hmenu = CreatePopupMenu().unwrap_or(HMENU(0));
+                let incognito = with_database(|db| db.is_incognito()).unwrap_or(false);
+                let incognito_flags = if incognito {
+                    MF_STRING | MF_CHECKED
+                } else {
+                    MF_STRING
+                };
+                let _ = AppendMenuW(
+                    hmenu,
+                    incognito_flags,
+                    ID_TRAY_INCOGNITO,
+                    w!("Incognito (don't record launches)"),
+                );
+                let _ = AppendMenuW(hmenu, MF_SEPARATOR, 0, PCWSTR::null());
                 let _ = AppendMenuW(hmenu, MF_STRING, ID_TRAY_EXIT, w!("Exit Oxistart"));
                 TrackPopupMenu(
                     hmenu,
@@ -647,6 +927,10 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
     if sel.0 == -1 {
         return;
     }
+    // In browse mode, launching a folder opens it
+    if open_selected_folder() {
+        return;
+    }
 
     let mut item = LVITEMW {
         mask: LVIF_PARAM,
@@ -661,9 +945,25 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
         LPARAM(&mut item as *mut _ as isize),
     );
     let app_idx = item.lParam.0 as usize;
+    // The result rank counts app rows only, not the folders listed while browsing
+    let rank = APP_MANAGER
+        .lock()
+        .unwrap()
+        .app_position(sel.0 as usize)
+        .map(|position| position as i32);
+    let query = get_search_text();
 
     // Obtener la información necesaria Y liberar el lock ANTES de ejecutar la app
-    let (parse_name, parse_name_wide, arguments, arguments_wide, entry_type) = {
+    let (
+        parse_name,
+        parse_name_wide,
+        arguments,
+        arguments_wide,
+        entry_type,
+        working_dir,
+        show_cmd,
+        hotkey,
+    ) = {
         let manager = APP_MANAGER.lock().unwrap();
         if let Some(app) = manager.apps().get(app_idx) {
             let parse_name = app.parse_name.clone();
@@ -671,18 +971,45 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
             let arguments = app.arguments.clone();
             let arguments_wide = arguments.as_ref().map(|a| utils::to_wide_string(a));
             let entry_type = app.entry_type.clone();
+            // The shortcut's "Start in" folder and window state, as Explorer would use them
+            let working_dir = app.working_dir.clone();
+            let show_cmd = app.show_cmd.map(SHOW_WINDOW_CMD).unwrap_or(SW_SHOWNORMAL);
+            let hotkey = app.shell_hotkey();
             (
                 parse_name,
                 parse_name_wide,
                 arguments,
                 arguments_wide,
                 entry_type,
+                working_dir,
+                show_cmd,
+                hotkey,
             )
         } else {
             return;
         }
     }; // El lock se libera aquí
 
+    // Una app empaquetada no tiene archivo que seleccionar en el Explorador y
+    // no se puede ejecutar como administrador desde su entrada de AppsFolder
+    if (open_location || as_admin) && appx::is_packaged(&parse_name) {
+        write_debug_log(&format!(
+            "Ignored {} for packaged app: {}",
+            if open_location {
+                "open location"
+            } else {
+                "run as admin"
+            },
+            parse_name
+        ));
+        return;
+    }
+
+    let working_dir_wide = working_dir.as_ref().map(|dir| utils::to_wide_string(dir));
+    let working_dir_ptr = working_dir_wide
+        .as_ref()
+        .map_or(PCWSTR::null(), |dir| PCWSTR(dir.as_ptr()));
+
     // Handle different entry types
     match entry_type {
         AppEntryType::Settings => {
@@ -735,8 +1062,8 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
                     verb,
                     PCWSTR(parse_name_wide.as_ptr()),
                     None,
-                    None,
-                    SW_SHOW,
+                    working_dir_ptr,
+                    show_cmd,
                 );
                 if result.0 <= 32 {
                     write_debug_log(&format!(
@@ -747,8 +1074,8 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
             } else {
                 // Normal execution
                 write_debug_log(&format!(
-                    "Launching normally: {} (args: {:?})",
-                    parse_name, arguments
+                    "Launching normally: {} (args: {:?}, dir: {:?})",
+                    parse_name, arguments, working_dir
                 ));
 
                 // Handle special parse names
@@ -797,25 +1124,16 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
                 let final_parse_name_wide = utils::to_wide_string(&final_parse_name);
 
                 // Pass arguments if available
-                let result = if let Some(ref args_wide) = arguments_wide {
-                    ShellExecuteW(
-                        None,
-                        w!("open"),
-                        PCWSTR(final_parse_name_wide.as_ptr()),
-                        PCWSTR(args_wide.as_ptr()),
-                        None,
-                        SW_SHOW,
-                    )
-                } else {
-                    ShellExecuteW(
-                        None,
-                        w!("open"),
-                        PCWSTR(final_parse_name_wide.as_ptr()),
-                        None,
-                        None,
-                        SW_SHOW,
-                    )
-                };
+                let args_ptr = arguments_wide
+                    .as_ref()
+                    .map_or(PCWSTR::null(), |args| PCWSTR(args.as_ptr()));
+                let result = shell_open(
+                    PCWSTR(final_parse_name_wide.as_ptr()),
+                    args_ptr,
+                    working_dir_ptr,
+                    show_cmd,
+                    hotkey,
+                );
 
                 if result.0 <= 32 {
                     write_debug_log(&format!(
@@ -898,9 +1216,29 @@ unsafe fn launch_selected_app_with_modifier(as_admin: bool, open_location: bool)
 
             // Update usage only for applications, not for settings
             {
+                let action = if open_location {
+                    db::LaunchAction::Location
+                } else if as_admin {
+                    db::LaunchAction::Admin
+                } else {
+                    db::LaunchAction::Open
+                };
+                let event = db::LaunchEvent::now(&parse_name, action, Some(&query), rank);
+                let recorded = with_database(|db| {
+                    if db.is_incognito() {
+                        return false;
+                    }
+                    let _ = db.record_launch(&event);
+                    true
+                })
+                .unwrap_or(true);
+                if recorded {
+                    publish_launches();
+                }
                 let mut manager = APP_MANAGER.lock().unwrap();
-                let _ = db::increment_usage(&parse_name);
-                manager.increment_usage(app_idx);
+                if recorded {
+                    manager.increment_usage(app_idx);
+                }
                 manager.sort_by_usage();
                 manager.filter("");
             }
@@ -919,6 +1257,70 @@ unsafe fn launch_selected_app() {
     launch_selected_app_with_modifier(false, false);
 }
 
+/// Show the apps and subfolders of `folder` (empty for the top level) and where we are
+unsafe fn browse_folder(folder: Vec<String>) {
+    let title = if folder.is_empty() {
+        "All folders".to_string()
+    } else {
+        folder.join(" › ")
+    };
+    let title_wide = utils::to_wide_string(&title);
+    let _ = SetWindowTextW(H_CALC_LABEL, PCWSTR(title_wide.as_ptr()));
+    ShowWindow(H_CALC_LABEL, SW_SHOW);
+
+    let mut manager = APP_MANAGER.lock().unwrap();
+    manager.browse(folder);
+    ui::update_listview(H_LIST, &manager);
+}
+
+/// Enter the folder selected in browse mode; false if the selection isn't a folder
+unsafe fn open_selected_folder() -> bool {
+    let sel = SendMessageW(
+        H_LIST,
+        LVM_GETNEXTITEM,
+        WPARAM(usize::MAX),
+        LPARAM(LVNI_SELECTED as isize),
+    );
+    let folder = {
+        let manager = APP_MANAGER.lock().unwrap();
+        let (Some(current), Some(name)) = (
+            manager.browsed_folder(),
+            manager.folder_at_row(sel.0 as usize),
+        ) else {
+            return false;
+        };
+        let mut folder = current.to_vec();
+        folder.push(name.to_string());
+        folder
+    };
+    browse_folder(folder);
+    true
+}
+
+/// Go to the parent of the browsed folder; false when not browsing or at the top
+unsafe fn browse_parent_folder() -> bool {
+    let parent = {
+        let manager = APP_MANAGER.lock().unwrap();
+        match manager.browsed_folder() {
+            Some(folder) if !folder.is_empty() => folder[..folder.len() - 1].to_vec(),
+            _ => return false,
+        }
+    };
+    browse_folder(parent);
+    true
+}
+
+/// Switch between search results and browsing the Start Menu folders
+unsafe fn toggle_browse_mode() {
+    let browsing = APP_MANAGER.lock().unwrap().browsed_folder().is_some();
+    // Clearing the search box shows every app and leaves browse mode
+    let _ = SetWindowTextW(H_EDIT, w!(""));
+    update_filter("");
+    if !browsing {
+        browse_folder(Vec::new());
+    }
+}
+
 unsafe fn launch_selected_app_as_admin() {
     launch_selected_app_with_modifier(true, false);
 }
@@ -927,6 +1329,91 @@ unsafe fn launch_selected_app_location() {
     launch_selected_app_with_modifier(false, true);
 }
 
+/// Run the selected program's uninstaller after asking to confirm
+unsafe fn uninstall_selected_app() {
+    let sel = SendMessageW(
+        H_LIST,
+        LVM_GETNEXTITEM,
+        WPARAM(usize::MAX),
+        LPARAM(LVNI_SELECTED as isize),
+    );
+    if sel.0 == -1 {
+        return;
+    }
+    let mut item = LVITEMW {
+        mask: LVIF_PARAM,
+        iItem: sel.0 as i32,
+        iSubItem: 0,
+        ..Default::default()
+    };
+    SendMessageW(
+        H_LIST,
+        LVM_GETITEMW,
+        WPARAM(0),
+        LPARAM(&mut item as *mut _ as isize),
+    );
+    let (name, program_name, command) = {
+        let manager = APP_MANAGER.lock().unwrap();
+        match manager.apps().get(item.lParam.0 as usize) {
+            Some(app) => match &app.uninstall {
+                Some(command) => (
+                    app.name.clone(),
+                    app.uninstall_name
+                        .clone()
+                        .unwrap_or_else(|| app.name.clone()),
+                    command.clone(),
+                ),
+                None => return,
+            },
+            None => return,
+        }
+    };
+
+    // Name the program that will be removed, which may be more than this entry
+    let message = if program_name == name {
+        format!("Uninstall {}?", program_name)
+    } else {
+        format!("Uninstall {}?\n\n{} is part of it.", program_name, name)
+    };
+    let message_wide = utils::to_wide_string(&message);
+    let answer = MessageBoxW(
+        MY_WINDOW,
+        PCWSTR(message_wide.as_ptr()),
+        w!("Oxistart"),
+        MB_YESNO | MB_ICONQUESTION,
+    );
+    if answer != IDYES {
+        return;
+    }
+
+    // Not a launch: usage isn't recorded
+    let (program, arguments) = uninstall::split_command(&command);
+    let program_wide = utils::to_wide_string(&program);
+    let arguments_wide = arguments.as_ref().map(|a| utils::to_wide_string(a));
+    write_debug_log(&format!("Uninstalling {}: {}", program_name, command));
+    let result = ShellExecuteW(
+        None,
+        w!("open"),
+        PCWSTR(program_wide.as_ptr()),
+        arguments_wide
+            .as_ref()
+            .map_or(PCWSTR::null(), |a| PCWSTR(a.as_ptr())),
+        None,
+        SW_SHOWNORMAL,
+    );
+    if result.0 <= 32 {
+        write_debug_log(&format!(
+            "Failed to uninstall: {} (error code: {})",
+            name, result.0
+        ));
+        show_launch_error(&name, result.0 as u32);
+    }
+
+    ShowWindow(MY_WINDOW, SW_HIDE);
+    let _ = SetWindowTextW(H_EDIT, w!(""));
+    update_filter("");
+}
+
 /// Open the native Windows Start menu by clicking the Start button
 unsafe fn open_native_start_menu() {
     // Find the Start button and click it
@@ -1093,6 +1580,20 @@ unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARA
                     }
                     return LRESULT(1);
                 }
+                if kbd.vkCode == VK_TAB.0 as u32 {
+                    toggle_browse_mode();
+                    return LRESULT(1);
+                }
+                // In browse mode Right opens the selected folder, Left and
+                // Backspace go back up
+                if kbd.vkCode == VK_RIGHT.0 as u32 && open_selected_folder() {
+                    return LRESULT(1);
+                }
+                if (kbd.vkCode == VK_LEFT.0 as u32 || kbd.vkCode == VK_BACK.0 as u32)
+                    && browse_parent_folder()
+                {
+                    return LRESULT(1);
+                }
                 if kbd.vkCode == VK_RETURN.0 as u32 {
                     let is_alt_pressed = GetKeyState(VK_MENU.0 as i32) < 0;
                     let is_shift_pressed = GetKeyState(VK_SHIFT.0 as i32) < 0;
@@ -1109,6 +1610,10 @@ unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARA
                     }
                     return LRESULT(1);
                 }
+                if kbd.vkCode == VK_DELETE.0 as u32 && GetKeyState(VK_CONTROL.0 as i32) < 0 {
+                    uninstall_selected_app();
+                    return LRESULT(1);
+                }
                 if kbd.vkCode == VK_ESCAPE.0 as u32 {
                     if IsWindowVisible(MY_WINDOW).as_bool() {
                         toggle_menu();
@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Upper bound on worker threads, however many cores there are; parsing
+/// shortcuts is mostly waiting on the disk and the shell
+pub const MAX_WORKERS: usize = 8;
+
+/// How many workers to use on this machine
+pub fn default_workers() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_WORKERS)
+}
+
+/// Apply `work` to every item on up to `workers` threads and return the
+/// results in the order of `items`, whatever order they finish in.
+///
+/// Each worker calls `setup` once before its first item and keeps the value
+/// until it is done, e.g. a guard that initializes COM for the thread.
+pub fn map_ordered<T, R, S>(
+    items: &[T],
+    workers: usize,
+    setup: impl Fn() -> S + Sync,
+    work: impl Fn(&T) -> R + Sync,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let workers = workers.clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                let _state = setup();
+                let mut done = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    done.push((index, work(item)));
+                }
+                results.lock().unwrap().extend(done);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_results_keep_input_order() {
+        let items = (0..100).collect::<Vec<u64>>();
+        let results = map_ordered(
+            &items,
+            4,
+            || (),
+            |&n| {
+                // Later items finish first
+                std::thread::sleep(Duration::from_micros(100 - n));
+                n * 2
+            },
+        );
+        assert_eq!(results, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_concurrency_is_bounded() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let setups = AtomicUsize::new(0);
+
+        map_ordered(
+            &[(); 50],
+            3,
+            || setups.fetch_add(1, Ordering::SeqCst),
+            |_| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(1));
+                running.fetch_sub(1, Ordering::SeqCst);
+            },
+        );
+
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(setups.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_no_items() {
+        let results: Vec<u8> = map_ordered(&[] as &[u8], 4, || (), |&n| n);
+        assert!(results.is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+use crate::utils;
+use windows::{core::*, Win32::System::Registry::*};
+
+/// A value read from the registry
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegValue {
+    /// `REG_SZ` or `REG_EXPAND_SZ`, variables left unexpanded
+    String(String),
+    Dword(u32),
+    /// Any other type, which nothing here needs to read
+    Other,
+}
+
+/// Where registry keys and values come from, so providers can be tested with
+/// a `.reg` export instead of the machine's registry.
+///
+/// Keys are full paths starting with the hive's long name, as in `.reg`
+/// files: `HKEY_LOCAL_MACHINE\SOFTWARE\...`. Names are case-insensitive.
+pub trait RegistryReader {
+    /// Names of the subkeys of `key`; empty if it doesn't exist
+    fn subkeys(&self, key: &str) -> Vec<String>;
+
+    /// The value `name` of `key` (`""` for the default value)
+    fn value(&self, key: &str, name: &str) -> Option<RegValue>;
+
+    /// The value `name` of `key` if it is a non-empty string
+    fn string(&self, key: &str, name: &str) -> Option<String> {
+        match self.value(key, name)? {
+            RegValue::String(value) if !value.is_empty() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value `name` of `key` if it is a DWORD
+    fn dword(&self, key: &str, name: &str) -> Option<u32> {
+        match self.value(key, name)? {
+            RegValue::Dword(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// The registry of this machine
+pub struct Win32Registry;
+
+impl Win32Registry {
+    /// Open `key` for reading; the caller closes it
+    unsafe fn open(key: &str) -> Option<HKEY> {
+        let (hive, path) = key.split_once('\\').unwrap_or((key, ""));
+        let hive = match hive.to_uppercase().as_str() {
+            "HKEY_LOCAL_MACHINE" | "HKLM" => HKEY_LOCAL_MACHINE,
+            "HKEY_CURRENT_USER" | "HKCU" => HKEY_CURRENT_USER,
+            "HKEY_CLASSES_ROOT" | "HKCR" => HKEY_CLASSES_ROOT,
+            "HKEY_USERS" | "HKU" => HKEY_USERS,
+            _ => return None,
+        };
+        let path = utils::to_wide_string(path);
+        let mut handle = HKEY::default();
+        RegOpenKeyExW(hive, PCWSTR(path.as_ptr()), 0, KEY_READ, &mut handle).ok()?;
+        Some(handle)
+    }
+}
+
+impl RegistryReader for Win32Registry {
+    fn subkeys(&self, key: &str) -> Vec<String> {
+        unsafe {
+            let Some(handle) = Self::open(key) else {
+                return Vec::new();
+            };
+            let mut names = Vec::new();
+            for index in 0.. {
+                // Key names are at most 255 characters
+                let mut name = [0u16; 256];
+                let mut len = name.len() as u32;
+                let found = RegEnumKeyExW(
+                    handle,
+                    index,
+                    PWSTR(name.as_mut_ptr()),
+                    &mut len,
+                    None,
+                    PWSTR::null(),
+                    None,
+                    None,
+                );
+                if found.is_err() {
+                    break;
+                }
+                names.push(String::from_utf16_lossy(&name[..len as usize]));
+            }
+            let _ = RegCloseKey(handle);
+            names
+        }
+    }
+
+    fn value(&self, key: &str, name: &str) -> Option<RegValue> {
+        unsafe {
+            let handle = Self::open(key)?;
+            let name = utils::to_wide_string(name);
+            let mut kind = REG_VALUE_TYPE::default();
+            let mut size = 0u32;
+            // Ask for the size first, then read the data
+            let sized = RegQueryValueExW(
+                handle,
+                PCWSTR(name.as_ptr()),
+                None,
+                Some(&mut kind),
+                None,
+                Some(&mut size),
+            );
+            let mut data = vec![0u8; size as usize];
+            let read = sized.and_then(|_| {
+                RegQueryValueExW(
+                    handle,
+                    PCWSTR(name.as_ptr()),
+                    None,
+                    Some(&mut kind),
+                    Some(data.as_mut_ptr()),
+                    Some(&mut size),
+                )
+            });
+            let _ = RegCloseKey(handle);
+            read.ok()?;
+            data.truncate(size as usize);
+
+            Some(match kind {
+                REG_SZ | REG_EXPAND_SZ => {
+                    let wide = data
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                        .collect::<Vec<_>>();
+                    let text = String::from_utf16_lossy(&wide);
+                    RegValue::String(text.trim_end_matches('\0').to_string())
+                }
+                REG_DWORD if data.len() >= 4 => {
+                    RegValue::Dword(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+                }
+                _ => RegValue::Other,
+            })
+        }
+    }
+}
+
+/// Keys and values read from a `.reg` export, for tests
+#[cfg(test)]
+pub struct RegFile {
+    /// Key path as written, and its values
+    keys: Vec<(String, Vec<(String, RegValue)>)>,
+}
+
+#[cfg(test)]
+impl RegFile {
+    /// Parse an export as `regedit` writes it: UTF-16 with a byte order mark,
+    /// or UTF-8 for hand-written fixtures
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, String> {
+        let text = match bytes {
+            [0xFF, 0xFE, rest @ ..] => {
+                let wide = rest
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect::<Vec<_>>();
+                String::from_utf16(&wide).map_err(|e| e.to_string())?
+            }
+            _ => String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())?,
+        };
+        Self::parse(&text)
+    }
+
+    /// Parse the text of an export
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        // A trailing backslash continues a hex value on the next line
+        let text = text.replace("\\\n", "");
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        match lines.next() {
+            Some("Windows Registry Editor Version 5.00") | Some("REGEDIT4") => {}
+            _ => return Err("Not a registry export".to_string()),
+        }
+
+        let mut keys: Vec<(String, Vec<(String, RegValue)>)> = Vec::new();
+        for line in lines {
+            if line.starts_with(';') {
+                continue;
+            }
+            if let Some(key) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                // `[-KEY]` deletes a key; there is nothing to read
+                if !key.starts_with('-') {
+                    keys.push((key.to_string(), Vec::new()));
+                }
+                continue;
+            }
+            let Some((_, values)) = keys.last_mut() else {
+                return Err(format!("Value outside a key: {}", line));
+            };
+            let (name, data) = parse_value_name(line)?;
+            if let Some(value) = parse_value_data(data)? {
+                values.push((name, value));
+            }
+        }
+        Ok(Self { keys })
+    }
+}
+
+#[cfg(test)]
+impl RegistryReader for RegFile {
+    fn subkeys(&self, key: &str) -> Vec<String> {
+        let prefix = format!("{}\\", key.to_lowercase());
+        let mut names: Vec<String> = Vec::new();
+        for (path, _) in &self.keys {
+            if !path.to_lowercase().starts_with(&prefix) {
+                continue;
+            }
+            // Intermediate keys exist even without a section of their own
+            let child = path[prefix.len()..].split('\\').next().unwrap_or_default();
+            if !names.iter().any(|name| name.eq_ignore_ascii_case(child)) {
+                names.push(child.to_string());
+            }
+        }
+        names
+    }
+
+    fn value(&self, key: &str, name: &str) -> Option<RegValue> {
+        self.keys
+            .iter()
+            .filter(|(path, _)| path.eq_ignore_ascii_case(key))
+            .flat_map(|(_, values)| values)
+            .rev()
+            .find(|(value_name, _)| value_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+    }
+}
+
+/// Split `"Name"=data` or `@=data` into the name and the data
+#[cfg(test)]
+fn parse_value_name(line: &str) -> std::result::Result<(String, &str), String> {
+    if let Some(data) = line.strip_prefix("@=") {
+        return Ok((String::new(), data));
+    }
+    let (name, rest) = parse_quoted(line).ok_or_else(|| format!("Bad value: {}", line))?;
+    let data = rest
+        .strip_prefix('=')
+        .ok_or_else(|| format!("Bad value: {}", line))?;
+    Ok((name, data))
+}
+
+/// The value of `data`, or `None` for a deletion (`-`) or a type nothing reads
+#[cfg(test)]
+fn parse_value_data(data: &str) -> std::result::Result<Option<RegValue>, String> {
+    if data == "-" {
+        return Ok(None);
+    }
+    if data.starts_with('"') {
+        let (value, rest) = parse_quoted(data).ok_or_else(|| format!("Bad string: {}", data))?;
+        if !rest.trim().is_empty() {
+            return Err(format!("Bad string: {}", data));
+        }
+        return Ok(Some(RegValue::String(value)));
+    }
+    if let Some(hex) = data.strip_prefix("dword:") {
+        let value =
+            u32::from_str_radix(hex, 16).map_err(|e| format!("Bad dword {}: {}", hex, e))?;
+        return Ok(Some(RegValue::Dword(value)));
+    }
+    if let Some(hex) = data.strip_prefix("hex(2):") {
+        // REG_EXPAND_SZ, written as UTF-16LE bytes
+        let bytes = hex
+            .split(',')
+            .map(|byte| u8::from_str_radix(byte.trim(), 16))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| format!("Bad hex value {}: {}", hex, e))?;
+        let wide = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        let text = String::from_utf16_lossy(&wide);
+        return Ok(Some(RegValue::String(
+            text.trim_end_matches('\0').to_string(),
+        )));
+    }
+    if data.starts_with("hex") {
+        return Ok(Some(RegValue::Other));
+    }
+    Err(format!("Unknown value type: {}", data))
+}
+
+/// Read a `"quoted"` string with `\\` and `\"` escapes; returns it and the rest
+#[cfg(test)]
+fn parse_quoted(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?.1),
+            '"' => return Some((value, &text[i + 2..])),
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"Windows Registry Editor Version 5.00
+
+; A comment
+[HKEY_CURRENT_USER\Software\Demo]
+@="Default"
+"Path"="C:\\Program Files\\Demo\\demo.exe"
+"Quoted"="say \"hi\""
+"Count"=dword:0000002a
+"Expand"=hex(2):25,00,54,00,45,00,4d,00,50,00,25,00,5c,00,78,00,00,00
+"Blob"=hex:01,02,\
+  03
+
+[HKEY_CURRENT_USER\Software\Demo\Child One]
+"Gone"=-
+
+[HKEY_CURRENT_USER\Software\Demo\Deep\Nested]
+"#;
+
+    #[test]
+    fn test_values() {
+        let reg = RegFile::parse(EXPORT).unwrap();
+        let key = "HKEY_CURRENT_USER\\Software\\Demo";
+
+        assert_eq!(reg.string(key, ""), Some("Default".to_string()));
+        assert_eq!(
+            reg.string("hkey_current_user\\software\\demo", "path"),
+            Some("C:\\Program Files\\Demo\\demo.exe".to_string())
+        );
+        assert_eq!(reg.string(key, "Quoted"), Some("say \"hi\"".to_string()));
+        assert_eq!(reg.dword(key, "Count"), Some(42));
+        assert_eq!(reg.string(key, "Expand"), Some("%TEMP%\\x".to_string()));
+        assert_eq!(reg.value(key, "Blob"), Some(RegValue::Other));
+        assert_eq!(reg.string(key, "Count"), None);
+        assert_eq!(reg.value(key, "Missing"), None);
+        assert_eq!(reg.value(&format!("{}\\Child One", key), "Gone"), None);
+    }
+
+    #[test]
+    fn test_subkeys() {
+        let reg = RegFile::parse(EXPORT).unwrap();
+        assert_eq!(
+            reg.subkeys("HKEY_CURRENT_USER\\Software\\Demo"),
+            vec!["Child One", "Deep"]
+        );
+        assert_eq!(
+            reg.subkeys("HKEY_CURRENT_USER\\Software\\Demo\\Deep"),
+            vec!["Nested"]
+        );
+        assert!(reg.subkeys("HKEY_LOCAL_MACHINE\\Software").is_empty());
+    }
+
+    #[test]
+    fn test_utf16_export() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in EXPORT.replace('\n', "\r\n").encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        let reg = RegFile::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            reg.dword("HKEY_CURRENT_USER\\Software\\Demo", "Count"),
+            Some(42)
+        );
+    }
+
+    #[test]
+    fn test_invalid_exports_are_errors() {
+        assert!(RegFile::parse("[HKEY_CURRENT_USER\\X]").is_err());
+        assert!(RegFile::parse("REGEDIT4\n\"Orphan\"=\"x\"").is_err());
+        assert!(RegFile::parse("REGEDIT4\n[HKEY_CURRENT_USER\\X]\n\"A\"=dword:zz").is_err());
+    }
+}
@@ -0,0 +1,608 @@
+use crate::app_model::{AppEntry, AppEntryType, DuplicateReason};
+use crate::config::ScanRoot;
+use crate::scanner::{Collision, ScanOutput, ScanTimings};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Output format of `oxistart scan --report` and `--broken`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// What `oxistart scan` prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanView {
+    /// One line with the totals
+    Summary,
+    /// Every file and decision (`--report`)
+    Report(ReportFormat),
+    /// Shortcuts whose target is missing, to clean up (`--broken`)
+    Broken(ReportFormat),
+}
+
+impl ReportFormat {
+    /// Parse the value of `--format`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(ReportFormat::Text),
+            "json" => Some(ReportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// What became of a file the scanner visited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    /// Its entry is listed
+    Kept,
+    /// A deny rule dropped its entry
+    Filtered,
+    /// Its entry duplicated one listed from another file
+    Duplicate,
+    /// It produced no entry (unreadable, broken or not a shortcut)
+    Unparsed,
+}
+
+/// An entry as the scanner parsed it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryReport {
+    pub name: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+    /// `application` or `settings`
+    pub kind: &'static str,
+    /// The shortcut or file the entry was read from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Start Menu folder, relative to its root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// The shortcut's comment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Extra search words, e.g. from the target's version resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<String>,
+    /// The shortcut's "Start in" directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    /// The shortcut's key, e.g. `Ctrl+Alt+N`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<String>,
+    /// Command that uninstalls the entry's program
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uninstall: Option<String>,
+    /// The target was missing (see `AppEntry::broken`)
+    pub broken: bool,
+}
+
+/// The listed entry a duplicate lost to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateOf {
+    pub name: String,
+    pub target: String,
+    /// `same-target` or `same-name`
+    pub reason: &'static str,
+}
+
+/// One visited file, or an entry from elsewhere that was left out
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileReport {
+    /// The file, or where an entry not read from a file came from (see
+    /// `DroppedFile::path`)
+    pub path: String,
+    /// The parse result, before filtering
+    pub entry: Option<EntryReport>,
+    pub decision: Decision,
+    /// The rule that decided: the deny rule that dropped the entry, or the
+    /// allow rule that kept it despite a deny rule
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<DuplicateOf>,
+}
+
+/// Time spent in each phase (see `ScanTimings`), in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimingsReport {
+    pub total_ms: u128,
+    pub walk_ms: u128,
+    pub parse_ms: u128,
+    pub workers: usize,
+    pub settings_ms: u128,
+    pub packages_ms: u128,
+    pub programs_ms: u128,
+    pub finish_ms: u128,
+}
+
+/// Everything a scan saw and decided, for finding out why an app is missing
+/// from the list (or why it is there)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanReport {
+    pub roots: Vec<String>,
+    pub files: Vec<FileReport>,
+    /// Packaged apps, installed programs and Settings pages that were filtered
+    /// out or duplicated another entry
+    pub left_out: Vec<FileReport>,
+    /// The list the scan produced, including Settings items
+    pub entries: Vec<EntryReport>,
+    pub timings: TimingsReport,
+}
+
+impl ScanReport {
+    /// Describe `output`, a scan of `roots`, from the decisions it recorded.
+    /// The scan must have been given no current entries, so its diff adds
+    /// every entry.
+    pub fn new(roots: &[ScanRoot], output: &ScanOutput) -> Self {
+        let files = output
+            .files
+            .iter()
+            .map(|file| {
+                let mut report = FileReport {
+                    path: file.path.clone(),
+                    entry: file.entry.as_ref().map(EntryReport::new),
+                    decision: Decision::Unparsed,
+                    rule: None,
+                    duplicate_of: None,
+                };
+                if file.entry.is_some() {
+                    report.decide(output);
+                }
+                report
+            })
+            .collect();
+
+        // Whatever else was dropped or duplicated came from outside the roots
+        let seen = output
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect::<HashSet<_>>();
+        let mut left_out_paths = Vec::new();
+        let dropped = output.dropped.iter().map(|dropped| &dropped.path);
+        let duplicated = output.collisions.iter().map(|collision| &collision.path);
+        for path in dropped.chain(duplicated) {
+            if !seen.contains(path.as_str()) && !left_out_paths.contains(path) {
+                left_out_paths.push(path.clone());
+            }
+        }
+        let left_out = left_out_paths
+            .into_iter()
+            .map(|path| {
+                let mut report = FileReport {
+                    path,
+                    entry: None,
+                    decision: Decision::Kept,
+                    rule: None,
+                    duplicate_of: None,
+                };
+                report.decide(output);
+                report
+            })
+            .collect();
+
+        Self {
+            roots: roots
+                .iter()
+                .map(|root| root.path.display().to_string())
+                .collect(),
+            files,
+            left_out,
+            entries: output.diff.added.iter().map(EntryReport::new).collect(),
+            timings: TimingsReport::new(&output.timings),
+        }
+    }
+
+    /// Number of visited files and other entries with `decision`
+    pub fn count(&self, decision: Decision) -> usize {
+        self.files
+            .iter()
+            .chain(&self.left_out)
+            .filter(|file| file.decision == decision)
+            .count()
+    }
+
+    /// Listed entries whose target is missing
+    pub fn broken(&self) -> Vec<&EntryReport> {
+        self.entries.iter().filter(|entry| entry.broken).collect()
+    }
+
+    /// One line with the totals
+    pub fn summary(&self) -> String {
+        format!(
+            "Scanned {} files in {} folders: {} entries listed ({} broken); \
+             {} filtered, {} duplicates, {} unparsed",
+            self.files.len(),
+            self.roots.len(),
+            self.entries.len(),
+            self.broken().len(),
+            self.count(Decision::Filtered),
+            self.count(Decision::Duplicate),
+            self.count(Decision::Unparsed)
+        )
+    }
+
+    /// One line with the time each phase took
+    pub fn timing(&self) -> String {
+        let t = &self.timings;
+        format!(
+            "Took {} ms: walking {} ms, parsing {} ms on {} threads, settings {} ms, \
+             packages {} ms, programs {} ms, filtering {} ms",
+            t.total_ms,
+            t.walk_ms,
+            t.parse_ms,
+            t.workers,
+            t.settings_ms,
+            t.packages_ms,
+            t.programs_ms,
+            t.finish_ms
+        )
+    }
+
+    /// Render the broken shortcuts in `format`, with where to delete them in text
+    pub fn render_broken(&self, format: ReportFormat) -> String {
+        let broken = self.broken();
+        if format == ReportFormat::Json {
+            return serde_json::to_string_pretty(&broken).expect("reports are always serializable");
+        }
+        if broken.is_empty() {
+            return "No broken shortcuts found\n".to_string();
+        }
+
+        let mut out = format!(
+            "{} shortcuts point at files that no longer exist:\n",
+            broken.len()
+        );
+        for entry in &broken {
+            out.push_str(&format!(
+                "  {}\n    -> {}\n",
+                entry.source.as_deref().unwrap_or(&entry.name),
+                entry.target
+            ));
+        }
+        out.push_str("Delete these shortcuts to remove them from the list.\n");
+        out
+    }
+
+    /// Render the report in `format`
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).expect("reports are always serializable")
+            }
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("Folders\n");
+        for root in &self.roots {
+            out.push_str(&format!("  {}\n", root));
+        }
+
+        out.push_str(&format!("\nFiles ({})\n", self.files.len()));
+        for file in &self.files {
+            file.write_text(&mut out);
+        }
+
+        if !self.left_out.is_empty() {
+            out.push_str(&format!(
+                "\nLeft out from elsewhere ({})\n",
+                self.left_out.len()
+            ));
+            for file in &self.left_out {
+                file.write_text(&mut out);
+            }
+        }
+
+        out.push_str(&format!("\nEntries ({})\n", self.entries.len()));
+        for entry in &self.entries {
+            let broken = if entry.broken {
+                "  [target missing]"
+            } else {
+                ""
+            };
+            out.push_str(&format!("  {}{}\n", entry.describe(), broken));
+        }
+
+        out.push('\n');
+        out.push_str(&self.summary());
+        out.push('\n');
+        out.push_str(&self.timing());
+        out.push('\n');
+        out
+    }
+}
+
+impl FileReport {
+    /// Take the decision `output` recorded for this path
+    fn decide(&mut self, output: &ScanOutput) {
+        if let Some(dropped) = output.dropped.iter().find(|d| d.path == self.path) {
+            self.decision = Decision::Filtered;
+            self.rule = Some(dropped.rule.clone());
+            return;
+        }
+        self.rule = output
+            .allowed
+            .iter()
+            .find(|allowed| allowed.path == self.path)
+            .map(|allowed| allowed.rule.clone());
+        self.decision = match output.collisions.iter().find(|c| c.path == self.path) {
+            Some(collision) => {
+                self.duplicate_of = Some(DuplicateOf::new(collision));
+                Decision::Duplicate
+            }
+            None => Decision::Kept,
+        };
+    }
+
+    fn write_text(&self, out: &mut String) {
+        let decision = match self.decision {
+            Decision::Kept => "kept",
+            Decision::Filtered => "filtered",
+            Decision::Duplicate => "duplicate",
+            Decision::Unparsed => "unparsed",
+        };
+        out.push_str(&format!("  {:<9}  {}\n", decision, self.path));
+        if let Some(entry) = &self.entry {
+            out.push_str(&format!("             {}\n", entry.describe()));
+        }
+        if let Some(rule) = &self.rule {
+            out.push_str(&format!("             rule: {}\n", rule));
+        }
+        if let Some(kept) = &self.duplicate_of {
+            out.push_str(&format!(
+                "             {} as {} -> {}\n",
+                kept.reason, kept.name, kept.target
+            ));
+        }
+    }
+}
+
+impl DuplicateOf {
+    fn new(collision: &Collision) -> Self {
+        Self {
+            name: collision.kept_name.clone(),
+            target: collision.kept_target.clone(),
+            reason: match collision.reason {
+                DuplicateReason::SameTarget => "same-target",
+                DuplicateReason::SameName => "same-name",
+            },
+        }
+    }
+}
+
+impl TimingsReport {
+    fn new(timings: &ScanTimings) -> Self {
+        Self {
+            total_ms: timings.total().as_millis(),
+            walk_ms: timings.walk.as_millis(),
+            parse_ms: timings.parse.as_millis(),
+            workers: timings.workers,
+            settings_ms: timings.settings.as_millis(),
+            packages_ms: timings.packages.as_millis(),
+            programs_ms: timings.programs.as_millis(),
+            finish_ms: timings.finish.as_millis(),
+        }
+    }
+}
+
+impl EntryReport {
+    fn new(app: &AppEntry) -> Self {
+        Self {
+            name: app.name.clone(),
+            target: app.parse_name.clone(),
+            arguments: app.arguments.clone(),
+            kind: match app.entry_type {
+                AppEntryType::Application => "application",
+                AppEntryType::Settings => "settings",
+            },
+            source: app.shortcut_path.clone(),
+            folder: app.folder.clone(),
+            description: app.description.clone(),
+            keywords: app.keywords.clone(),
+            working_dir: app.working_dir.clone(),
+            hotkey: app.hotkey_label(),
+            uninstall: app.uninstall.clone(),
+            broken: app.broken,
+        }
+    }
+
+    /// `Name -> target arguments`
+    fn describe(&self) -> String {
+        match &self.arguments {
+            Some(arguments) => format!("{} -> {} {}", self.name, self.target, arguments),
+            None => format!("{} -> {}", self.name, self.target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_model::ScanDiff;
+    use crate::scanner::{AllowedFile, DroppedFile, ScannedFile};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn file(path: &str, name: &str, target: &str) -> ScannedFile {
+        ScannedFile {
+            path: path.to_string(),
+            size: 1,
+            mtime: 1,
+            entry: Some(AppEntry::new(name.to_string(), target.to_string(), 0, 0)),
+        }
+    }
+
+    fn report() -> ScanReport {
+        let code = AppEntry::new("Code".to_string(), "C:\\code.exe".to_string(), 0, 0);
+        let mut old_tool = AppEntry::new("Old Tool".to_string(), "C:\\old.exe".to_string(), 0, 0);
+        old_tool.shortcut_path = Some("Old Tool.lnk".to_string());
+        old_tool.broken = true;
+        let output = ScanOutput {
+            diff: ScanDiff {
+                added: vec![code, old_tool],
+                ..ScanDiff::default()
+            },
+            files: vec![
+                file("Code.lnk", "Code", "C:\\code.exe"),
+                file("Code (2).lnk", "VS Code", "C:\\code.exe"),
+                file("Code Setup.lnk", "Code Setup", "C:\\setup.exe"),
+                ScannedFile {
+                    path: "Broken.lnk".to_string(),
+                    size: 1,
+                    mtime: 1,
+                    entry: None,
+                },
+                file("Old Tool.lnk", "Old Tool", "C:\\old.exe"),
+            ],
+            reprocessed: 5,
+            dropped: vec![
+                DroppedFile {
+                    path: "Code Setup.lnk".to_string(),
+                    name: "Code Setup".to_string(),
+                    rule: "installer".to_string(),
+                },
+                DroppedFile {
+                    path: "HKLM\\Uninstall\\Code Helper".to_string(),
+                    name: "Code Helper".to_string(),
+                    rule: "helpers".to_string(),
+                },
+            ],
+            allowed: vec![AllowedFile {
+                path: "Old Tool.lnk".to_string(),
+                rule: "keep-old-tool".to_string(),
+            }],
+            collisions: vec![
+                Collision {
+                    path: "Code (2).lnk".to_string(),
+                    kept_name: "Code".to_string(),
+                    kept_target: "C:\\code.exe".to_string(),
+                    reason: DuplicateReason::SameTarget,
+                },
+                Collision {
+                    path: "ms-settings:display".to_string(),
+                    kept_name: "Display".to_string(),
+                    kept_target: "C:\\display.exe".to_string(),
+                    reason: DuplicateReason::SameName,
+                },
+            ],
+            timings: ScanTimings {
+                walk: Duration::from_millis(12),
+                parse: Duration::from_millis(340),
+                workers: 4,
+                settings: Duration::from_millis(30),
+                packages: Duration::from_millis(20),
+                programs: Duration::from_millis(8),
+                finish: Duration::from_millis(5),
+            },
+        };
+        ScanReport::new(&[ScanRoot::new(PathBuf::from("C:\\Start Menu"))], &output)
+    }
+
+    #[test]
+    fn test_decisions() {
+        let report = report();
+        let decisions = report
+            .files
+            .iter()
+            .map(|file| file.decision)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decisions,
+            vec![
+                Decision::Kept,
+                Decision::Duplicate,
+                Decision::Filtered,
+                Decision::Unparsed,
+                Decision::Kept
+            ]
+        );
+        assert_eq!(report.files[2].rule.as_deref(), Some("installer"));
+        assert_eq!(report.files[4].rule.as_deref(), Some("keep-old-tool"));
+        assert_eq!(
+            report.files[1].duplicate_of.as_ref().unwrap().reason,
+            "same-target"
+        );
+        assert_eq!(
+            report.summary(),
+            "Scanned 5 files in 1 folders: 2 entries listed (1 broken); \
+             2 filtered, 2 duplicates, 1 unparsed"
+        );
+    }
+
+    #[test]
+    fn test_left_out_from_elsewhere() {
+        let report = report();
+        let left_out = report
+            .left_out
+            .iter()
+            .map(|file| (file.path.as_str(), file.decision, file.rule.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            left_out,
+            vec![
+                (
+                    "HKLM\\Uninstall\\Code Helper",
+                    Decision::Filtered,
+                    Some("helpers")
+                ),
+                ("ms-settings:display", Decision::Duplicate, None),
+            ]
+        );
+        assert_eq!(
+            report.left_out[1].duplicate_of.as_ref().unwrap().name,
+            "Display"
+        );
+
+        let text = report.render(ReportFormat::Text);
+        assert!(text.contains(
+            "\nLeft out from elsewhere (2)\n  filtered   HKLM\\Uninstall\\Code Helper\n"
+        ));
+    }
+
+    #[test]
+    fn test_render() {
+        let report = report();
+
+        let text = report.render(ReportFormat::Text);
+        assert!(text.contains("  duplicate  Code (2).lnk\n"));
+        assert!(text.contains("same-target as Code -> C:\\code.exe"));
+        assert!(text.contains("rule: installer"));
+        assert!(text.ends_with(
+            "Took 415 ms: walking 12 ms, parsing 340 ms on 4 threads, settings 30 ms, \
+             packages 20 ms, programs 8 ms, filtering 5 ms\n"
+        ));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(ReportFormat::Json)).unwrap();
+        assert_eq!(json["files"][2]["decision"], "filtered");
+        assert_eq!(json["files"][3]["entry"], serde_json::Value::Null);
+        assert_eq!(json["entries"][0]["kind"], "application");
+        assert_eq!(json["entries"][1]["broken"], true);
+        assert_eq!(json["timings"]["parse_ms"], 340);
+    }
+
+    #[test]
+    fn test_render_broken() {
+        let report = report();
+        assert_eq!(
+            report.render_broken(ReportFormat::Text),
+            "1 shortcuts point at files that no longer exist:\n  \
+             Old Tool.lnk\n    -> C:\\old.exe\n\
+             Delete these shortcuts to remove them from the list.\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render_broken(ReportFormat::Json)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["source"], "Old Tool.lnk");
+    }
+}
@@ -1,167 +1,771 @@
-use crate::app_model::{AppEntry, AppManager};
-use crate::db;
+use crate::app_model::{AppEntry, AppEntryType, AppManager, DuplicateReason, ScanDiff};
+use crate::appx::{self, AppxManifest};
+use crate::config::{ScanConfig, ScanRoot};
+use crate::debug_log::write_debug_log;
+use crate::env_vars;
+use crate::filter_rules::FilterRules;
+use crate::known_folders::{self, KnownFolders, ProcessEnvironment};
+use crate::parallel;
+use crate::registry::Win32Registry;
 use crate::settings;
+use crate::uninstall::{self, InstalledProgram};
 use crate::utils;
+use crate::version_info::{self, VersionInfo};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use windows::{core::*, Win32::System::Com::*};
 
-/// Scan all applications from Start Menu shortcuts
-pub unsafe fn scan_apps(app_manager: &mut AppManager) {
-    app_manager.clear();
+/// Version of the data the scanner produces. Bump it whenever scanning changes
+/// in a way that makes previously cached entries stale.
+pub const SCANNER_VERSION: u32 = 6;
 
-    let usage_map = db::load_usage_map();
+/// A Start Menu file as seen by a scan, with the entry it produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedFile {
+    pub path: String,
+    pub size: i64,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: i64,
+    /// `None` when the file produced no entry (unreadable, not a shortcut, ...).
+    /// Filter rules are applied afterwards, so this is kept even for filtered entries.
+    pub entry: Option<AppEntry>,
+}
 
-    // Get username for Start Menu paths
-    let username = std::env::var("USERNAME").unwrap_or_else(|_| "Default".to_string());
+/// A file whose entry a filter rule kept out of the list
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedFile {
+    /// The file; for entries not read from a file, the packaged app's target,
+    /// the installed program's registry key or the Settings page's URI
+    pub path: String,
+    pub name: String,
+    /// Id of the deny rule that matched
+    pub rule: String,
+}
 
-    // Scan Start Menu folders
-    let start_menu_paths = [
-        format!(
-            "C:\\Users\\{}\\AppData\\Roaming\\Microsoft\\Windows\\Start Menu\\Programs",
-            username
-        ),
-        "C:\\ProgramData\\Microsoft\\Windows\\Start Menu\\Programs".to_string(),
-    ];
-
-    for start_menu_path in start_menu_paths {
-        if let Ok(entries) = std::fs::read_dir(&start_menu_path) {
-            for entry in entries.flatten() {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_file() {
-                        if let Some(extension) = entry.path().extension() {
-                            if extension == "lnk" {
-                                if let Some(app) = process_shortcut(&entry.path(), &usage_map) {
-                                    app_manager.add_app(app);
-                                }
-                            } else if extension == "url" {
-                                if let Some(app) = process_url_shortcut(&entry.path(), &usage_map) {
-                                    app_manager.add_app(app);
-                                }
-                            }
-                        }
-                    } else if file_type.is_dir() {
-                        // Recursively scan subdirectories
-                        scan_directory_recursively(&entry.path(), app_manager, &usage_map);
-                    }
-                }
-            }
+/// A file whose entry an allow rule kept in the list
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllowedFile {
+    /// Like `DroppedFile::path`
+    pub path: String,
+    /// Id of the allow rule that matched
+    pub rule: String,
+}
+
+/// A file whose entry duplicated one already listed, so it was left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    /// Like `DroppedFile::path`
+    pub path: String,
+    /// Name and target of the entry that was kept instead
+    pub kept_name: String,
+    pub kept_target: String,
+    pub reason: DuplicateReason,
+}
+
+/// Result of `scan_apps`
+pub struct ScanOutput {
+    /// Changes relative to the entries the scan was given
+    pub diff: ScanDiff,
+    /// Every file seen, to be stored for the next scan
+    pub files: Vec<ScannedFile>,
+    /// How many files had to be read again because they are new or changed
+    pub reprocessed: usize,
+    /// Files whose entries the filter rules dropped
+    pub dropped: Vec<DroppedFile>,
+    /// Files whose entries an allow rule kept, whether or not a deny rule matched
+    pub allowed: Vec<AllowedFile>,
+    /// Files whose entries were dropped as duplicates of another entry
+    pub collisions: Vec<Collision>,
+    pub timings: ScanTimings,
+}
+
+/// How long each phase of a scan took
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ScanTimings {
+    /// Listing the files under the roots
+    pub walk: Duration,
+    /// Reading the new and changed files
+    pub parse: Duration,
+    /// Threads `parse` was spread over
+    pub workers: usize,
+    /// Looking up the Settings pages
+    pub settings: Duration,
+    /// Reading the manifests of installed packages
+    pub packages: Duration,
+    /// Reading the programs listed for uninstalling
+    pub programs: Duration,
+    /// Filtering, deduplicating and comparing with the current list
+    pub finish: Duration,
+}
+
+impl ScanTimings {
+    pub fn total(&self) -> Duration {
+        self.walk + self.parse + self.settings + self.packages + self.programs + self.finish
+    }
+}
+
+/// COM initialized on the current thread until dropped, for workers that parse shortcuts
+struct ComApartment {
+    initialized: bool,
+}
+
+impl ComApartment {
+    fn enter() -> Self {
+        let initialized = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok() };
+        Self { initialized }
+    }
+}
+
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { CoUninitialize() };
         }
     }
+}
+
+/// Size and modification time of a file found on disk
+struct FileStamp {
+    path: PathBuf,
+    size: i64,
+    mtime: i64,
+    /// Folder of the file relative to its root (see `AppEntry::folder`)
+    folder: Option<String>,
+}
+
+/// Folders that are scanned: the Start Menu plus the roots listed in `config`
+pub fn scan_roots(config: &ScanConfig, folders: &KnownFolders) -> Vec<ScanRoot> {
+    // Scan Start Menu folders
+    let mut roots = folders
+        .start_menu_dirs()
+        .into_iter()
+        .map(ScanRoot::new)
+        .collect::<Vec<_>>();
+    roots.extend(config.roots.iter().cloned());
+    roots
+}
+
+/// Scan all applications under `roots` that pass `rules`, taking usage counts from `usage_map`.
+///
+/// Only files that are new or changed since `previous_files` are read again,
+/// on a pool of worker threads; the rest reuse the entry recorded last time.
+/// The result is the difference from `current`, the entries shown before the scan.
+pub unsafe fn scan_apps(
+    roots: &[ScanRoot],
+    rules: &FilterRules,
+    current: &[AppEntry],
+    previous_files: &[ScannedFile],
+    usage_map: &HashMap<String, i32>,
+) -> ScanOutput {
+    let mut timings = ScanTimings::default();
+
+    let started = Instant::now();
+    let mut listing = Vec::new();
+    for root in roots {
+        list_files(root, &root.path, &mut listing);
+    }
+    timings.walk = started.elapsed();
+
+    let started = Instant::now();
+    timings.workers = parallel::default_workers();
+    let (files, reprocessed) = rescan_files(
+        listing,
+        previous_files,
+        timings.workers,
+        ComApartment::enter,
+        |path| process_file(path, usage_map),
+        file_icon_index,
+    );
+    timings.parse = started.elapsed();
 
     // Add Windows Settings items
+    let started = Instant::now();
     let settings_items = settings::get_settings_items();
-    for settings_item in settings_items {
-        let display_name = settings::get_localized_name(settings_item.canonical_name)
-            .unwrap_or_else(|| settings_item.display_name_en.to_string());
+    let settings_entries: Vec<_> = settings_items
+        .into_iter()
+        .map(|settings_item| {
+            let display_name = settings::get_localized_name(settings_item.canonical_name)
+                .unwrap_or_else(|| settings_item.display_name_en.to_string());
+            AppEntry::new_settings(display_name, settings_item.ms_settings_uri.to_string(), -1)
+        })
+        .collect();
+    timings.settings = started.elapsed();
+
+    // Add packaged (Store) apps, which have no shortcut files
+    let started = Instant::now();
+    let mut entries = scan_packages(usage_map);
+    entries.extend(settings_entries);
+    timings.packages = started.elapsed();
+
+    let started = Instant::now();
+    let programs = read_programs();
+    timings.programs = started.elapsed();
+
+    finish_scan(
+        current,
+        files,
+        Extras { entries, programs },
+        usage_map,
+        reprocessed,
+        rules,
+        timings,
+    )
+}
+
+/// Rescan only the files under `subtrees` (e.g. folders a watcher saw change) of
+/// `roots`, keeping everything else as recorded in `previous_files`
+pub fn rescan_subtrees(
+    roots: &[ScanRoot],
+    rules: &FilterRules,
+    subtrees: &[PathBuf],
+    current: &[AppEntry],
+    previous_files: &[ScannedFile],
+    usage_map: &HashMap<String, i32>,
+) -> ScanOutput {
+    let in_subtree = |path: &Path| subtrees.iter().any(|subtree| path.starts_with(subtree));
+    let mut timings = ScanTimings::default();
 
-        let settings_entry =
-            AppEntry::new_settings(display_name, settings_item.ms_settings_uri.to_string(), -1);
-        app_manager.add_app(settings_entry);
+    let started = Instant::now();
+    let mut listing = Vec::new();
+    for subtree in subtrees {
+        // The innermost root decides which files count
+        let root = roots
+            .iter()
+            .filter(|root| subtree.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count());
+        if let Some(root) = root {
+            list_files(root, subtree, &mut listing);
+        }
     }
+    timings.walk = started.elapsed();
+
+    let started = Instant::now();
+    timings.workers = parallel::default_workers();
+    let mut files = previous_files
+        .iter()
+        .filter(|file| !in_subtree(Path::new(&file.path)))
+        .cloned()
+        .collect::<Vec<_>>();
+    refresh_icon_indices(
+        &mut files,
+        timings.workers,
+        ComApartment::enter,
+        file_icon_index,
+    );
+    let (rescanned, reprocessed) = rescan_files(
+        listing,
+        previous_files,
+        timings.workers,
+        ComApartment::enter,
+        |path| process_file(path, usage_map),
+        file_icon_index,
+    );
+    files.extend(rescanned);
+    timings.parse = started.elapsed();
+
+    // Settings pages and packaged apps don't live under the roots; keep the ones already shown
+    let entries = current
+        .iter()
+        .filter(|app| {
+            app.entry_type == AppEntryType::Settings || appx::is_packaged(&app.parse_name)
+        })
+        .cloned()
+        .collect();
 
-    app_manager.sort_by_usage();
-    app_manager.filter("");
+    // Installed programs are quick to read again, and new files may belong to one
+    let started = Instant::now();
+    let programs = unsafe { read_programs() };
+    timings.programs = started.elapsed();
+
+    finish_scan(
+        current,
+        files,
+        Extras { entries, programs },
+        usage_map,
+        reprocessed,
+        rules,
+        timings,
+    )
 }
 
-/// Recursively scan a directory for shortcuts
-fn scan_directory_recursively(
-    dir_path: &std::path::Path,
-    app_manager: &mut AppManager,
-    usage_map: &std::collections::HashMap<String, i32>,
-) {
-    if let Ok(entries) = std::fs::read_dir(dir_path) {
+/// Entries that don't come from files under the roots
+#[derive(Default)]
+struct Extras {
+    /// Listed as they are (Settings pages, packaged apps)
+    entries: Vec<AppEntry>,
+    programs: Vec<ProgramListing>,
+}
+
+/// An installed program, and the entry that starts it if it has an executable
+struct ProgramListing {
+    program: InstalledProgram,
+    entry: Option<AppEntry>,
+}
+
+/// Filter and deduplicate the entries of all scanned files, give each the
+/// uninstall command of the program it belongs to, add `extras` and compare
+/// the result with `current`.
+///
+/// A program gets an entry of its own only when no scanned file belongs to it.
+fn finish_scan(
+    current: &[AppEntry],
+    files: Vec<ScannedFile>,
+    extras: Extras,
+    usage_map: &HashMap<String, i32>,
+    reprocessed: usize,
+    rules: &FilterRules,
+    mut timings: ScanTimings,
+) -> ScanOutput {
+    let started = Instant::now();
+    let mut listing = Listing::default();
+    let mut owned = vec![false; extras.programs.len()];
+    for file in &files {
+        let Some(entry) = &file.entry else {
+            continue;
+        };
+        if !listing.admits(rules, &file.path, entry) {
+            continue;
+        }
+
+        let mut app = entry.clone();
+        app.usage_count = *usage_map.get(&app.parse_name).unwrap_or(&0);
+        // Checked on every scan: uninstalling an app leaves its shortcut unchanged
+        app.broken = target_missing(&app.parse_name, known_folders::current(), |path| {
+            path.exists()
+        });
+        if let Some(owner) = owning_program(&extras.programs, &app.parse_name) {
+            let program = &extras.programs[owner].program;
+            app.uninstall = program.uninstall_command.clone();
+            app.uninstall_name = Some(program.name.clone());
+            owned[owner] = true;
+        }
+        listing.add(&file.path, app);
+    }
+    for (program, owned) in extras.programs.into_iter().zip(owned) {
+        let Some(mut app) = program.entry.filter(|_| !owned) else {
+            continue;
+        };
+        if !listing.admits(rules, &program.program.key, &app) {
+            continue;
+        }
+        app.usage_count = *usage_map.get(&app.parse_name).unwrap_or(&0);
+        app.broken = target_missing(&app.parse_name, known_folders::current(), |path| {
+            path.exists()
+        });
+        listing.add(&program.program.key, app);
+    }
+    for app in extras.entries {
+        // Settings pages are always listed; packaged apps answer to the rules
+        let path = app.parse_name.clone();
+        if appx::is_packaged(&path) && !listing.admits(rules, &path, &app) {
+            continue;
+        }
+        listing.add(&path, app);
+    }
+
+    write_debug_log(&format!(
+        "Scanned {} files, {} of them new or changed",
+        files.len(),
+        reprocessed
+    ));
+
+    let diff = ScanDiff::between(current, listing.apps.apps());
+    timings.finish = started.elapsed();
+
+    ScanOutput {
+        diff,
+        files,
+        reprocessed,
+        dropped: listing.dropped,
+        allowed: listing.allowed,
+        collisions: listing.collisions,
+        timings,
+    }
+}
+
+/// The entries a scan lists, and what it left out or let in and why
+#[derive(Default)]
+struct Listing {
+    apps: AppManager,
+    dropped: Vec<DroppedFile>,
+    allowed: Vec<AllowedFile>,
+    collisions: Vec<Collision>,
+}
+
+impl Listing {
+    /// Whether `rules` let `app`, read from `path`, into the list
+    fn admits(&mut self, rules: &FilterRules, path: &str, app: &AppEntry) -> bool {
+        let verdict = rules.evaluate(app);
+        if !verdict.keep {
+            write_debug_log(&format!(
+                "Filtered out {} -> {} (rule {:?})",
+                app.name, app.parse_name, verdict.rule
+            ));
+            self.dropped.push(DroppedFile {
+                path: path.to_string(),
+                name: app.name.clone(),
+                rule: verdict.rule.unwrap_or_default(),
+            });
+        } else if let Some(rule) = verdict.rule {
+            self.allowed.push(AllowedFile {
+                path: path.to_string(),
+                rule,
+            });
+        }
+        verdict.keep
+    }
+
+    /// Add `app`, read from `path`, unless it duplicates a listed entry
+    fn add(&mut self, path: &str, app: AppEntry) {
+        if let Some(duplicate) = self.apps.add_app(app) {
+            let kept = &self.apps.apps()[duplicate.index];
+            self.collisions.push(Collision {
+                path: path.to_string(),
+                kept_name: kept.name.clone(),
+                kept_target: kept.parse_name.clone(),
+                reason: duplicate.reason,
+            });
+        }
+    }
+}
+
+/// Recursively collect the files under `dir` (inside `root`) that `root` accepts
+fn list_files(root: &ScanRoot, dir: &Path, files: &mut Vec<FileStamp>) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
         for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(relative) = path.strip_prefix(&root.path) else {
+                continue;
+            };
             if let Ok(file_type) = entry.file_type() {
                 if file_type.is_file() {
-                    if let Some(extension) = entry.path().extension() {
-                        if extension == "lnk" {
-                            if let Some(app) = process_shortcut(&entry.path(), usage_map) {
-                                app_manager.add_app(app);
-                            }
-                        } else if extension == "url" {
-                            if let Some(app) = process_url_shortcut(&entry.path(), usage_map) {
-                                app_manager.add_app(app);
-                            }
-                        }
+                    if let (true, Ok(metadata)) = (root.accepts_file(relative), entry.metadata()) {
+                        files.push(FileStamp {
+                            size: metadata.len() as i64,
+                            mtime: file_mtime(&path).unwrap_or(0),
+                            folder: relative_folder(relative),
+                            path,
+                        });
                     }
-                } else if file_type.is_dir() {
+                } else if file_type.is_dir() && root.accepts_dir(relative) {
                     // Continue recursing
-                    scan_directory_recursively(&entry.path(), app_manager, usage_map);
+                    list_files(root, &path, files);
                 }
             }
         }
     }
 }
 
+/// Pair every file in `listing` with its entry, reusing the previous result
+/// when size and modification time are unchanged and calling `process` on up to
+/// `workers` threads otherwise. A reused entry gets its icon index from
+/// `icon_index` again. Files stay in listing order; the second value is how many
+/// were processed.
+fn rescan_files<S>(
+    listing: Vec<FileStamp>,
+    previous_files: &[ScannedFile],
+    workers: usize,
+    setup_worker: impl Fn() -> S + Sync,
+    process: impl Fn(&Path) -> Option<AppEntry> + Sync,
+    icon_index: impl Fn(&Path, &AppEntry) -> i32 + Sync,
+) -> (Vec<ScannedFile>, usize) {
+    let previous = previous_files
+        .iter()
+        .map(|file| (file.path.as_str(), file))
+        .collect::<HashMap<_, _>>();
+
+    // Reuse what's unchanged and leave a gap for each file to read again
+    let mut files = Vec::with_capacity(listing.len());
+    let mut reused = Vec::new();
+    let mut reused_at = Vec::new();
+    let mut stale = Vec::new();
+    for stamp in listing {
+        let path = stamp.path.to_string_lossy().to_string();
+        match previous.get(path.as_str()) {
+            Some(file) if file.size == stamp.size && file.mtime == stamp.mtime => {
+                reused_at.push(files.len());
+                reused.push((*file).clone());
+            }
+            _ => stale.push((files.len(), path, stamp)),
+        }
+        files.push(None);
+    }
+
+    refresh_icon_indices(&mut reused, workers, &setup_worker, icon_index);
+    for (index, file) in reused_at.into_iter().zip(reused) {
+        files[index] = Some(file);
+    }
+
+    let entries = parallel::map_ordered(&stale, workers, setup_worker, |(_, _, stamp)| {
+        process(&stamp.path).map(|mut app| {
+            app.folder = stamp.folder.clone();
+            app
+        })
+    });
+    let reprocessed = stale.len();
+    for ((index, path, stamp), entry) in stale.into_iter().zip(entries) {
+        files[index] = Some(ScannedFile {
+            path,
+            size: stamp.size,
+            mtime: stamp.mtime,
+            entry,
+        });
+    }
+
+    (files.into_iter().flatten().collect(), reprocessed)
+}
+
+/// Look up the icon index of every entry in `files` again, on up to `workers`
+/// threads. Indices into the system image list are only valid in the process
+/// that asked for them, so entries kept from an earlier run need new ones.
+fn refresh_icon_indices<S>(
+    files: &mut [ScannedFile],
+    workers: usize,
+    setup_worker: impl Fn() -> S + Sync,
+    icon_index: impl Fn(&Path, &AppEntry) -> i32 + Sync,
+) {
+    let indices = parallel::map_ordered(files, workers, setup_worker, |file| {
+        let app = file.entry.as_ref()?;
+        Some(icon_index(Path::new(&file.path), app))
+    });
+    for (file, index) in files.iter_mut().zip(indices) {
+        if let (Some(app), Some(index)) = (file.entry.as_mut(), index) {
+            app.icon_index = index;
+        }
+    }
+}
+
+/// Index in this process's system image list of the icon for `app`, read from
+/// the file at `path`: the icon file it names, else its target's icon
+fn file_icon_index(path: &Path, app: &AppEntry) -> i32 {
+    if let Some(index) = located_icon_index(app) {
+        return index;
+    }
+    let icon_path = app.icon_path.as_deref().filter(|icon| !icon.is_empty());
+    let is_url = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("url"));
+    unsafe {
+        if is_url {
+            return match icon_path {
+                Some(icon) => utils::get_file_info_path(icon)
+                    .map(|shfi| shfi.iIcon)
+                    .unwrap_or(app.icon_resource),
+                // Try to get icon from a known application for the protocol
+                None => get_protocol_icon_index(&app.parse_name).unwrap_or(0),
+            };
+        }
+        icon_path
+            .filter(|icon| Path::new(icon).exists())
+            .and_then(|icon| utils::get_file_info_path(icon))
+            .or_else(|| utils::get_file_info_path(&app.parse_name))
+            .map(|shfi| shfi.iIcon)
+            .unwrap_or(0)
+    }
+}
+
+/// Index of the icon `app`'s icon location picks (see `AppEntry::icon_location`),
+/// extracted into the system image list here rather than on the UI thread
+fn located_icon_index(app: &AppEntry) -> Option<i32> {
+    let (path, index) = app.icon_location()?;
+    unsafe { utils::get_icon_location_index(path, index) }
+}
+
+/// The folders between a root and the file at `relative` to it, joined with `\\`
+fn relative_folder(relative: &Path) -> Option<String> {
+    let folders = relative
+        .parent()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    (!folders.is_empty()).then(|| folders.join("\\"))
+}
+
+/// Read a .lnk or .url file; any other file is an entry that opens itself
+fn process_file(path: &Path, usage_map: &HashMap<String, i32>) -> Option<AppEntry> {
+    match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "lnk" => process_shortcut(path, usage_map),
+        "url" => process_url_shortcut(path, usage_map),
+        _ => process_plain_file(path, usage_map),
+    }
+}
+
+/// Make an entry for a file found under a configured root (e.g. a portable app's .exe)
+fn process_plain_file(path: &Path, usage_map: &HashMap<String, i32>) -> Option<AppEntry> {
+    let name = path.file_stem()?.to_string_lossy().to_string();
+    let target_path = path.to_string_lossy().to_string();
+
+    let usage_count = *usage_map.get(&target_path).unwrap_or(&0);
+
+    write_debug_log(&format!("Added file: {} -> {}", name, target_path));
+
+    let mut app = AppEntry::new(name, target_path, 0, usage_count);
+    app.icon_index = file_icon_index(path, &app);
+    if let Some(info) = target_version_info(&app.parse_name) {
+        add_version_info(&mut app, &info, true);
+    }
+    app.shortcut_path = Some(path.to_string_lossy().to_string());
+    app.source_mtime = file_mtime(path);
+    Some(app)
+}
+
+/// The version resource of `target` if it is a local executable that has one
+fn target_version_info(target: &str) -> Option<VersionInfo> {
+    // Reading a file on a share can stall the scan for as long as the server takes
+    if !target.to_lowercase().ends_with(".exe") || target.starts_with("\\\\") {
+        return None;
+    }
+    version_info::read(Path::new(target))
+        .map_err(|e| write_debug_log(&format!("No version info for {}: {}", target, e)))
+        .ok()
+        .flatten()
+}
+
+/// Make what the version resource says searchable. `rename` replaces a name
+/// that is only the file's stem with the program's own name, keeping the stem
+/// as a keyword.
+fn add_version_info(app: &mut AppEntry, info: &VersionInfo, rename: bool) {
+    let stem = app.name.clone();
+    if let Some(name) = info.display_name().filter(|_| rename) {
+        app.name = name.to_string();
+    }
+
+    let name = app.name.to_lowercase();
+    let mut keywords: Vec<&str> = Vec::new();
+    let renamed = (app.name != stem).then_some(stem.as_str());
+    for keyword in renamed.into_iter().chain(info.keywords()) {
+        let repeated = name.contains(&keyword.to_lowercase())
+            || keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword));
+        if !repeated {
+            keywords.push(keyword);
+        }
+    }
+    app.keywords = (!keywords.is_empty()).then(|| keywords.join(", "));
+}
+
 /// Process a shortcut file to extract application information
 fn process_shortcut(
     shortcut_path: &std::path::Path,
-    usage_map: &std::collections::HashMap<String, i32>,
+    usage_map: &HashMap<String, i32>,
 ) -> Option<AppEntry> {
-    // Read the shortcut target, arguments, and icon info
-    if let Some((target_path, arguments, icon_path, _icon_index)) = get_shortcut_info(shortcut_path)
-    {
+    // Read the shortcut target, arguments, icon info and launch settings
+    if let Some(info) = get_shortcut_info(shortcut_path) {
+        let ShortcutInfo {
+            target: target_path,
+            arguments,
+            icon_path,
+            icon_resource,
+            description,
+            working_dir,
+            show_cmd,
+            hotkey,
+        } = info;
+
         // Get the display name from the filename (without .lnk extension)
         let name = shortcut_path.file_stem()?.to_string_lossy().to_string();
 
-        // Skip problematic apps
-        if should_filter_app(&name, &target_path) {
-            write_debug_log(&format!(
-                "Filtering out problematic app: {} -> {}",
-                name, target_path
-            ));
-            return None;
-        }
-
-        // Get icon index - prefer shortcut's icon, fall back to target's icon
-        let icon_index = unsafe {
-            if let Some(ref icon_loc) = icon_path {
-                if !icon_loc.is_empty() && std::path::Path::new(icon_loc).exists() {
-                    utils::get_file_info_path(icon_loc)
-                        .map(|shfi| shfi.iIcon)
-                        .unwrap_or_else(|| {
-                            utils::get_file_info_path(&target_path)
-                                .map(|shfi| shfi.iIcon)
-                                .unwrap_or(0)
-                        })
-                } else {
-                    utils::get_file_info_path(&target_path)
-                        .map(|shfi| shfi.iIcon)
-                        .unwrap_or(0)
-                }
-            } else {
-                utils::get_file_info_path(&target_path)
-                    .map(|shfi| shfi.iIcon)
-                    .unwrap_or(0)
-            }
-        };
-
         // Get usage count
         let usage_count = *usage_map.get(&target_path).unwrap_or(&0);
 
         write_debug_log(&format!(
-            "Added app: {} -> {} (args: {:?}, icon: {:?})",
-            name, target_path, arguments, icon_path
+            "Added app: {} -> {} (args: {:?}, icon: {:?}, dir: {:?})",
+            name, target_path, arguments, icon_path, working_dir
         ));
 
-        Some(AppEntry::new_with_args(
-            name,
-            target_path,
-            arguments,
-            icon_index,
-            usage_count,
-        ))
+        let mut app = AppEntry::new_with_args(name, target_path, arguments, 0, usage_count);
+        app.icon_path = icon_path;
+        app.icon_resource = icon_resource;
+        // Prefer the shortcut's icon, fall back to the target's icon
+        app.icon_index = file_icon_index(shortcut_path, &app);
+        app.description = description;
+        app.working_dir = working_dir;
+        app.show_cmd = show_cmd;
+        app.hotkey = hotkey;
+        if let Some(info) = target_version_info(&app.parse_name) {
+            add_version_info(&mut app, &info, false);
+        }
+        app.shortcut_path = Some(shortcut_path.to_string_lossy().to_string());
+        app.source_mtime = file_mtime(shortcut_path);
+        Some(app)
     } else {
         None
     }
 }
 
+/// Entries for the packaged apps installed for the current user, read from
+/// each package's AppxManifest.xml
+unsafe fn scan_packages(usage_map: &HashMap<String, i32>) -> Vec<AppEntry> {
+    let mut entries = Vec::new();
+    for package in appx::installed_packages(&Win32Registry) {
+        let manifest = std::fs::read_to_string(package.root.join("AppxManifest.xml"))
+            .map_err(|e| e.to_string())
+            .and_then(|text| AppxManifest::parse(&text));
+        let manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                write_debug_log(&format!("Skipped package {}: {}", package.full_name, e));
+                continue;
+            }
+        };
+
+        for mut app in manifest.entries(&package.full_name, |source| {
+            appx::load_indirect_string(source)
+        }) {
+            app.icon_index = appx::app_icon_index(&app.parse_name).unwrap_or(0);
+            app.usage_count = *usage_map.get(&app.parse_name).unwrap_or(&0);
+            write_debug_log(&format!(
+                "Added package app: {} -> {}",
+                app.name, app.parse_name
+            ));
+            entries.push(app);
+        }
+    }
+    entries
+}
+
+/// The programs listed for uninstalling, each with an entry if it can be started
+unsafe fn read_programs() -> Vec<ProgramListing> {
+    uninstall::installed_programs(&Win32Registry, &ProcessEnvironment)
+        .into_iter()
+        .map(|program| {
+            let entry = program.to_entry().map(|mut app| {
+                app.icon_index = located_icon_index(&app)
+                    .or_else(|| {
+                        let icon = app.icon_path.as_deref()?;
+                        utils::get_file_info_path(icon).map(|shfi| shfi.iIcon)
+                    })
+                    .unwrap_or(0);
+                app
+            });
+            ProgramListing { program, entry }
+        })
+        .collect()
+}
+
+/// The program `target` belongs to, if any: the one it is the executable of,
+/// or else the one with the innermost install folder holding it
+fn owning_program(programs: &[ProgramListing], target: &str) -> Option<usize> {
+    programs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, listing)| {
+            listing
+                .program
+                .claims(target, known_folders::current())
+                .map(|score| (score, i))
+        })
+        .max_by_key(|&(score, i)| (score, std::cmp::Reverse(i)))
+        .map(|(_, i)| i)
+}
+
 /// Process a URL shortcut file (.url) to extract application information
 /// These are internet shortcuts used by Steam games and other applications
 fn process_url_shortcut(
     shortcut_path: &std::path::Path,
-    usage_map: &std::collections::HashMap<String, i32>,
+    usage_map: &HashMap<String, i32>,
 ) -> Option<AppEntry> {
     // Parse the .url file
     let (url, icon_file, icon_index) = parse_url_file(shortcut_path)?;
@@ -169,30 +773,54 @@ fn process_url_shortcut(
     // Get the display name from the filename (without .url extension)
     let name = shortcut_path.file_stem()?.to_string_lossy().to_string();
 
-    // Skip problematic entries
-    if should_filter_url_shortcut(&name, &url) {
-        write_debug_log(&format!("Filtering out URL shortcut: {} -> {}", name, url));
-        return None;
-    }
-
-    // Get icon index - try from the icon file if specified, otherwise use default
-    let final_icon_index = if let Some(ref icon_path) = icon_file {
-        unsafe {
-            utils::get_file_info_path(icon_path)
-                .map(|shfi| shfi.iIcon)
-                .unwrap_or(icon_index.unwrap_or(0))
-        }
-    } else {
-        // Try to get icon from a known application for the protocol
-        get_protocol_icon_index(&url).unwrap_or(0)
-    };
-
     // Get usage count
     let usage_count = *usage_map.get(&url).unwrap_or(&0);
 
     write_debug_log(&format!("Added URL shortcut: {} -> {}", name, url));
 
-    Some(AppEntry::new(name, url, final_icon_index, usage_count))
+    let mut app = AppEntry::new(name, url, 0, usage_count);
+    app.icon_path = icon_file;
+    app.icon_resource = icon_index.unwrap_or(0);
+    app.icon_index = file_icon_index(shortcut_path, &app);
+    app.shortcut_path = Some(shortcut_path.to_string_lossy().to_string());
+    app.source_mtime = file_mtime(shortcut_path);
+    Some(app)
+}
+
+/// Whether `target` is a local file that doesn't exist, not even under another
+/// Program Files folder (a 32-bit process reading a 64-bit app's shortcut may
+/// see the wrong one).
+///
+/// Targets are already expanded when read. URLs, shell locations and network
+/// paths are never reported: they can't be checked, or not quickly.
+fn target_missing(target: &str, folders: &KnownFolders, exists: impl Fn(&Path) -> bool) -> bool {
+    let bytes = target.as_bytes();
+    let is_local_path = bytes.len() > 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    if !is_local_path || exists(Path::new(target)) {
+        return false;
+    }
+
+    let program_files = folders.program_files_dirs();
+    let relative = program_files.iter().find_map(|dir| {
+        let dir = dir.to_string_lossy();
+        let prefix = target.get(..dir.len())?;
+        let rest = target[dir.len()..].strip_prefix(['\\', '/'])?;
+        prefix.eq_ignore_ascii_case(&dir).then_some(rest)
+    });
+    match relative {
+        Some(relative) => !program_files.iter().any(|dir| exists(&dir.join(relative))),
+        None => true,
+    }
+}
+
+/// Modification time of a file in seconds since the Unix epoch
+fn file_mtime(path: &std::path::Path) -> Option<i64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_secs() as i64)
 }
 
 /// Parse a .url file and extract URL, IconFile, and IconIndex
@@ -210,7 +838,7 @@ fn parse_url_file(path: &std::path::Path) -> Option<(String, Option<String>, Opt
         if let Some(value) = line.strip_prefix("URL=") {
             url = Some(value.to_string());
         } else if let Some(value) = line.strip_prefix("IconFile=") {
-            icon_file = Some(value.to_string());
+            icon_file = Some(env_vars::expand_path(value, &ProcessEnvironment));
         } else if let Some(value) = line.strip_prefix("IconIndex=") {
             icon_index = value.parse().ok();
         }
@@ -219,40 +847,17 @@ fn parse_url_file(path: &std::path::Path) -> Option<(String, Option<String>, Opt
     url.map(|u| (u, icon_file, icon_index))
 }
 
-/// Check if a URL shortcut should be filtered out
-fn should_filter_url_shortcut(name: &str, url: &str) -> bool {
-    // Filter out empty URLs
-    if url.is_empty() {
-        return true;
-    }
-
-    // Filter out uninstall shortcuts
-    let name_lower = name.to_lowercase();
-    if name_lower.contains("uninstall") || name_lower.contains("setup") {
-        return true;
-    }
-
-    // Filter out http/https URLs (we only want protocol handlers like steam://)
-    if url.starts_with("http://") || url.starts_with("https://") {
-        return true;
-    }
-
-    false
-}
-
 /// Get icon index for known protocol handlers
 fn get_protocol_icon_index(url: &str) -> Option<i32> {
     // For Steam URLs, try to get the Steam icon
     if url.starts_with("steam://") {
         // Try to find Steam executable for its icon
-        let steam_paths = [
-            "C:\\Program Files (x86)\\Steam\\steam.exe",
-            "C:\\Program Files\\Steam\\steam.exe",
-        ];
-
-        for steam_path in steam_paths {
-            if std::path::Path::new(steam_path).exists() {
-                return unsafe { utils::get_file_info_path(steam_path).map(|shfi| shfi.iIcon) };
+        for program_files in known_folders::current().program_files_dirs() {
+            let steam_path = program_files.join("Steam").join("steam.exe");
+            if steam_path.exists() {
+                return unsafe {
+                    utils::get_file_info_path(&steam_path.to_string_lossy()).map(|shfi| shfi.iIcon)
+                };
             }
         }
     }
@@ -260,17 +865,35 @@ fn get_protocol_icon_index(url: &str) -> Option<i32> {
     None
 }
 
-/// Get the target path, arguments, and icon info from a .lnk shortcut file
-fn get_shortcut_info(
-    shortcut_path: &std::path::Path,
-) -> Option<(String, Option<String>, Option<String>, i32)> {
+/// What a .lnk file says about its target
+struct ShortcutInfo {
+    target: String,
+    arguments: Option<String>,
+    icon_path: Option<String>,
+    icon_resource: i32,
+    description: Option<String>,
+    working_dir: Option<String>,
+    show_cmd: Option<i32>,
+    hotkey: Option<u16>,
+}
+
+/// Get the target path, arguments, icon and launch settings from a .lnk shortcut file
+fn get_shortcut_info(shortcut_path: &std::path::Path) -> Option<ShortcutInfo> {
     use std::os::windows::ffi::OsStrExt;
     use windows::Win32::System::Com::IPersistFile;
     use windows::Win32::UI::Shell::*;
+    use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
 
     // ShellLink CLSID: {00021401-0000-0000-C000-000000000046}
     const CLSID_SHELL_LINK: GUID = GUID::from_u128(0x00021401_0000_0000_C000_000000000046);
 
+    /// A NUL-terminated buffer as a trimmed string, or `None` if it is empty
+    fn non_empty(buffer: &[u16]) -> Option<String> {
+        let text = String::from_utf16_lossy(buffer);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
     unsafe {
         // Create a shell link object
         let shell_link: IShellLinkW =
@@ -299,9 +922,10 @@ fn get_shortcut_info(
             )
             .ok()?;
 
-        // Convert target path to string
+        // Convert target path to string; the raw path may still hold %VARIABLES%
         let target_str = String::from_utf16_lossy(&target_path);
-        let target_str = target_str.trim_end_matches('\0');
+        let target_str =
+            env_vars::expand_path(target_str.trim_end_matches('\0'), &ProcessEnvironment);
 
         if target_str.is_empty() {
             return None;
@@ -309,75 +933,54 @@ fn get_shortcut_info(
 
         // Get the arguments
         let mut arguments = [0u16; 1024];
-        let args_result = shell_link.GetArguments(&mut arguments);
-
-        let arguments_str = if args_result.is_ok() {
-            let args = String::from_utf16_lossy(&arguments);
-            let args = args.trim_end_matches('\0').trim();
-            if args.is_empty() {
-                None
-            } else {
-                Some(args.to_string())
-            }
-        } else {
-            None
-        };
+        let arguments_str = shell_link
+            .GetArguments(&mut arguments)
+            .ok()
+            .and_then(|_| non_empty(&arguments))
+            .map(|args| env_vars::expand(&args, &ProcessEnvironment));
 
         // Get the icon location
         let mut icon_path = [0u16; 260];
         let mut icon_index: i32 = 0;
-        let icon_result = shell_link.GetIconLocation(&mut icon_path, &mut icon_index);
-
-        let icon_path_str = if icon_result.is_ok() {
-            let icon = String::from_utf16_lossy(&icon_path);
-            let icon = icon.trim_end_matches('\0').trim();
-            if icon.is_empty() {
-                None
-            } else {
-                Some(icon.to_string())
-            }
-        } else {
-            None
-        };
+        let icon_path_str = shell_link
+            .GetIconLocation(&mut icon_path, &mut icon_index)
+            .ok()
+            .and_then(|_| non_empty(&icon_path))
+            .map(|icon| env_vars::expand_path(&icon, &ProcessEnvironment));
 
-        Some((
-            target_str.to_string(),
-            arguments_str,
-            icon_path_str,
-            icon_index,
-        ))
-    }
-}
-
-/// Check if an app should be filtered out
-fn should_filter_app(name: &str, target_path: &str) -> bool {
-    // Filter out Microsoft Store apps that can't be launched directly
-    if target_path.contains("Microsoft.AutoGenerated.")
-        || target_path.contains("WindowsApps\\")
-        || name.to_lowercase().contains("uninstall")
-        || name.to_lowercase().contains("setup")
-    {
-        return true;
-    }
-
-    false
-}
-
-/// Write a message to the debug log file
-fn write_debug_log(message: &str) {
-    if let Ok(mut file) = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("oxistart_debug.log")
-    {
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let _ = std::io::Write::write_all(
-            &mut file,
-            format!("[{}] {}\n", timestamp, message).as_bytes(),
-        );
+        // The comment shown as the shortcut's tooltip
+        let mut description = [0u16; 1024];
+        let description_str = shell_link
+            .GetDescription(&mut description)
+            .ok()
+            .and_then(|_| non_empty(&description));
+
+        // The "Start in" directory, which may also hold %VARIABLES%
+        let mut working_dir = [0u16; 260];
+        let working_dir_str = shell_link
+            .GetWorkingDirectory(&mut working_dir)
+            .ok()
+            .and_then(|_| non_empty(&working_dir))
+            .map(|dir| env_vars::expand_path(&dir, &ProcessEnvironment));
+
+        // Only keep settings that differ from a plain launch
+        let show_cmd = shell_link
+            .GetShowCmd()
+            .ok()
+            .map(|cmd| cmd.0)
+            .filter(|&cmd| cmd != SW_SHOWNORMAL.0);
+        let hotkey = shell_link.GetHotkey().ok().filter(|&key| key != 0);
+
+        Some(ShortcutInfo {
+            target: target_str,
+            arguments: arguments_str,
+            icon_path: icon_path_str,
+            icon_resource: icon_index,
+            description: description_str,
+            working_dir: working_dir_str,
+            show_cmd,
+            hotkey,
+        })
     }
 }
 
@@ -388,16 +991,570 @@ mod tests {
     #[test]
     fn test_scan_apps_initializes_manager() {
         unsafe {
-            let mut manager = AppManager::new();
-
             // This test just ensures the function doesn't panic
             // The actual scanning depends on the Windows environment
-            scan_apps(&mut manager);
+            let roots = scan_roots(&ScanConfig::default(), known_folders::current());
+            let output = scan_apps(&roots, &FilterRules::default(), &[], &[], &HashMap::new());
 
             // We can't assert specific apps since it depends on the system,
-            // but we can check that the manager is properly initialized
-            // The scan may produce apps depending on the system
-            let _ = manager.apps().len(); // Just ensure it's accessible
+            // but everything found is new compared to an empty list
+            assert!(output.diff.removed.is_empty());
+            assert!(output.diff.changed.is_empty());
         }
     }
+
+    fn write_file(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_scan_roots_follow_known_folders() {
+        let folders = KnownFolders {
+            app_data: Some(PathBuf::from("D:\\Profiles\\me\\Roaming")),
+            program_data: Some(PathBuf::from("D:\\ProgramData")),
+            ..KnownFolders::default()
+        };
+        let config = ScanConfig {
+            roots: vec![ScanRoot::new(PathBuf::from("E:\\PortableApps"))],
+        };
+
+        let roots = scan_roots(&config, &folders)
+            .into_iter()
+            .map(|root| root.path)
+            .collect::<Vec<_>>();
+        let start_menu = |base: &str| {
+            Path::new(base)
+                .join("Microsoft")
+                .join("Windows")
+                .join("Start Menu")
+                .join("Programs")
+        };
+        assert_eq!(
+            roots,
+            vec![
+                start_menu("D:\\Profiles\\me\\Roaming"),
+                start_menu("D:\\ProgramData"),
+                PathBuf::from("E:\\PortableApps"),
+            ]
+        );
+    }
+
+    fn listed_names(root: &ScanRoot) -> Vec<String> {
+        let mut files = Vec::new();
+        list_files(root, &root.path, &mut files);
+        assert!(files.iter().all(|f| f.size > 0 && f.mtime > 0));
+        let mut names = files
+            .iter()
+            .map(|f| {
+                f.path
+                    .strip_prefix(&root.path)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_list_files_recurses() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("Code.lnk"), "lnk");
+        write_file(&dir.path().join("Games").join("Game.url"), "URL=steam://1");
+        write_file(&dir.path().join("readme.txt"), "not a shortcut");
+
+        let root = ScanRoot::new(dir.path().to_path_buf());
+        assert_eq!(listed_names(&root), vec!["Code.lnk", "Games/Game.url"]);
+    }
+
+    #[test]
+    fn test_relative_folder() {
+        assert_eq!(relative_folder(Path::new("Code.lnk")), None);
+        assert_eq!(
+            relative_folder(
+                &Path::new("Visual Studio 2022")
+                    .join("Tools")
+                    .join("Prompt.lnk")
+            ),
+            Some("Visual Studio 2022\\Tools".to_string())
+        );
+    }
+
+    #[test]
+    fn test_list_files_applies_root_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path();
+        write_file(&apps.join("Tool").join("tool.exe"), "exe");
+        write_file(&apps.join("Tool").join("uninstall.exe"), "exe");
+        write_file(&apps.join("Tool").join("helper.dll"), "dll");
+        write_file(&apps.join("Tool").join("bin").join("deep.exe"), "exe");
+        write_file(&apps.join("Editor").join("Editor.EXE"), "exe");
+        write_file(&apps.join("Editor").join("Editor.lnk"), "lnk");
+        write_file(&apps.join("Backup").join("old.exe"), "exe");
+        write_file(&apps.join("Notes").join("notes.exe"), "exe");
+
+        let root = ScanRoot {
+            depth: Some(1),
+            extensions: vec!["exe".to_string()],
+            include: vec![
+                "Tool/*".to_string(),
+                "Editor/*".to_string(),
+                "Backup/*".to_string(),
+            ],
+            exclude: vec!["uninstall*".to_string(), "Backup".to_string()],
+            ..ScanRoot::new(apps.to_path_buf())
+        };
+        assert_eq!(
+            listed_names(&root),
+            vec!["Editor/Editor.EXE", "Tool/tool.exe"]
+        );
+    }
+
+    #[test]
+    fn test_rescan_subtrees_keeps_files_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let vendor = dir.path().join("Vendor");
+        write_file(&vendor.join("Tool.url"), "URL=tool://open\n");
+        write_file(&vendor.join("Old.url"), "URL=old://open\n");
+        std::fs::remove_file(vendor.join("Old.url")).unwrap();
+
+        let elsewhere = dir.path().join("Other").join("Kept.url");
+        let mut kept = AppEntry::new("Kept".to_string(), "kept://open".to_string(), 0, 0);
+        kept.shortcut_path = Some(elsewhere.to_string_lossy().to_string());
+        let mut old = AppEntry::new("Old".to_string(), "old://open".to_string(), 0, 0);
+        old.shortcut_path = Some(vendor.join("Old.url").to_string_lossy().to_string());
+
+        let previous = vec![
+            ScannedFile {
+                path: elsewhere.to_string_lossy().to_string(),
+                size: 1,
+                mtime: 1,
+                entry: Some(kept.clone()),
+            },
+            ScannedFile {
+                path: vendor.join("Old.url").to_string_lossy().to_string(),
+                size: 1,
+                mtime: 1,
+                entry: Some(old.clone()),
+            },
+        ];
+        let settings =
+            AppEntry::new_settings("Display".to_string(), "ms-settings:display".to_string(), -1);
+        let current = vec![kept, old, settings];
+
+        let output = rescan_subtrees(
+            &[ScanRoot::new(dir.path().to_path_buf())],
+            &FilterRules::default(),
+            std::slice::from_ref(&vendor),
+            &current,
+            &previous,
+            &HashMap::new(),
+        );
+
+        // Only the file in the changed folder was read
+        assert_eq!(output.reprocessed, 1);
+        assert_eq!(output.files.len(), 2);
+        assert_eq!(output.diff.added.len(), 1);
+        assert_eq!(output.diff.added[0].parse_name, "tool://open");
+        assert_eq!(
+            output.diff.removed,
+            vec![vendor.join("Old.url").to_string_lossy().to_string()]
+        );
+        assert!(output.diff.changed.is_empty());
+    }
+
+    fn stamp(path: &str, size: i64, mtime: i64) -> FileStamp {
+        FileStamp {
+            path: PathBuf::from(path),
+            size,
+            mtime,
+            folder: None,
+        }
+    }
+
+    fn scanned(path: &str, size: i64, mtime: i64, name: &str) -> ScannedFile {
+        ScannedFile {
+            path: path.to_string(),
+            size,
+            mtime,
+            entry: Some(AppEntry::new(name.to_string(), name.to_string(), 0, 0)),
+        }
+    }
+
+    #[test]
+    fn test_filter_rules_drop_entries_after_parsing() {
+        let mut setup = AppEntry::new("Foo Setup".to_string(), "setup.exe".to_string(), 0, 0);
+        setup.shortcut_path = Some("Foo Setup.lnk".to_string());
+        let files = vec![
+            scanned("code.lnk", 1, 1, "Code"),
+            ScannedFile {
+                path: "Foo Setup.lnk".to_string(),
+                size: 1,
+                mtime: 1,
+                entry: Some(setup),
+            },
+        ];
+
+        let output = finish_scan(
+            &[],
+            files,
+            Extras::default(),
+            &HashMap::new(),
+            0,
+            &FilterRules::default(),
+            ScanTimings::default(),
+        );
+
+        assert_eq!(output.diff.added.len(), 1);
+        assert_eq!(output.diff.added[0].name, "Code");
+        assert_eq!(
+            output.dropped,
+            vec![DroppedFile {
+                path: "Foo Setup.lnk".to_string(),
+                name: "Foo Setup".to_string(),
+                rule: "installer".to_string(),
+            }]
+        );
+        // The parsed entry is still stored, so a rule change applies without a rescan
+        assert!(output.files[1].entry.is_some());
+    }
+
+    #[test]
+    fn test_programs_give_uninstall_commands() {
+        let program = |name: &str, folder: &str, exe: Option<&str>| {
+            let program = InstalledProgram {
+                key: format!("HKEY_CURRENT_USER\\Uninstall\\{}", name),
+                name: name.to_string(),
+                icon: exe.map(|exe| (format!("{}\\{}", folder, exe), 0)),
+                install_location: Some(folder.to_string()),
+                uninstall_command: Some(format!("{}\\unins000.exe", folder)),
+            };
+            let entry = program.to_entry();
+            ProgramListing { program, entry }
+        };
+        let mut code = AppEntry::new(
+            "Visual Studio Code".to_string(),
+            "C:\\VS Code\\Code.exe".to_string(),
+            0,
+            0,
+        );
+        code.shortcut_path = Some("Code.lnk".to_string());
+        let tool = AppEntry::new("Tool".to_string(), "C:\\Tools\\tool.exe".to_string(), 0, 0);
+        let files = vec![
+            ScannedFile {
+                path: "Code.lnk".to_string(),
+                size: 1,
+                mtime: 1,
+                entry: Some(code),
+            },
+            ScannedFile {
+                path: "Tool.lnk".to_string(),
+                size: 1,
+                mtime: 1,
+                entry: Some(tool),
+            },
+        ];
+        let extras = Extras {
+            entries: Vec::new(),
+            programs: vec![
+                program(
+                    "Microsoft Visual Studio Code",
+                    "C:\\VS Code",
+                    Some("Code.exe"),
+                ),
+                program("7-Zip", "C:\\7-Zip", Some("7zFM.exe")),
+                program("Printer Driver", "C:\\Driver", None),
+                // Installed to the drive root, which holds every other program too
+                program("Greedy", "C:", None),
+                program("Contoso Setup", "C:\\Contoso", Some("contoso.exe")),
+            ],
+        };
+
+        let output = finish_scan(
+            &[],
+            files,
+            extras,
+            &HashMap::new(),
+            0,
+            &FilterRules::default(),
+            ScanTimings::default(),
+        );
+
+        // The shortcut stands in for its program, which isn't listed again
+        let listed = output
+            .diff
+            .added
+            .iter()
+            .map(|app| {
+                (
+                    app.name.as_str(),
+                    app.uninstall.as_deref(),
+                    app.uninstall_name.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            vec![
+                (
+                    "Visual Studio Code",
+                    Some("C:\\VS Code\\unins000.exe"),
+                    Some("Microsoft Visual Studio Code")
+                ),
+                ("Tool", None, None),
+                ("7-Zip", Some("C:\\7-Zip\\unins000.exe"), Some("7-Zip")),
+            ]
+        );
+        assert_eq!(
+            output.dropped,
+            vec![DroppedFile {
+                path: "HKEY_CURRENT_USER\\Uninstall\\Contoso Setup".to_string(),
+                name: "Contoso Setup".to_string(),
+                rule: "installer-name".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_are_recorded() {
+        let files = vec![
+            scanned("Code.lnk", 1, 1, "Code"),
+            scanned("Programs/Code.lnk", 1, 1, "Code"),
+        ];
+
+        let output = finish_scan(
+            &[],
+            files,
+            Extras::default(),
+            &HashMap::new(),
+            0,
+            &FilterRules::default(),
+            ScanTimings::default(),
+        );
+
+        assert_eq!(output.diff.added.len(), 1);
+        assert_eq!(
+            output.collisions,
+            vec![Collision {
+                path: "Programs/Code.lnk".to_string(),
+                kept_name: "Code".to_string(),
+                kept_target: "Code".to_string(),
+                reason: DuplicateReason::SameTarget,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_packaged_apps_are_filtered_and_deduplicated() {
+        let package = |name: &str, id: &str| {
+            AppEntry::new(name.to_string(), format!("shell:AppsFolder\\{}", id), 0, 0)
+        };
+        let extras = Extras {
+            entries: vec![
+                package(
+                    "Calculator",
+                    "Microsoft.WindowsCalculator_8wekyb3d8bbwe!App",
+                ),
+                package("Spotify", "SpotifyAB.SpotifyMusic_zpdnekdrzrea0!Spotify"),
+                package("Contoso Setup", "Contoso.Installer_abc!App"),
+                AppEntry::new_settings("Display".to_string(), "ms-settings:display".to_string(), 0),
+            ],
+            programs: Vec::new(),
+        };
+        let files = vec![
+            scanned("Spotify.lnk", 1, 1, "Spotify"),
+            scanned("Display.lnk", 1, 1, "Display"),
+        ];
+        let output = finish_scan(
+            &[],
+            files,
+            extras,
+            &HashMap::new(),
+            0,
+            &FilterRules::default(),
+            ScanTimings::default(),
+        );
+
+        let names = output
+            .diff
+            .added
+            .iter()
+            .map(|app| app.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Spotify", "Display", "Calculator"]);
+        assert_eq!(
+            output.dropped,
+            vec![DroppedFile {
+                path: "shell:AppsFolder\\Contoso.Installer_abc!App".to_string(),
+                name: "Contoso Setup".to_string(),
+                rule: "installer-name".to_string(),
+            }]
+        );
+        let collisions = output
+            .collisions
+            .iter()
+            .map(|collision| (collision.path.as_str(), collision.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            collisions,
+            vec![
+                (
+                    "shell:AppsFolder\\SpotifyAB.SpotifyMusic_zpdnekdrzrea0!Spotify",
+                    DuplicateReason::SameName
+                ),
+                // Settings pages aren't filtered, but still give way to a file
+                ("ms-settings:display", DuplicateReason::SameName),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_info_names_bare_executables() {
+        let info = VersionInfo {
+            file_description: Some("PowerShell 7".to_string()),
+            product_name: Some("PowerShell".to_string()),
+            company_name: Some("Microsoft Corporation".to_string()),
+            file_version: Some("7.4.1.500".to_string()),
+        };
+
+        // A plain .exe takes the program's name and keeps its stem searchable
+        let mut exe = AppEntry::new("pwsh".to_string(), "C:\\pwsh.exe".to_string(), 0, 0);
+        add_version_info(&mut exe, &info, true);
+        assert_eq!(exe.name, "PowerShell 7");
+        assert_eq!(exe.keywords.as_deref(), Some("pwsh, Microsoft Corporation"));
+
+        // A shortcut keeps the name it was given
+        let mut shortcut = AppEntry::new("pwsh".to_string(), "C:\\pwsh.exe".to_string(), 0, 0);
+        add_version_info(&mut shortcut, &info, false);
+        assert_eq!(shortcut.name, "pwsh");
+        assert_eq!(
+            shortcut.keywords.as_deref(),
+            Some("PowerShell 7, PowerShell, Microsoft Corporation")
+        );
+
+        let mut nothing_new = AppEntry::new("PowerShell 7".to_string(), "p".to_string(), 0, 0);
+        add_version_info(&mut nothing_new, &VersionInfo::default(), true);
+        assert_eq!(nothing_new.name, "PowerShell 7");
+        assert_eq!(nothing_new.keywords, None);
+    }
+
+    #[test]
+    fn test_target_version_info() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pe");
+        let pwsh = fixtures.join("pwsh.exe").to_string_lossy().to_string();
+        assert_eq!(
+            target_version_info(&pwsh).and_then(|info| info.file_description),
+            Some("PowerShell 7".to_string())
+        );
+        let script = fixtures
+            .join("make_fixtures.py")
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(target_version_info(&script), None);
+        assert_eq!(target_version_info("\\\\server\\share\\tool.exe"), None);
+    }
+
+    #[test]
+    fn test_target_missing() {
+        let folders = KnownFolders {
+            program_files: Some(PathBuf::from("C:\\Program Files")),
+            program_files_x86: Some(PathBuf::from("C:\\Program Files (x86)")),
+            ..KnownFolders::default()
+        };
+        let installed = ["C:\\Tools\\tool.exe", "C:\\Program Files\\Vendor\\app.exe"];
+        let exists = |path: &Path| {
+            let path = path.to_string_lossy().replace('/', "\\");
+            installed.iter().any(|p| p.eq_ignore_ascii_case(&path))
+        };
+
+        assert!(!target_missing("C:\\Tools\\tool.exe", &folders, exists));
+        assert!(target_missing("C:\\Tools\\gone.exe", &folders, exists));
+        // Found in the other Program Files folder
+        assert!(!target_missing(
+            "c:\\program files (x86)\\Vendor\\app.exe",
+            &folders,
+            exists
+        ));
+        assert!(target_missing(
+            "C:\\Program Files (x86)\\Gone\\app.exe",
+            &folders,
+            exists
+        ));
+        // Not checked
+        for target in [
+            "steam://rungameid/1",
+            "\\\\server\\app.exe",
+            "shell:AppsFolder\\x",
+            "C:",
+        ] {
+            assert!(!target_missing(target, &folders, exists));
+        }
+    }
+
+    #[test]
+    fn test_rescan_only_processes_new_and_changed_files() {
+        let previous = vec![
+            scanned("same.lnk", 10, 100, "Same"),
+            scanned("touched.lnk", 10, 100, "Old"),
+            scanned("resized.lnk", 10, 100, "Old"),
+            scanned("deleted.lnk", 10, 100, "Deleted"),
+        ];
+        let listing = vec![
+            stamp("same.lnk", 10, 100),
+            stamp("touched.lnk", 10, 200),
+            stamp("resized.lnk", 20, 100),
+            FileStamp {
+                folder: Some("Tools".to_string()),
+                ..stamp("new.lnk", 5, 300)
+            },
+        ];
+
+        let processed = std::sync::Mutex::new(Vec::new());
+        let requeried = std::sync::Mutex::new(Vec::new());
+        let (files, reprocessed) = rescan_files(
+            listing,
+            &previous,
+            2,
+            || (),
+            |path| {
+                let path = path.to_string_lossy().to_string();
+                processed.lock().unwrap().push(path.clone());
+                Some(AppEntry::new("New".to_string(), path, 0, 0))
+            },
+            |path, _| {
+                requeried
+                    .lock()
+                    .unwrap()
+                    .push(path.to_string_lossy().to_string());
+                42
+            },
+        );
+
+        let mut processed = processed.into_inner().unwrap();
+        processed.sort();
+        assert_eq!(processed, vec!["new.lnk", "resized.lnk", "touched.lnk"]);
+        assert_eq!(reprocessed, 3);
+        // Listing order, whichever worker finished first
+        let paths = files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["same.lnk", "touched.lnk", "resized.lnk", "new.lnk"]
+        );
+        assert_eq!(files[3].entry.as_ref().unwrap().parse_name, "new.lnk");
+        assert_eq!(
+            files[3].entry.as_ref().unwrap().folder.as_deref(),
+            Some("Tools")
+        );
+        assert_eq!(files.len(), 4);
+        // The stored icon index belonged to an earlier process, so it is asked for again
+        assert_eq!(requeried.into_inner().unwrap(), vec!["same.lnk"]);
+        assert_eq!(files[0].entry.as_ref().unwrap().icon_index, 42);
+        assert_eq!(files[0].entry.as_ref().unwrap().name, "Same");
+        assert_eq!((files[0].size, files[0].mtime), (10, 100));
+        assert_eq!(files[1].mtime, 200);
+        assert_eq!(files[1].entry.as_ref().unwrap().name, "New");
+        assert!(!files.iter().any(|f| f.path == "deleted.lnk"));
+    }
 }
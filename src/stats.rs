@@ -0,0 +1,416 @@
+use crate::app_model::AppEntryType;
+use crate::db::{Database, LaunchAction, SECONDS_PER_DAY};
+use crate::scanner::SCANNER_VERSION;
+use rusqlite::Result as SqlResult;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Number of apps listed per period unless `--top` says otherwise
+pub const DEFAULT_TOP: usize = 10;
+
+/// Width of the longest bar in the hour histogram
+const HISTOGRAM_WIDTH: i32 = 40;
+
+/// How `oxistart stats` prints its report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl StatsFormat {
+    /// Parse the value of `--format`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "table" => Some(StatsFormat::Table),
+            "json" => Some(StatsFormat::Json),
+            "csv" => Some(StatsFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Launch counts of a single app
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppCount {
+    pub entry_id: String,
+    /// Display name from the app cache, or the entry id if the app is no longer installed
+    pub name: String,
+    pub count: i32,
+}
+
+/// How often launches used one action
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActionCount {
+    pub action: LaunchAction,
+    pub count: i32,
+}
+
+/// Launch statistics for the period ending now
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodStats {
+    /// `day`, `week` or `month`
+    pub period: &'static str,
+    pub launches: i32,
+    /// Most launched apps, most launched first
+    pub top: Vec<AppCount>,
+    /// Average position of the launched result (0 = first), for launches made from a search
+    pub average_rank: Option<f64>,
+}
+
+/// Everything `oxistart stats` reports
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    /// When the report was made (seconds since the Unix epoch)
+    pub generated_at: i64,
+    pub periods: Vec<PeriodStats>,
+    /// Dated launches in each hour of the day, local time
+    pub launches_by_hour: Vec<i32>,
+    /// Launches that opened the app, ran it as administrator or opened its location
+    pub launches_by_action: Vec<ActionCount>,
+    /// Names of installed apps that have never been launched; Settings pages
+    /// aren't counted as launches, so they are left out
+    pub never_launched: Vec<String>,
+    /// Average result rank over the whole history
+    pub average_rank: Option<f64>,
+}
+
+impl Stats {
+    /// Gather the report from the launch history and the app cache
+    pub fn collect(db: &Database, now: i64, top: usize) -> SqlResult<Self> {
+        let names = db
+            .load_app_cache(SCANNER_VERSION)?
+            .into_iter()
+            .filter(|app| app.entry_type == AppEntryType::Application)
+            .map(|app| (app.parse_name, app.name))
+            .collect::<HashMap<_, _>>();
+
+        let mut periods = Vec::new();
+        for (period, days) in [("day", 1), ("week", 7), ("month", 30)] {
+            let since = now - days * SECONDS_PER_DAY;
+            let counts = db.launch_counts_since(since)?;
+            periods.push(PeriodStats {
+                period,
+                launches: counts.values().sum(),
+                top: top_apps(counts, &names, top),
+                average_rank: db.average_launch_rank(since)?,
+            });
+        }
+
+        let usage = db.load_usage_map()?;
+        let launched = db.launch_counts_since(i64::MIN)?;
+        let mut never_launched = names
+            .iter()
+            .filter(|(id, _)| !usage.contains_key(*id) && !launched.contains_key(*id))
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>();
+        never_launched.sort_by_key(|name| name.to_lowercase());
+
+        let by_action = db.launch_counts_by_action()?;
+        let launches_by_action = [
+            LaunchAction::Open,
+            LaunchAction::Admin,
+            LaunchAction::Location,
+        ]
+        .into_iter()
+        .map(|action| ActionCount {
+            action,
+            count: by_action.get(&action).copied().unwrap_or(0),
+        })
+        .collect();
+
+        Ok(Stats {
+            generated_at: now,
+            periods,
+            launches_by_hour: db.launch_counts_by_hour()?.to_vec(),
+            launches_by_action,
+            never_launched,
+            average_rank: db.average_launch_rank(i64::MIN)?,
+        })
+    }
+
+    /// Render the report in `format`
+    pub fn render(&self, format: StatsFormat) -> String {
+        match format {
+            StatsFormat::Table => self.to_table(),
+            StatsFormat::Json => {
+                serde_json::to_string_pretty(self).expect("stats are always serializable")
+            }
+            StatsFormat::Csv => self.to_csv(),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        let mut out = String::new();
+
+        for period in &self.periods {
+            out.push_str(&format!(
+                "Top apps, last {} ({} launches, average rank {})\n",
+                period.period,
+                period.launches,
+                format_rank(period.average_rank)
+            ));
+            if period.top.is_empty() {
+                out.push_str("  (none)\n");
+            }
+            for app in &period.top {
+                out.push_str(&format!("  {:>5}  {}\n", app.count, app.name));
+            }
+            out.push('\n');
+        }
+
+        out.push_str("Launches per hour\n");
+        let busiest = self
+            .launches_by_hour
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        for (hour, count) in self.launches_by_hour.iter().enumerate() {
+            let bar = "#".repeat((count * HISTOGRAM_WIDTH / busiest) as usize);
+            out.push_str(&format!("  {:02}  {:>5}  {}\n", hour, count, bar));
+        }
+        out.push('\n');
+
+        out.push_str("Launches by action\n");
+        for action in &self.launches_by_action {
+            out.push_str(&format!(
+                "  {:>5}  {}\n",
+                action.count,
+                action.action.as_str()
+            ));
+        }
+        out.push('\n');
+
+        out.push_str(&format!("Never launched ({})\n", self.never_launched.len()));
+        for name in &self.never_launched {
+            out.push_str(&format!("  {}\n", name));
+        }
+        out.push('\n');
+
+        out.push_str(&format!(
+            "Average rank (all time): {}\n",
+            format_rank(self.average_rank)
+        ));
+        out
+    }
+
+    /// One `section,name,value` row per figure, so it loads into a spreadsheet as-is
+    fn to_csv(&self) -> String {
+        let mut rows = vec![[
+            "section".to_string(),
+            "name".to_string(),
+            "value".to_string(),
+        ]];
+
+        for period in &self.periods {
+            for app in &period.top {
+                rows.push([
+                    format!("top_{}", period.period),
+                    app.name.clone(),
+                    app.count.to_string(),
+                ]);
+            }
+            rows.push([
+                format!("launches_{}", period.period),
+                String::new(),
+                period.launches.to_string(),
+            ]);
+            rows.push([
+                format!("average_rank_{}", period.period),
+                String::new(),
+                period
+                    .average_rank
+                    .map(|r| r.to_string())
+                    .unwrap_or_default(),
+            ]);
+        }
+        for (hour, count) in self.launches_by_hour.iter().enumerate() {
+            rows.push(["hour".to_string(), hour.to_string(), count.to_string()]);
+        }
+        for action in &self.launches_by_action {
+            rows.push([
+                "action".to_string(),
+                action.action.as_str().to_string(),
+                action.count.to_string(),
+            ]);
+        }
+        for name in &self.never_launched {
+            rows.push(["never_launched".to_string(), name.clone(), String::new()]);
+        }
+        rows.push([
+            "average_rank".to_string(),
+            String::new(),
+            self.average_rank.map(|r| r.to_string()).unwrap_or_default(),
+        ]);
+
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|f| csv_field(f))
+                    .collect::<Vec<_>>()
+                    .join(",")
+                    + "\n"
+            })
+            .collect()
+    }
+}
+
+/// The `top` most launched apps, ties broken by name
+fn top_apps(
+    counts: HashMap<String, i32>,
+    names: &HashMap<String, String>,
+    top: usize,
+) -> Vec<AppCount> {
+    let mut apps = counts
+        .into_iter()
+        .map(|(entry_id, count)| AppCount {
+            name: names
+                .get(&entry_id)
+                .cloned()
+                .unwrap_or_else(|| entry_id.clone()),
+            entry_id,
+            count,
+        })
+        .collect::<Vec<_>>();
+    apps.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    apps.truncate(top);
+    apps
+}
+
+fn format_rank(rank: Option<f64>) -> String {
+    rank.map(|r| format!("{:.2}", r))
+        .unwrap_or_else(|| "n/a".to_string())
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_model::AppEntry;
+    use crate::db::LaunchEvent;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn sample_db() -> Database {
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_app_cache(
+            &[
+                AppEntry::new("Code".to_string(), "code.exe".to_string(), 0, 0),
+                AppEntry::new("Paint".to_string(), "paint.exe".to_string(), 0, 0),
+                AppEntry::new("Steam".to_string(), "steam.exe".to_string(), 0, 0),
+                AppEntry::new_settings("Display".to_string(), "ms-settings:display".to_string(), 0),
+            ],
+            SCANNER_VERSION,
+        )
+        .unwrap();
+
+        let launches = [
+            (NOW - 60, "code.exe", Some(0)),
+            (NOW - 120, "code.exe", Some(2)),
+            (NOW - 3 * SECONDS_PER_DAY, "steam.exe", Some(1)),
+            (NOW - 3 * SECONDS_PER_DAY, "removed.exe", None),
+        ];
+        for (timestamp, entry_id, rank) in launches {
+            db.record_launch(&LaunchEvent {
+                timestamp,
+                entry_id: entry_id.to_string(),
+                action: if entry_id == "steam.exe" {
+                    LaunchAction::Admin
+                } else {
+                    LaunchAction::Open
+                },
+                query: rank.map(|_| "q".to_string()),
+                rank,
+            })
+            .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_collect() {
+        let stats = Stats::collect(&sample_db(), NOW, DEFAULT_TOP).unwrap();
+
+        let day = &stats.periods[0];
+        assert_eq!(day.launches, 2);
+        assert_eq!(day.top.len(), 1);
+        assert_eq!(day.top[0].name, "Code");
+        assert_eq!(day.average_rank, Some(1.0));
+
+        let week = &stats.periods[1];
+        assert_eq!(week.launches, 4);
+        assert_eq!(week.top[0].name, "Code");
+        // Apps missing from the cache are shown by their id
+        assert!(week.top.iter().any(|app| app.name == "removed.exe"));
+
+        assert_eq!(stats.never_launched, vec!["Paint".to_string()]);
+        assert_eq!(stats.launches_by_hour.len(), 24);
+        assert_eq!(stats.launches_by_hour.iter().sum::<i32>(), 4);
+        assert_eq!(
+            stats.launches_by_action,
+            vec![
+                ActionCount {
+                    action: LaunchAction::Open,
+                    count: 3
+                },
+                ActionCount {
+                    action: LaunchAction::Admin,
+                    count: 1
+                },
+                ActionCount {
+                    action: LaunchAction::Location,
+                    count: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_limit() {
+        let stats = Stats::collect(&sample_db(), NOW, 1).unwrap();
+        assert_eq!(stats.periods[1].top.len(), 1);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let stats = Stats::collect(&sample_db(), NOW, DEFAULT_TOP).unwrap();
+
+        let table = stats.render(StatsFormat::Table);
+        assert!(table.contains("Top apps, last day (2 launches, average rank 1.00)"));
+        assert!(table.contains("Never launched (1)"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&stats.render(StatsFormat::Json)).unwrap();
+        assert_eq!(json["periods"][0]["top"][0]["name"], "Code");
+
+        let csv = stats.render(StatsFormat::Csv);
+        assert!(csv.starts_with("section,name,value\n"));
+        assert!(csv.contains("top_day,Code,2\n"));
+        assert!(csv.contains("never_launched,Paint,\n"));
+        assert!(csv.contains("action,admin,1\n"));
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("Code"), "Code");
+        assert_eq!(csv_field("Foo, Inc."), "\"Foo, Inc.\"");
+        assert_eq!(csv_field("Say \"hi\""), "\"Say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!(StatsFormat::parse("csv"), Some(StatsFormat::Csv));
+        assert_eq!(StatsFormat::parse("xml"), None);
+    }
+}
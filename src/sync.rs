@@ -0,0 +1,496 @@
+use crate::db::{Database, LaunchEvent};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// `meta` key holding the shared directory chosen with `oxistart sync --dir`
+const SYNC_DIR_KEY: &str = "sync_dir";
+
+/// `meta` key holding the seq of the last launch written to this machine's log
+const PUBLISHED_SEQ_KEY: &str = "sync_published_seq";
+
+/// Extension of the per-machine event logs in the sync directory
+const LOG_EXTENSION: &str = "jsonl";
+
+/// One line of a machine's event log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Position of the event in its machine's history; never reused
+    pub seq: i64,
+    #[serde(flatten)]
+    pub event: LaunchEvent,
+}
+
+/// What a sync run did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SyncReport {
+    /// Local events appended to this machine's log
+    pub published: usize,
+    /// Events from other machines that were new here
+    pub merged: usize,
+    /// Number of other machines' logs that were read
+    pub machines: usize,
+}
+
+/// Id of this machine, created on first use
+pub fn machine_id(db: &Database) -> Result<String, String> {
+    db.machine_id().map_err(|e| e.to_string())
+}
+
+/// The sync directory remembered from a previous `oxistart sync --dir`
+pub fn configured_dir(db: &Database) -> Option<PathBuf> {
+    db.meta_value(SYNC_DIR_KEY)
+        .ok()
+        .flatten()
+        .map(PathBuf::from)
+}
+
+/// Remember `dir` as the sync directory for future runs
+pub fn set_configured_dir(db: &Database, dir: &Path) -> Result<(), String> {
+    if configured_dir(db).as_deref() != Some(dir) {
+        // A new directory starts a new log
+        db.set_meta_value(PUBLISHED_SEQ_KEY, "0")
+            .map_err(|e| e.to_string())?;
+    }
+    db.set_meta_value(SYNC_DIR_KEY, &dir.to_string_lossy())
+        .map_err(|e| e.to_string())
+}
+
+/// Publish local launches to `dir` and merge in every other machine's log
+pub fn sync(db: &mut Database, dir: &Path) -> Result<SyncReport, String> {
+    plan_sync(db, dir)?.exchange()?.apply(db)
+}
+
+/// The database side of a sync, read before touching the sync directory
+pub struct SyncPlan {
+    dir: PathBuf,
+    own_id: String,
+    publication: Publication,
+}
+
+/// Logs read from the sync directory, waiting to be merged
+pub struct SyncExchange {
+    publication: Publication,
+    logs: Vec<(String, PathBuf, Vec<LogEntry>)>,
+}
+
+/// Collect what a sync of `dir` needs from the database. The file work is
+/// left to [`SyncPlan::exchange`], so callers can do it without holding the
+/// database.
+pub fn plan_sync(db: &Database, dir: &Path) -> Result<SyncPlan, String> {
+    Ok(SyncPlan {
+        dir: dir.to_path_buf(),
+        own_id: machine_id(db)?,
+        publication: prepare_publish(db, dir)?,
+    })
+}
+
+impl SyncPlan {
+    /// Append our launches to our log and read every other machine's log
+    pub fn exchange(self) -> Result<SyncExchange, String> {
+        let dir = &self.dir;
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        self.publication.write()?;
+
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let mut paths = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(LOG_EXTENSION))
+            .filter_map(|path| {
+                let origin = path.file_stem()?.to_str()?.to_string();
+                (origin != self.own_id).then_some((origin, path))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let logs = paths
+            .into_iter()
+            .map(|(origin, path)| {
+                let entries = read_log(&path)?;
+                Ok((origin, path, entries))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(SyncExchange {
+            publication: self.publication,
+            logs,
+        })
+    }
+}
+
+impl SyncExchange {
+    /// Record what was published and merge the other machines' launches
+    pub fn apply(self, db: &mut Database) -> Result<SyncReport, String> {
+        let mut report = SyncReport {
+            published: self.publication.finish(db)?,
+            ..SyncReport::default()
+        };
+
+        // Launches this machine has cleared or let expire stay gone
+        let floor = db
+            .history_floor(crate::db::now_timestamp())
+            .map_err(|e| e.to_string())?;
+
+        for (origin, path, entries) in self.logs {
+            let events = entries
+                .into_iter()
+                .filter(|entry| entry.event.timestamp >= floor)
+                .map(|entry| (entry.seq, entry.event))
+                .collect::<Vec<_>>();
+            report.merged += db
+                .merge_foreign_events(&origin, &events)
+                .map_err(|e| format!("Failed to merge {}: {}", path.display(), e))?;
+            report.machines += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+/// Local launches that are not yet in this machine's log
+pub struct Publication {
+    path: PathBuf,
+    events: Vec<(i64, LaunchEvent)>,
+}
+
+/// Collect the local launches that are not yet in this machine's log, so
+/// they can be written without holding the database
+pub fn prepare_publish(db: &Database, dir: &Path) -> Result<Publication, String> {
+    let path = own_log_path(db, dir)?;
+
+    // A deleted log is simply rewritten
+    let published = if path.exists() {
+        db.meta_value(PUBLISHED_SEQ_KEY)
+            .map_err(|e| e.to_string())?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    let events = db
+        .load_local_events_after(published)
+        .map_err(|e| e.to_string())?;
+
+    Ok(Publication { path, events })
+}
+
+impl Publication {
+    /// Whether there is nothing to append
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Append the launches to the log
+    pub fn write(&self) -> Result<(), String> {
+        if self.events.is_empty() {
+            return Ok(());
+        }
+        let path = &self.path;
+
+        let mut text = String::new();
+        // Finish a line left incomplete by an interrupted write so ours stays readable
+        if !ends_with_newline(path) {
+            text.push('\n');
+        }
+        for (seq, event) in &self.events {
+            let entry = LogEntry {
+                seq: *seq,
+                event: event.clone(),
+            };
+            text.push_str(&serde_json::to_string(&entry).map_err(|e| e.to_string())?);
+            text.push('\n');
+        }
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Remember the written launches as published; returns how many there were
+    pub fn finish(&self, db: &Database) -> Result<usize, String> {
+        if let Some((seq, _)) = self.events.last() {
+            db.set_meta_value(PUBLISHED_SEQ_KEY, &seq.to_string())
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(self.events.len())
+    }
+}
+
+/// Whether `path` is missing, empty or ends with a complete line
+fn ends_with_newline(path: &Path) -> bool {
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return true;
+    };
+    let mut last = [b'\n'];
+    if file.seek(SeekFrom::End(-1)).is_ok() {
+        let _ = file.read_exact(&mut last);
+    }
+    last[0] == b'\n'
+}
+
+/// This machine's log in `dir`
+fn own_log_path(db: &Database, dir: &Path) -> Result<PathBuf, String> {
+    Ok(dir.join(format!("{}.{}", machine_id(db)?, LOG_EXTENSION)))
+}
+
+/// Delete this machine's log from `dir`, e.g. after the history was cleared
+pub fn remove_own_log(db: &Database, dir: &Path) -> Result<(), String> {
+    let path = own_log_path(db, dir)?;
+    db.set_meta_value(PUBLISHED_SEQ_KEY, "0")
+        .map_err(|e| e.to_string())?;
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {}", path.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read a log, skipping lines that don't parse (e.g. one still being written
+/// by the other machine or half-synced by the file sync tool)
+fn read_log(path: &Path) -> Result<Vec<LogEntry>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::LaunchAction;
+
+    fn launch(db: &mut Database, entry_id: &str, timestamp: i64) {
+        let mut event = LaunchEvent::now(entry_id, LaunchAction::Open, None, None);
+        event.timestamp = timestamp;
+        db.record_launch(&event).unwrap();
+    }
+
+    fn publish(db: &Database, dir: &Path) -> Result<usize, String> {
+        let publication = prepare_publish(db, dir)?;
+        publication.write()?;
+        publication.finish(db)
+    }
+
+    fn usage(db: &Database, entry_id: &str) -> Option<i32> {
+        db.load_usage_map().unwrap().get(entry_id).copied()
+    }
+
+    #[test]
+    fn test_machine_id_is_stable() {
+        let db = Database::open_in_memory().unwrap();
+        let id = machine_id(&db).unwrap();
+        assert_eq!(id.len(), 16);
+        assert_eq!(machine_id(&db).unwrap(), id);
+        assert_ne!(
+            machine_id(&Database::open_in_memory().unwrap()).unwrap(),
+            id
+        );
+    }
+
+    #[test]
+    fn test_two_machines_converge() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut laptop = Database::open_in_memory().unwrap();
+        let mut desktop = Database::open_in_memory().unwrap();
+
+        launch(&mut laptop, "code.exe", 100);
+        launch(&mut laptop, "code.exe", 200);
+        launch(&mut desktop, "code.exe", 150);
+        launch(&mut desktop, "steam.exe", 160);
+
+        sync(&mut laptop, dir.path()).unwrap();
+        let report = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(report.published, 2);
+        assert_eq!(report.merged, 2);
+        assert_eq!(report.machines, 1);
+        sync(&mut laptop, dir.path()).unwrap();
+
+        for db in [&laptop, &desktop] {
+            assert_eq!(usage(db, "code.exe"), Some(3));
+            assert_eq!(usage(db, "steam.exe"), Some(1));
+            assert_eq!(db.load_launch_events(None).unwrap().len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_merging_twice_never_double_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut laptop = Database::open_in_memory().unwrap();
+        let mut desktop = Database::open_in_memory().unwrap();
+
+        launch(&mut laptop, "code.exe", 100);
+        sync(&mut laptop, dir.path()).unwrap();
+
+        sync(&mut desktop, dir.path()).unwrap();
+        let again = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(again.merged, 0);
+        assert_eq!(usage(&desktop, "code.exe"), Some(1));
+
+        // Foreign events are never republished into our own log
+        assert_eq!(again.published, 0);
+        sync(&mut laptop, dir.path()).unwrap();
+        assert_eq!(usage(&laptop, "code.exe"), Some(1));
+    }
+
+    #[test]
+    fn test_exported_launches_are_not_synced_twice() {
+        use crate::user_data::ImportMode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut laptop = Database::open_in_memory().unwrap();
+        let mut desktop = Database::open_in_memory().unwrap();
+
+        launch(&mut laptop, "code.exe", 100);
+        launch(&mut desktop, "steam.exe", 150);
+        let exported = laptop.export_user_data().unwrap();
+        desktop
+            .import_user_data(&exported, ImportMode::Merge)
+            .unwrap();
+        launch(&mut laptop, "code.exe", 200);
+
+        // The imported launch is the laptop's, so the desktop doesn't
+        // publish it and the laptop doesn't merge it back
+        let report = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(report.published, 1);
+        sync(&mut laptop, dir.path()).unwrap();
+        let report = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(report.merged, 1);
+
+        for db in [&laptop, &desktop] {
+            assert_eq!(usage(db, "code.exe"), Some(2));
+            assert_eq!(usage(db, "steam.exe"), Some(1));
+            assert_eq!(db.load_launch_events(None).unwrap().len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_only_new_launches_are_appended() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::open_in_memory().unwrap();
+
+        launch(&mut db, "a.exe", 100);
+        assert_eq!(publish(&db, dir.path()).unwrap(), 1);
+        assert_eq!(publish(&db, dir.path()).unwrap(), 0);
+        launch(&mut db, "b.exe", 200);
+        assert_eq!(publish(&db, dir.path()).unwrap(), 1);
+
+        let log = dir
+            .path()
+            .join(format!("{}.jsonl", machine_id(&db).unwrap()));
+        let entries = read_log(&log).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].seq < entries[1].seq);
+    }
+
+    #[test]
+    fn test_deleted_log_is_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::open_in_memory().unwrap();
+        launch(&mut db, "a.exe", 100);
+        publish(&db, dir.path()).unwrap();
+
+        std::fs::remove_file(own_log_path(&db, dir.path()).unwrap()).unwrap();
+        launch(&mut db, "b.exe", 200);
+        assert_eq!(publish(&db, dir.path()).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_nothing_is_published_until_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::open_in_memory().unwrap();
+        launch(&mut db, "a.exe", 100);
+
+        let publication = prepare_publish(&db, dir.path()).unwrap();
+        assert!(!publication.is_empty());
+        // Not finished, e.g. because the directory was offline
+        assert_eq!(publish(&db, dir.path()).unwrap(), 1);
+        assert!(prepare_publish(&db, dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_truncated_line_is_skipped_and_repaired() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut laptop = Database::open_in_memory().unwrap();
+        launch(&mut laptop, "a.exe", 100);
+        publish(&laptop, dir.path()).unwrap();
+
+        let log = dir
+            .path()
+            .join(format!("{}.jsonl", machine_id(&laptop).unwrap()));
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        file.write_all(b"{\"seq\": 99, \"timest").unwrap();
+        drop(file);
+
+        launch(&mut laptop, "b.exe", 200);
+        publish(&laptop, dir.path()).unwrap();
+
+        let mut desktop = Database::open_in_memory().unwrap();
+        let report = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(report.merged, 2);
+        assert_eq!(usage(&desktop, "b.exe"), Some(1));
+    }
+
+    #[test]
+    fn test_cleared_history_is_not_merged_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut laptop = Database::open_in_memory().unwrap();
+        let mut desktop = Database::open_in_memory().unwrap();
+
+        launch(&mut laptop, "code.exe", 100);
+        sync(&mut laptop, dir.path()).unwrap();
+        sync(&mut desktop, dir.path()).unwrap();
+
+        desktop.clear_history(crate::db::now_timestamp()).unwrap();
+        remove_own_log(&desktop, dir.path()).unwrap();
+        let report = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(report.merged, 0);
+        assert_eq!(usage(&desktop, "code.exe"), None);
+    }
+
+    #[test]
+    fn test_pruned_launches_are_not_merged_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut laptop = Database::open_in_memory().unwrap();
+        let mut desktop = Database::open_in_memory().unwrap();
+
+        launch(&mut laptop, "C:\\Gone\\app.exe", 100);
+        launch(&mut laptop, "C:\\Here\\app.exe", 100);
+        sync(&mut laptop, dir.path()).unwrap();
+        sync(&mut desktop, dir.path()).unwrap();
+
+        desktop
+            .set_retention_policy(&crate::db::RetentionPolicy {
+                max_age_days: None,
+                prune_missing: true,
+            })
+            .unwrap();
+        desktop
+            .apply_retention(200, |path| !path.to_string_lossy().starts_with("C:\\Gone"))
+            .unwrap();
+        let report = sync(&mut desktop, dir.path()).unwrap();
+        assert_eq!(report.merged, 0);
+        assert_eq!(usage(&desktop, "C:\\Gone\\app.exe"), None);
+        assert_eq!(usage(&desktop, "C:\\Here\\app.exe"), Some(1));
+    }
+
+    #[test]
+    fn test_configured_dir_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(configured_dir(&db), None);
+        set_configured_dir(&db, Path::new("D:\\Sync")).unwrap();
+        assert_eq!(configured_dir(&db), Some(PathBuf::from("D:\\Sync")));
+    }
+}
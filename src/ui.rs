@@ -1,4 +1,4 @@
-use crate::app_model::{AppEntryType, AppManager};
+use crate::app_model::{AppEntryType, AppManager, ListRow};
 use crate::utils;
 use std::ffi::c_void;
 use windows::{
@@ -107,29 +107,22 @@ pub unsafe fn setup_listview(hwnd: HWND, dark_mode: bool) -> Result<()> {
     Ok(())
 }
 
-/// Get system image list for icons (large icons for better visibility)
-pub unsafe fn get_system_image_list() -> isize {
-    let mut shfi = SHFILEINFOW::default();
-    SHGetFileInfoW(
-        w!(""),
-        FILE_FLAGS_AND_ATTRIBUTES(FILE_ATTRIBUTE_NORMAL.0),
-        Some(&mut shfi),
-        std::mem::size_of::<SHFILEINFOW>() as u32,
-        SHGFI_SYSICONINDEX | SHGFI_LARGEICON | SHGFI_USEFILEATTRIBUTES, // Changed to LARGEICON
-    ) as isize
-}
-
-/// Get system image list for small icons
-#[allow(dead_code)]
-pub unsafe fn get_system_image_list_small() -> isize {
-    let mut shfi = SHFILEINFOW::default();
-    SHGetFileInfoW(
-        w!(""),
-        FILE_FLAGS_AND_ATTRIBUTES(FILE_ATTRIBUTE_NORMAL.0),
-        Some(&mut shfi),
-        std::mem::size_of::<SHFILEINFOW>() as u32,
-        SHGFI_SYSICONINDEX | SHGFI_SMALLICON | SHGFI_USEFILEATTRIBUTES,
-    ) as isize
+/// Get icon index for folders in browse mode
+pub unsafe fn get_folder_icon_index() -> i32 {
+    let mut sii = SHSTOCKICONINFO {
+        cbSize: std::mem::size_of::<SHSTOCKICONINFO>() as u32,
+        ..Default::default()
+    };
+    let result = SHGetStockIconInfo(
+        SHSTOCKICONID(3), // SIID_FOLDER
+        SHGSI_SYSICONINDEX,
+        &mut sii,
+    );
+    if result.is_ok() {
+        sii.iSysImageIndex
+    } else {
+        0
+    }
 }
 
 /// Get icon index for settings (gear icon)
@@ -170,48 +163,59 @@ pub unsafe fn get_settings_icon_index() -> i32 {
     0 // Final fallback
 }
 
-/// Update the list view with filtered applications
+/// Update the list view with filtered applications, and folders while browsing.
+///
+/// Folder rows have an `lParam` of -1; app rows hold the app's position.
 pub unsafe fn update_listview(list_hwnd: HWND, app_manager: &AppManager) {
+    let rows = app_manager.rows();
     SendMessageW(list_hwnd, LVM_DELETEALLITEMS, WPARAM(0), LPARAM(0));
-    SendMessageW(
-        list_hwnd,
-        LVM_SETITEMCOUNT,
-        WPARAM(app_manager.filtered_indices().len()),
-        LPARAM(0),
-    );
+    SendMessageW(list_hwnd, LVM_SETITEMCOUNT, WPARAM(rows.len()), LPARAM(0));
 
     let settings_icon_index = get_settings_icon_index();
+    let folder_icon_index = get_folder_icon_index();
+
+    for (list_idx, row) in rows.iter().enumerate() {
+        let (label, icon_index, param) = match *row {
+            ListRow::Folder(name) => (name.to_string(), folder_icon_index, -1),
+            ListRow::App(app_idx) => {
+                let Some(app) = app_manager.apps().get(app_idx) else {
+                    continue;
+                };
+                // Broken shortcuts stay findable, but say why they won't open
+                let label = if app.broken {
+                    format!("{} (target missing)", app.name)
+                } else {
+                    app.name.clone()
+                };
+                // Use gear icon for settings items, otherwise use the app's icon
+                let icon_index = match app.entry_type {
+                    AppEntryType::Settings => settings_icon_index,
+                    AppEntryType::Application => app.icon_index,
+                };
+                (label, icon_index, app_idx as isize)
+            }
+        };
+        let mut name_wide = utils::to_wide_string(&label);
 
-    for (list_idx, &app_idx) in app_manager.filtered_indices().iter().enumerate() {
-        if let Some(app) = app_manager.apps().get(app_idx) {
-            let mut name_wide = utils::to_wide_string(&app.name);
-
-            // Use gear icon for settings items, otherwise use the app's icon
-            let icon_index = match app.entry_type {
-                AppEntryType::Settings => settings_icon_index,
-                AppEntryType::Application => app.icon_index,
-            };
-
-            let mut item = LVITEMW {
-                mask: LVIF_TEXT | LVIF_IMAGE | LVIF_PARAM,
-                iItem: list_idx as i32,
-                iSubItem: 0,
-                pszText: PWSTR(name_wide.as_mut_ptr()),
-                iImage: icon_index,
-                lParam: LPARAM(app_idx as isize),
-                ..Default::default()
-            };
-            SendMessageW(
-                list_hwnd,
-                LVM_INSERTITEMW,
-                WPARAM(0),
-                LPARAM(&mut item as *mut _ as isize),
-            );
-        }
+        let mut item = LVITEMW {
+            mask: LVIF_TEXT | LVIF_IMAGE | LVIF_PARAM,
+            iItem: list_idx as i32,
+            iSubItem: 0,
+            pszText: PWSTR(name_wide.as_mut_ptr()),
+            iImage: icon_index,
+            lParam: LPARAM(param),
+            ..Default::default()
+        };
+        SendMessageW(
+            list_hwnd,
+            LVM_INSERTITEMW,
+            WPARAM(0),
+            LPARAM(&mut item as *mut _ as isize),
+        );
     }
 
     // Select first item if available
-    if !app_manager.filtered_indices().is_empty() {
+    if !rows.is_empty() {
         let mut item = LVITEMW {
             mask: LVIF_STATE,
             state: LIST_VIEW_ITEM_STATE_FLAGS(LVIS_SELECTED.0 | LVIS_FOCUSED.0),
@@ -227,6 +231,32 @@ pub unsafe fn update_listview(list_hwnd: HWND, app_manager: &AppManager) {
     }
 }
 
+/// Select row `row` of the list view (and only that row) and scroll it into view
+pub unsafe fn select_item(list_hwnd: HWND, row: usize) {
+    let mut item = LVITEMW {
+        mask: LVIF_STATE,
+        state: LIST_VIEW_ITEM_STATE_FLAGS(0),
+        stateMask: LIST_VIEW_ITEM_STATE_FLAGS(LVIS_SELECTED.0 | LVIS_FOCUSED.0),
+        ..Default::default()
+    };
+    // Item -1 means every item
+    SendMessageW(
+        list_hwnd,
+        LVM_SETITEMSTATE,
+        WPARAM(usize::MAX),
+        LPARAM(&mut item as *mut _ as isize),
+    );
+
+    item.state = LIST_VIEW_ITEM_STATE_FLAGS(LVIS_SELECTED.0 | LVIS_FOCUSED.0);
+    SendMessageW(
+        list_hwnd,
+        LVM_SETITEMSTATE,
+        WPARAM(row),
+        LPARAM(&mut item as *mut _ as isize),
+    );
+    SendMessageW(list_hwnd, LVM_ENSUREVISIBLE, WPARAM(row), LPARAM(0));
+}
+
 /// Add a system tray icon
 pub unsafe fn add_tray_icon(hwnd: HWND) -> Result<()> {
     use windows::Win32::System::LibraryLoader::GetModuleHandleW;
@@ -327,7 +357,7 @@ mod tests {
     #[test]
     fn test_get_system_image_list() {
         unsafe {
-            let img_list = get_system_image_list();
+            let img_list = utils::get_system_image_list();
             assert_ne!(img_list, 0);
         }
     }
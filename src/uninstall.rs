@@ -0,0 +1,386 @@
+use crate::app_model::AppEntry;
+use crate::env_vars;
+use crate::known_folders::{Environment, KnownFolders};
+use crate::registry::RegistryReader;
+
+/// Keys listing installed programs: machine-wide (64-bit and 32-bit views)
+/// and for the current user
+pub const UNINSTALL_KEYS: [&str; 3] = [
+    "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall",
+    "HKEY_LOCAL_MACHINE\\SOFTWARE\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Uninstall",
+    "HKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall",
+];
+
+/// `ReleaseType`s of entries that patch another program rather than being one
+const UPDATE_RELEASE_TYPES: [&str; 4] = ["Update", "Hotfix", "Security Update", "Update Rollup"];
+
+/// A program as Apps & Features lists it, from its Uninstall registry entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledProgram {
+    /// The registry key the program was read from
+    pub key: String,
+    pub name: String,
+    /// File the icon comes from and the icon's index in it (`DisplayIcon`)
+    pub icon: Option<(String, i32)>,
+    /// Folder the program is installed in, without a trailing separator
+    pub install_location: Option<String>,
+    /// Command that removes the program
+    pub uninstall_command: Option<String>,
+}
+
+impl InstalledProgram {
+    /// The executable to start the program with: its icon file, unless that
+    /// isn't an .exe or is the uninstaller itself
+    pub fn executable(&self) -> Option<&str> {
+        let (path, _) = self.icon.as_ref()?;
+        let file = path.rsplit('\\').next()?.to_lowercase();
+        let stem = file.strip_suffix(".exe")?;
+        let uninstaller = stem.starts_with("unins") || stem.contains("uninstall");
+        (!uninstaller).then_some(path.as_str())
+    }
+
+    /// How closely this program claims `target`: `Some` if it is the program's
+    /// executable or lies in its install folder, higher for a better match.
+    /// An install folder that is a drive root or one of `folders` claims
+    /// nothing, since every program's files would lie in it.
+    pub fn claims(&self, target: &str, folders: &KnownFolders) -> Option<usize> {
+        if self
+            .executable()
+            .is_some_and(|exe| exe.eq_ignore_ascii_case(target))
+        {
+            return Some(usize::MAX);
+        }
+        let location = self
+            .install_location
+            .as_deref()
+            .filter(|location| !is_shared_folder(location, folders))?;
+        let inside = target
+            .get(..location.len() + 1)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&format!("{}\\", location)));
+        inside.then_some(location.len())
+    }
+
+    /// An entry that starts the program and offers to uninstall it, if it has
+    /// an executable to start
+    pub fn to_entry(&self) -> Option<AppEntry> {
+        let executable = self.executable()?;
+        let (icon_path, icon_resource) = self.icon.clone()?;
+        let mut app = AppEntry::new(self.name.clone(), executable.to_string(), 0, 0);
+        app.icon_path = Some(icon_path);
+        app.icon_resource = icon_resource;
+        app.uninstall = self.uninstall_command.clone();
+        app.uninstall_name = Some(self.name.clone());
+        Some(app)
+    }
+}
+
+/// Whether `location` is a drive root or one of `folders`
+fn is_shared_folder(location: &str, folders: &KnownFolders) -> bool {
+    // Install locations have no trailing separator, so a drive root is just `C:`
+    !location.contains('\\')
+        || folders.roots().iter().any(|root| {
+            root.to_string_lossy()
+                .trim_end_matches('\\')
+                .eq_ignore_ascii_case(location)
+        })
+}
+
+/// The programs listed under [`UNINSTALL_KEYS`], leaving out system
+/// components and updates as Apps & Features does
+pub fn installed_programs(
+    registry: &impl RegistryReader,
+    env: &impl Environment,
+) -> Vec<InstalledProgram> {
+    let mut programs = Vec::new();
+    for root in UNINSTALL_KEYS {
+        for subkey in registry.subkeys(root) {
+            let key = format!("{}\\{}", root, subkey);
+            if let Some(program) = read_program(registry, env, key) {
+                programs.push(program);
+            }
+        }
+    }
+    programs
+}
+
+fn read_program(
+    registry: &impl RegistryReader,
+    env: &impl Environment,
+    key: String,
+) -> Option<InstalledProgram> {
+    let name = registry.string(&key, "DisplayName")?;
+    let hidden = registry.dword(&key, "SystemComponent") == Some(1)
+        || registry.string(&key, "ParentKeyName").is_some()
+        || registry
+            .string(&key, "ReleaseType")
+            .is_some_and(|kind| UPDATE_RELEASE_TYPES.contains(&kind.as_str()));
+    if hidden {
+        return None;
+    }
+
+    let icon = registry
+        .string(&key, "DisplayIcon")
+        .map(|value| parse_icon_location(&value))
+        .map(|(path, index)| (env_vars::expand_path(&path, env), index))
+        .filter(|(path, _)| !path.is_empty());
+    let install_location = registry
+        .string(&key, "InstallLocation")
+        .map(|path| env_vars::expand_path(path.trim_matches('"'), env))
+        .map(|path| path.trim_end_matches('\\').to_string())
+        .filter(|path| !path.is_empty());
+    let uninstall_command = registry
+        .string(&key, "UninstallString")
+        .map(|command| env_vars::expand(&command, env));
+
+    Some(InstalledProgram {
+        key,
+        name: name.trim().to_string(),
+        icon,
+        install_location,
+        uninstall_command,
+    })
+}
+
+/// Split a `DisplayIcon` value such as `"C:\app.exe",0` into the file and the
+/// icon index
+fn parse_icon_location(value: &str) -> (String, i32) {
+    let value = value.trim();
+    let (path, index) = match value.rsplit_once(',') {
+        Some((path, index)) => match index.trim().parse() {
+            Ok(index) => (path, index),
+            Err(_) => (value, 0),
+        },
+        None => (value, 0),
+    };
+    (path.trim().trim_matches('"').to_string(), index)
+}
+
+/// Split a command line into the program and its arguments.
+///
+/// Uninstall strings are often unquoted even when the path has spaces, so an
+/// unquoted command is cut after the first `.exe` rather than the first space.
+pub fn split_command(command: &str) -> (String, Option<String>) {
+    let command = command.trim();
+    let (program, rest) = if let Some(quoted) = command.strip_prefix('"') {
+        match quoted.split_once('"') {
+            Some((program, rest)) => (program, rest),
+            None => (quoted, ""),
+        }
+    } else {
+        let lower = command.to_lowercase();
+        let end = lower
+            .match_indices(".exe")
+            .map(|(i, _)| i + 4)
+            .find(|&end| command[end..].is_empty() || command[end..].starts_with(' '))
+            .or_else(|| command.find(' '))
+            .unwrap_or(command.len());
+        command.split_at(end)
+    };
+    let rest = rest.trim();
+    (
+        program.to_string(),
+        (!rest.is_empty()).then(|| rest.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::known_folders::FakeEnvironment;
+    use crate::registry::RegFile;
+    use std::path::Path;
+
+    fn fixture() -> RegFile {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/registry/uninstall.reg");
+        RegFile::from_bytes(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn programs() -> Vec<InstalledProgram> {
+        let env = FakeEnvironment::new(&[("ProgramFiles(x86)", "C:\\Program Files (x86)")]);
+        installed_programs(&fixture(), &env)
+    }
+
+    fn program(name: &str) -> InstalledProgram {
+        programs()
+            .into_iter()
+            .find(|program| program.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_system_components_and_updates_are_hidden() {
+        let names = programs()
+            .into_iter()
+            .map(|program| program.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "7-Zip 23.01 (x64)",
+                "Notepad++ (64-bit x64)",
+                "Contoso Printer Driver",
+                "Old Tool",
+                "Microsoft Visual Studio Code (User)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_program_fields() {
+        assert_eq!(
+            program("7-Zip 23.01 (x64)"),
+            InstalledProgram {
+                key: "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\7-Zip"
+                    .to_string(),
+                name: "7-Zip 23.01 (x64)".to_string(),
+                icon: Some(("C:\\Program Files\\7-Zip\\7zFM.exe".to_string(), 0)),
+                install_location: Some("C:\\Program Files\\7-Zip".to_string()),
+                uninstall_command: Some("\"C:\\Program Files\\7-Zip\\Uninstall.exe\"".to_string()),
+            }
+        );
+
+        let notepad = program("Notepad++ (64-bit x64)");
+        assert_eq!(
+            notepad.icon,
+            Some(("C:\\Program Files\\Notepad++\\notepad++.exe".to_string(), 0))
+        );
+        assert_eq!(notepad.install_location, None);
+
+        // REG_EXPAND_SZ values are expanded
+        let old = program("Old Tool");
+        assert_eq!(
+            old.icon,
+            Some((
+                "C:\\Program Files (x86)\\Old Tool\\unins000.exe".to_string(),
+                0
+            ))
+        );
+    }
+
+    #[test]
+    fn test_entries_need_an_executable() {
+        let code = program("Microsoft Visual Studio Code (User)")
+            .to_entry()
+            .unwrap();
+        assert_eq!(code.name, "Microsoft Visual Studio Code (User)");
+        assert_eq!(
+            code.uninstall_name.as_deref(),
+            Some("Microsoft Visual Studio Code (User)")
+        );
+        assert_eq!(
+            code.parse_name,
+            "C:\\Users\\me\\AppData\\Local\\Programs\\Microsoft VS Code\\Code.exe"
+        );
+        assert_eq!(
+            code.uninstall.as_deref(),
+            Some("\"C:\\Users\\me\\AppData\\Local\\Programs\\Microsoft VS Code\\unins000.exe\"")
+        );
+
+        // The only icon is the uninstaller's, or there is none
+        assert_eq!(program("Old Tool").to_entry(), None);
+        assert_eq!(program("Contoso Printer Driver").to_entry(), None);
+    }
+
+    fn folders() -> KnownFolders {
+        KnownFolders::resolve(&FakeEnvironment::new(&[
+            ("ProgramW6432", "C:\\Program Files"),
+            ("ProgramFiles(x86)", "C:\\Program Files (x86)"),
+            ("LOCALAPPDATA", "C:\\Users\\me\\AppData\\Local"),
+        ]))
+    }
+
+    #[test]
+    fn test_claims() {
+        let folders = folders();
+        let zip = program("7-Zip 23.01 (x64)");
+        assert_eq!(
+            zip.claims("c:\\program files\\7-zip\\7zFM.exe", &folders),
+            Some(usize::MAX)
+        );
+        assert_eq!(
+            zip.claims("C:\\Program Files\\7-Zip\\7z.exe", &folders),
+            Some(22)
+        );
+        assert_eq!(
+            zip.claims("C:\\Program Files\\7-Zip Extra\\x.exe", &folders),
+            None
+        );
+        assert_eq!(zip.claims("C:\\Program Files\\7-Zip", &folders), None);
+
+        let notepad = program("Notepad++ (64-bit x64)");
+        assert!(notepad
+            .claims("C:\\Program Files\\Notepad++\\notepad++.exe", &folders)
+            .is_some());
+        assert_eq!(
+            notepad.claims("C:\\Program Files\\Notepad++\\x.exe", &folders),
+            None
+        );
+    }
+
+    #[test]
+    fn test_root_install_locations_claim_nothing() {
+        let folders = folders();
+        let mut program = program("7-Zip 23.01 (x64)");
+        for location in ["C:", "C:\\Program Files", "c:\\users\\me\\appdata\\local"] {
+            program.install_location = Some(location.to_string());
+            assert_eq!(
+                program.claims("C:\\Program Files\\Other\\x.exe", &folders),
+                None
+            );
+            assert_eq!(
+                program.claims("C:\\Users\\me\\AppData\\Local\\x.exe", &folders),
+                None
+            );
+        }
+        // Its own executable is still its own
+        assert!(program
+            .claims("C:\\Program Files\\7-Zip\\7zFM.exe", &folders)
+            .is_some());
+    }
+
+    #[test]
+    fn test_parse_icon_location() {
+        assert_eq!(
+            parse_icon_location("\"C:\\A B\\app.exe\",-101"),
+            ("C:\\A B\\app.exe".to_string(), -101)
+        );
+        assert_eq!(
+            parse_icon_location("C:\\app.exe"),
+            ("C:\\app.exe".to_string(), 0)
+        );
+        assert_eq!(
+            parse_icon_location("C:\\a,b\\app.ico"),
+            ("C:\\a,b\\app.ico".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command("\"C:\\Program Files\\Foo\\unins000.exe\" /SILENT"),
+            (
+                "C:\\Program Files\\Foo\\unins000.exe".to_string(),
+                Some("/SILENT".to_string())
+            )
+        );
+        assert_eq!(
+            split_command("MsiExec.exe /X{A1B2C3D4-0000-4000-8000-000000000001}"),
+            (
+                "MsiExec.exe".to_string(),
+                Some("/X{A1B2C3D4-0000-4000-8000-000000000001}".to_string())
+            )
+        );
+        assert_eq!(
+            split_command("C:\\Program Files\\Foo\\uninst.exe /S"),
+            (
+                "C:\\Program Files\\Foo\\uninst.exe".to_string(),
+                Some("/S".to_string())
+            )
+        );
+        assert_eq!(
+            split_command("C:\\Tools\\remove"),
+            ("C:\\Tools\\remove".to_string(), None)
+        );
+    }
+}
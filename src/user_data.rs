@@ -0,0 +1,186 @@
+use crate::db::LaunchEvent;
+use serde::{Deserialize, Serialize};
+
+/// Value of the `format` field identifying an Oxistart export
+pub const FORMAT_NAME: &str = "oxistart-user-data";
+
+/// Version of the export format written by this build. Version 2 added the
+/// origin of each launch event.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Everything Oxistart has learned from the user, as written by `oxistart export`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserData {
+    pub format: String,
+    pub version: u32,
+    /// When the export was written (seconds since the Unix epoch)
+    pub exported_at: i64,
+    #[serde(default)]
+    pub usage: Vec<UsageCount>,
+    #[serde(default)]
+    pub launch_events: Vec<ExportedEvent>,
+    /// Pinned entries, in display order
+    #[serde(default)]
+    pub pins: Vec<String>,
+    #[serde(default)]
+    pub aliases: Vec<Alias>,
+    #[serde(default)]
+    pub hidden: Vec<String>,
+}
+
+/// A launch event with the machine it happened on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedEvent {
+    #[serde(flatten)]
+    pub event: LaunchEvent,
+    /// Id of the machine the launch was made on, as used by sync; missing
+    /// in version 1 exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Position of the launch in that machine's history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin_seq: Option<i64>,
+}
+
+impl From<LaunchEvent> for ExportedEvent {
+    /// An event of unknown origin, as in version 1 exports
+    fn from(event: LaunchEvent) -> Self {
+        Self {
+            event,
+            origin: None,
+            origin_seq: None,
+        }
+    }
+}
+
+/// Usage count of a single entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageCount {
+    pub entry_id: String,
+    pub count: i32,
+}
+
+/// A user-defined short name for an entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alias {
+    pub alias: String,
+    pub entry_id: String,
+}
+
+/// How imported data is combined with what is already in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Sum usage counts and take the union of every list
+    Merge,
+    /// Throw away local data and use the imported data as-is
+    Replace,
+}
+
+impl ImportMode {
+    /// Parse the value of `--mode`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "merge" => Some(ImportMode::Merge),
+            "replace" => Some(ImportMode::Replace),
+            _ => None,
+        }
+    }
+}
+
+impl UserData {
+    /// Create an empty export stamped with `exported_at`
+    pub fn new(exported_at: i64) -> Self {
+        Self {
+            format: FORMAT_NAME.to_string(),
+            version: FORMAT_VERSION,
+            exported_at,
+            usage: Vec::new(),
+            launch_events: Vec::new(),
+            pins: Vec::new(),
+            aliases: Vec::new(),
+            hidden: Vec::new(),
+        }
+    }
+
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("user data is always serializable")
+    }
+
+    /// Parse an export, rejecting other formats and newer versions
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let data: UserData =
+            serde_json::from_str(text).map_err(|e| format!("Invalid export file: {}", e))?;
+
+        if data.format != FORMAT_NAME {
+            return Err(format!("Not an Oxistart export (format '{}')", data.format));
+        }
+        if data.version > FORMAT_VERSION {
+            return Err(format!(
+                "Export version {} is newer than this Oxistart supports ({})",
+                data.version, FORMAT_VERSION
+            ));
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::LaunchAction;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut data = UserData::new(1_700_000_000);
+        data.usage.push(UsageCount {
+            entry_id: "C:\\app.exe".to_string(),
+            count: 4,
+        });
+        data.launch_events.push(ExportedEvent {
+            event: LaunchEvent {
+                timestamp: 1_700_000_000,
+                entry_id: "C:\\app.exe".to_string(),
+                action: LaunchAction::Location,
+                query: Some("ap".to_string()),
+                rank: Some(1),
+            },
+            origin: Some("0123456789abcdef".to_string()),
+            origin_seq: Some(7),
+        });
+        data.pins.push("C:\\app.exe".to_string());
+
+        let json = data.to_json();
+        assert!(json.contains("\"action\": \"location\""));
+        assert!(json.contains("\"origin_seq\": 7"));
+        assert_eq!(UserData::from_json(&json), Ok(data));
+    }
+
+    #[test]
+    fn test_missing_lists_default_to_empty() {
+        let data = UserData::from_json(
+            r#"{"format": "oxistart-user-data", "version": 1, "exported_at": 0}"#,
+        )
+        .unwrap();
+        assert!(data.usage.is_empty());
+        assert!(data.hidden.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_other_formats_and_newer_versions() {
+        assert!(UserData::from_json(r#"{"format": "x", "version": 1, "exported_at": 0}"#).is_err());
+        assert!(UserData::from_json(
+            r#"{"format": "oxistart-user-data", "version": 99, "exported_at": 0}"#
+        )
+        .is_err());
+        assert!(UserData::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_import_mode_parse() {
+        assert_eq!(ImportMode::parse("merge"), Some(ImportMode::Merge));
+        assert_eq!(ImportMode::parse("replace"), Some(ImportMode::Replace));
+        assert_eq!(ImportMode::parse("other"), None);
+    }
+}
@@ -1,7 +1,18 @@
+use crate::debug_log::write_debug_log;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::ffi::c_void;
+use std::sync::Mutex;
 use windows::{
-    core::*, Win32::Foundation::*, Win32::Graphics::Gdi::*, Win32::Storage::FileSystem::*,
-    Win32::System::LibraryLoader::*, Win32::System::Registry::*, Win32::UI::Shell::*,
+    core::*,
+    Win32::Foundation::*,
+    Win32::Graphics::Gdi::*,
+    Win32::Storage::FileSystem::*,
+    Win32::System::LibraryLoader::*,
+    Win32::System::Registry::*,
+    Win32::UI::Controls::*,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::{DestroyIcon, HICON},
 };
 
 /// Check if Windows is in dark mode
@@ -47,6 +58,13 @@ pub unsafe fn set_dark_mode_preference(dark_mode: bool) {
     }
 }
 
+/// Attach to the console of the process that started us, so command output is visible
+/// even though Oxistart is a GUI-subsystem executable
+pub unsafe fn attach_parent_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+}
+
 /// Create a solid brush with the specified color
 pub unsafe fn create_brush(color: u32) -> HBRUSH {
     CreateSolidBrush(COLORREF(color))
@@ -105,6 +123,95 @@ pub unsafe fn get_file_info_path(path: &str) -> Option<SHFILEINFOW> {
     }
 }
 
+/// Get system image list for icons (large icons for better visibility)
+pub unsafe fn get_system_image_list() -> isize {
+    let mut shfi = SHFILEINFOW::default();
+    SHGetFileInfoW(
+        w!(""),
+        FILE_FLAGS_AND_ATTRIBUTES(FILE_ATTRIBUTE_NORMAL.0),
+        Some(&mut shfi),
+        std::mem::size_of::<SHFILEINFOW>() as u32,
+        SHGFI_SYSICONINDEX | SHGFI_LARGEICON | SHGFI_USEFILEATTRIBUTES, // Changed to LARGEICON
+    ) as isize
+}
+
+/// Get system image list for small icons
+pub unsafe fn get_system_image_list_small() -> isize {
+    let mut shfi = SHFILEINFOW::default();
+    SHGetFileInfoW(
+        w!(""),
+        FILE_FLAGS_AND_ATTRIBUTES(FILE_ATTRIBUTE_NORMAL.0),
+        Some(&mut shfi),
+        std::mem::size_of::<SHFILEINFOW>() as u32,
+        SHGFI_SYSICONINDEX | SHGFI_SMALLICON | SHGFI_USEFILEATTRIBUTES,
+    ) as isize
+}
+
+/// An icon file, lowercased, and the icon's index in it
+type IconLocation = (String, i32);
+
+/// Where icons extracted by [`get_icon_location_index`] went in the system
+/// image list, or `None` for ones that couldn't be extracted
+static EXTRACTED_ICONS: Lazy<Mutex<HashMap<IconLocation, Option<i32>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Index in the system image list of icon `index` in the file `path`, as a
+/// shortcut or `DisplayIcon` names it (a negative index is a resource id).
+/// The icon is extracted and added to the image list the first time, which is
+/// slow enough that scans do it rather than the UI thread.
+pub unsafe fn get_icon_location_index(path: &str, index: i32) -> Option<i32> {
+    let key = (path.to_lowercase(), index);
+    // Held while extracting, so scan workers add each icon once and the two
+    // lists grow in step
+    let mut extracted = EXTRACTED_ICONS.lock().unwrap();
+    if let Some(known) = extracted.get(&key) {
+        return *known;
+    }
+    let added = add_icon_location(path, index);
+    extracted.insert(key, added);
+    added
+}
+
+unsafe fn add_icon_location(path: &str, index: i32) -> Option<i32> {
+    let path_wide = to_wide_string(path);
+    let mut large = HICON::default();
+    let mut small = HICON::default();
+    ExtractIconExW(
+        PCWSTR(path_wide.as_ptr()),
+        index,
+        Some(&mut large),
+        Some(&mut small),
+        1,
+    );
+    if large.is_invalid() {
+        if !small.is_invalid() {
+            let _ = DestroyIcon(small);
+        }
+        return None;
+    }
+
+    // Add to both sizes of the list; the list view shows the large list's
+    // index, so it is only usable if the small list put the icon at the same one
+    let added = ImageList_ReplaceIcon(HIMAGELIST(get_system_image_list()), -1, large);
+    let added_small = ImageList_ReplaceIcon(
+        HIMAGELIST(get_system_image_list_small()),
+        -1,
+        if small.is_invalid() { large } else { small },
+    );
+    let _ = DestroyIcon(large);
+    if !small.is_invalid() {
+        let _ = DestroyIcon(small);
+    }
+    if added != added_small {
+        write_debug_log(&format!(
+            "Icon {},{} went to index {} in the large image list but {} in the small one",
+            path, index, added, added_small
+        ));
+        return None;
+    }
+    (added >= 0).then_some(added)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,464 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Resource type of version resources (`RT_VERSION`)
+const RT_VERSION: u32 = 16;
+/// Signature of `VS_FIXEDFILEINFO`
+const FIXED_INFO_SIGNATURE: u32 = 0xFEEF04BD;
+/// Version resources are a few KB; anything past this is not one
+const MAX_VERSION_INFO_BYTES: u32 = 64 * 1024;
+/// More sections or directory entries than any real image has
+const MAX_ENTRIES: usize = 4096;
+/// `VS_VERSIONINFO` nests four levels deep; deeper blocks are not one
+const MAX_BLOCK_DEPTH: usize = 8;
+/// English (United States), the string table used when the translation list
+/// doesn't name one that exists
+const ENGLISH: &str = "0409";
+
+/// What an executable's version resource (`VS_VERSIONINFO`) says about it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VersionInfo {
+    pub file_description: Option<String>,
+    pub product_name: Option<String>,
+    pub company_name: Option<String>,
+    /// The `FileVersion` string, or the fixed file version when there is none
+    pub file_version: Option<String>,
+}
+
+impl VersionInfo {
+    /// A name to show for the program: its description, or its product name
+    pub fn display_name(&self) -> Option<&str> {
+        self.file_description
+            .as_deref()
+            .or(self.product_name.as_deref())
+    }
+
+    /// Description, product and company, leaving out repeats
+    pub fn keywords(&self) -> Vec<&str> {
+        let mut keywords: Vec<&str> = Vec::new();
+        for value in [
+            &self.file_description,
+            &self.product_name,
+            &self.company_name,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !keywords.iter().any(|k| k.eq_ignore_ascii_case(value)) {
+                keywords.push(value);
+            }
+        }
+        keywords
+    }
+}
+
+/// Read the version resource of the PE file at `path`; `Ok(None)` if it has none
+pub fn read(path: &Path) -> Result<Option<VersionInfo>, String> {
+    let mut file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    from_reader(&mut file)
+}
+
+/// Read the version resource of a PE image, seeking to the few parts needed
+/// rather than loading the whole file
+pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Option<VersionInfo>, String> {
+    let mut image = Image::open(reader)?;
+    let Some(resources) = image.resource_rva else {
+        return Ok(None);
+    };
+
+    // Resources are a three-level tree: type, then name, then language.
+    // Any name and language will do; executables have one version resource.
+    let Some(names) = image.find_entry(resources, 0, Some(RT_VERSION))? else {
+        return Ok(None);
+    };
+    let names = subdirectory(names)?;
+    let Some(languages) = image.find_entry(resources, names, None)? else {
+        return Ok(None);
+    };
+    let languages = subdirectory(languages)?;
+    let Some(data_entry) = image.find_entry(resources, languages, None)? else {
+        return Ok(None);
+    };
+    if data_entry & 0x8000_0000 != 0 {
+        return Err("Version resource is a directory".to_string());
+    }
+
+    let entry_rva = resources
+        .checked_add(data_entry)
+        .ok_or("Resource data entry is out of range")?;
+    let entry = image.read_rva(entry_rva, 8)?;
+    let (data_rva, size) = (u32_at(&entry, 0)?, u32_at(&entry, 4)?);
+    if size > MAX_VERSION_INFO_BYTES {
+        return Err(format!("Version resource of {} bytes", size));
+    }
+    let data = image.read_rva(data_rva, size as usize)?;
+    parse_version_info(&data).map(Some)
+}
+
+/// Directory offset an entry's `OffsetToData` points at
+fn subdirectory(offset: u32) -> Result<u32, String> {
+    if offset & 0x8000_0000 == 0 {
+        return Err("Expected a resource directory".to_string());
+    }
+    Ok(offset & 0x7FFF_FFFF)
+}
+
+/// A section's place in memory and in the file
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+    raw_size: u32,
+}
+
+/// A PE file, read through its section table
+struct Image<'a, R> {
+    reader: &'a mut R,
+    sections: Vec<Section>,
+    /// Address of the resource directory, if the image has one
+    resource_rva: Option<u32>,
+}
+
+impl<'a, R: Read + Seek> Image<'a, R> {
+    fn open(reader: &'a mut R) -> Result<Self, String> {
+        let dos = read_at(reader, 0, 64)?;
+        if &dos[..2] != b"MZ" {
+            return Err("Not an executable (no MZ header)".to_string());
+        }
+        let pe_offset = u32_at(&dos, 0x3C)? as u64;
+
+        let coff = read_at(reader, pe_offset, 24)?;
+        if &coff[..4] != b"PE\0\0" {
+            return Err("Not a PE file".to_string());
+        }
+        let section_count = u16_at(&coff, 6)? as usize;
+        let optional_size = u16_at(&coff, 20)? as usize;
+        if section_count > MAX_ENTRIES {
+            return Err(format!("{} sections", section_count));
+        }
+
+        let optional = read_at(reader, pe_offset + 24, optional_size)?;
+        // Where NumberOfRvaAndSizes and the data directories are
+        let (count_offset, directories) = match u16_at(&optional, 0)? {
+            0x10B => (92, 96),
+            0x20B => (108, 112),
+            magic => return Err(format!("Unknown optional header magic {:#x}", magic)),
+        };
+        let directory_count = u32_at(&optional, count_offset)?;
+        let resource_rva = if directory_count > 2 {
+            Some(u32_at(&optional, directories + 16)?).filter(|&rva| rva != 0)
+        } else {
+            None
+        };
+
+        let table = read_at(
+            reader,
+            pe_offset + 24 + optional_size as u64,
+            40 * section_count,
+        )?;
+        let sections = table
+            .chunks_exact(40)
+            .map(|header| {
+                Ok(Section {
+                    virtual_size: u32_at(header, 8)?,
+                    virtual_address: u32_at(header, 12)?,
+                    raw_size: u32_at(header, 16)?,
+                    raw_offset: u32_at(header, 20)?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            reader,
+            sections,
+            resource_rva,
+        })
+    }
+
+    /// Read `len` bytes at the relative virtual address `rva`
+    fn read_rva(&mut self, rva: u32, len: usize) -> Result<Vec<u8>, String> {
+        let section = self
+            .sections
+            .iter()
+            .find(|s| {
+                rva >= s.virtual_address && rva - s.virtual_address < s.virtual_size.max(s.raw_size)
+            })
+            .ok_or_else(|| format!("Address {:#x} is in no section", rva))?;
+        let offset = rva - section.virtual_address;
+        if offset as u64 + len as u64 > section.raw_size as u64 {
+            return Err(format!("Address {:#x} is past the section's data", rva));
+        }
+        read_at(self.reader, section.raw_offset as u64 + offset as u64, len)
+    }
+
+    /// `OffsetToData` of the entry with `id` in the resource directory at
+    /// `directory` (relative to `resources`), or of its first entry for `None`
+    fn find_entry(
+        &mut self,
+        resources: u32,
+        directory: u32,
+        id: Option<u32>,
+    ) -> Result<Option<u32>, String> {
+        let header_rva = resources
+            .checked_add(directory)
+            .ok_or("Resource directory is out of range")?;
+        let entries_rva = header_rva
+            .checked_add(16)
+            .ok_or("Resource directory is out of range")?;
+        let header = self.read_rva(header_rva, 16)?;
+        let count = u16_at(&header, 12)? as usize + u16_at(&header, 14)? as usize;
+        if count > MAX_ENTRIES {
+            return Err(format!("{} resource entries", count));
+        }
+        let entries = self.read_rva(entries_rva, 8 * count)?;
+        for entry in entries.chunks_exact(8) {
+            let name = u32_at(entry, 0)?;
+            // Named entries have the high bit set and never match an ID
+            if id.is_none_or(|id| name == id) {
+                return Ok(Some(u32_at(entry, 4)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A `VS_VERSIONINFO` node: a key, a value and child nodes
+struct Block<'a> {
+    key: String,
+    /// Text values are UTF-16; anything else is left as bytes
+    text: bool,
+    value: &'a [u8],
+    children: Vec<Block<'a>>,
+}
+
+impl Block<'_> {
+    fn child(&self, key: &str) -> Option<&Block<'_>> {
+        self.children.iter().find(|child| child.key == key)
+    }
+
+    /// The value as text, up to its terminating NUL
+    fn text_value(&self) -> String {
+        let units = self
+            .value
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    }
+}
+
+/// Parse the block starting at `start` of `data`, `depth` blocks inside the
+/// root. Every block begins on a 32-bit boundary from the start of the
+/// resource, as do its key's padding, its value and its children.
+fn parse_block(data: &[u8], start: usize, depth: usize) -> Result<Block<'_>, String> {
+    if depth > MAX_BLOCK_DEPTH {
+        return Err(format!("Version block nested {} deep at {}", depth, start));
+    }
+    let length = u16_at(data, start)? as usize;
+    let end = start + length;
+    if length < 6 || end > data.len() {
+        return Err(format!("Version block of {} bytes at {}", length, start));
+    }
+    let value_length = u16_at(data, start + 2)? as usize;
+    let text = u16_at(data, start + 4)? == 1;
+
+    let mut units = Vec::new();
+    let mut pos = start + 6;
+    loop {
+        let unit = u16_at(&data[..end], pos)?;
+        pos += 2;
+        if unit == 0 {
+            break;
+        }
+        units.push(unit);
+    }
+    let key = String::from_utf16_lossy(&units);
+
+    // Text lengths count UTF-16 units, though some compilers write bytes;
+    // the block's end bounds both
+    pos = align(pos).min(end);
+    let value_bytes = if text { value_length * 2 } else { value_length };
+    let value_end = (pos + value_bytes).min(end);
+    let value = &data[pos..value_end];
+
+    let mut children = Vec::new();
+    pos = align(value_end);
+    while pos + 6 <= end {
+        let child = parse_block(&data[..end], pos, depth + 1)?;
+        pos = align(pos + u16_at(data, pos)? as usize);
+        children.push(child);
+    }
+
+    Ok(Block {
+        key,
+        text,
+        value,
+        children,
+    })
+}
+
+fn align(pos: usize) -> usize {
+    (pos + 3) & !3
+}
+
+/// Pick the strings out of a `VS_VERSIONINFO` resource
+fn parse_version_info(data: &[u8]) -> Result<VersionInfo, String> {
+    let root = parse_block(data, 0, 0)?;
+    if root.key != "VS_VERSION_INFO" {
+        return Err(format!("Unexpected version resource key {:?}", root.key));
+    }
+
+    // The string table for the first listed translation, else English, else any
+    let translation = root
+        .child("VarFileInfo")
+        .and_then(|info| info.child("Translation"))
+        .filter(|translation| !translation.text && translation.value.len() >= 4)
+        .map(|translation| {
+            let value = translation.value;
+            format!(
+                "{:04x}{:04x}",
+                u16::from_le_bytes([value[0], value[1]]),
+                u16::from_le_bytes([value[2], value[3]])
+            )
+        });
+    let tables = root
+        .child("StringFileInfo")
+        .map(|info| info.children.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let table = tables
+        .iter()
+        .find(|table| Some(table.key.to_lowercase()) == translation)
+        .or_else(|| tables.iter().find(|table| table.key.starts_with(ENGLISH)))
+        .or(tables.first());
+
+    let string = |name: &str| {
+        table
+            .and_then(|table| table.child(name))
+            .map(|string| string.text_value().trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    Ok(VersionInfo {
+        file_description: string("FileDescription"),
+        product_name: string("ProductName"),
+        company_name: string("CompanyName"),
+        file_version: string("FileVersion").or_else(|| fixed_file_version(root.value)),
+    })
+}
+
+/// `a.b.c.d` from a `VS_FIXEDFILEINFO`
+fn fixed_file_version(value: &[u8]) -> Option<String> {
+    if u32_at(value, 0).ok()? != FIXED_INFO_SIGNATURE {
+        return None;
+    }
+    let (high, low) = (u32_at(value, 8).ok()?, u32_at(value, 12).ok()?);
+    Some(format!(
+        "{}.{}.{}.{}",
+        high >> 16,
+        high & 0xFFFF,
+        low >> 16,
+        low & 0xFFFF
+    ))
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![0; len];
+    reader
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| reader.read_exact(&mut buffer))
+        .map_err(|_| format!("Truncated file: no {} bytes at {}", len, offset))?;
+    Ok(buffer)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| format!("Unexpected end of data at {}", offset))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("Unexpected end of data at {}", offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/pe")
+            .join(name);
+        std::fs::read(path).unwrap()
+    }
+
+    fn version_of(bytes: Vec<u8>) -> Result<Option<VersionInfo>, String> {
+        from_reader(&mut Cursor::new(bytes))
+    }
+
+    #[test]
+    fn test_read_version_strings() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pe/pwsh.exe");
+        let info = read(&path).unwrap().unwrap();
+        assert_eq!(
+            info,
+            VersionInfo {
+                file_description: Some("PowerShell 7".to_string()),
+                product_name: Some("PowerShell".to_string()),
+                company_name: Some("Microsoft Corporation".to_string()),
+                file_version: Some("7.4.1.500".to_string()),
+            }
+        );
+        assert_eq!(info.display_name(), Some("PowerShell 7"));
+        assert_eq!(
+            info.keywords(),
+            vec!["PowerShell 7", "PowerShell", "Microsoft Corporation"]
+        );
+    }
+
+    #[test]
+    fn test_translation_picks_the_string_table() {
+        // 32-bit image whose resources list a manifest first, with a German
+        // table before an English one and a blank description
+        let info = version_of(fixture("toolbox.exe")).unwrap().unwrap();
+        assert_eq!(info.file_description, None);
+        assert_eq!(info.product_name.as_deref(), Some("Werkzeugkasten Pro"));
+        assert_eq!(info.company_name.as_deref(), Some("Beispiel GmbH"));
+        assert_eq!(info.file_version.as_deref(), Some("2.1.0.7"));
+        assert_eq!(info.display_name(), Some("Werkzeugkasten Pro"));
+    }
+
+    #[test]
+    fn test_images_without_version_resource() {
+        assert_eq!(version_of(fixture("noversion.exe")), Ok(None));
+    }
+
+    #[test]
+    fn test_malformed_files_are_errors() {
+        assert!(version_of(b"not an executable".to_vec()).is_err());
+        assert!(version_of(b"#!/bin/sh\necho hi\n".repeat(8)).is_err());
+
+        let mut truncated = fixture("pwsh.exe");
+        truncated.truncate(0x480);
+        assert!(version_of(truncated).is_err());
+
+        // .rsrc moved to the top of the address space, with a language entry
+        // whose offset runs past it
+        let mut overflowing = fixture("pwsh.exe");
+        let mut patch = |offset: usize, value: u32| {
+            overflowing[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        // Resource data directory, .rsrc VirtualAddress, language OffsetToData
+        patch(0xD8, 0xF000_0000);
+        patch(0x17C, 0xF000_0000);
+        patch(0x444, 0x7FFF_FFF0);
+        assert!(version_of(overflowing).is_err());
+
+        // Thousands of blocks nested inside each other
+        let nested = version_of(fixture("nested.exe")).unwrap_err();
+        assert!(nested.contains("nested 9 deep"), "{}", nested);
+    }
+}
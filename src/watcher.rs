@@ -0,0 +1,324 @@
+use crate::debug_log::write_debug_log;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::Storage::FileSystem::*;
+
+/// How long the folders must stay quiet before a rescan starts
+pub const QUIET_PERIOD: Duration = Duration::from_millis(1500);
+
+/// Longest a rescan is held back while an installer keeps writing files
+pub const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Size of the buffer each `ReadDirectoryChangesW` call fills
+const NOTIFY_BUFFER_BYTES: usize = 64 * 1024;
+
+/// How often to look again for a root that doesn't exist yet, or that was
+/// deleted while watched
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Collects changed folders until they have been quiet for a while, so that an
+/// installer creating twenty shortcuts triggers one rescan instead of twenty
+pub struct Debouncer {
+    quiet_period: Duration,
+    max_delay: Duration,
+    pending: BTreeSet<PathBuf>,
+    first_change: Option<Instant>,
+    last_change: Option<Instant>,
+}
+
+impl Debouncer {
+    pub fn new(quiet_period: Duration, max_delay: Duration) -> Self {
+        Self {
+            quiet_period,
+            max_delay,
+            pending: BTreeSet::new(),
+            first_change: None,
+            last_change: None,
+        }
+    }
+
+    /// Note a change in `folder` at `now`
+    pub fn push(&mut self, folder: PathBuf, now: Instant) {
+        self.pending.insert(folder);
+        self.first_change.get_or_insert(now);
+        self.last_change = Some(now);
+    }
+
+    /// How long to wait at `now` before `take` can return something, if anything is pending
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        let quiet_at = self.last_change? + self.quiet_period;
+        let deadline = self.first_change? + self.max_delay;
+        Some(quiet_at.min(deadline).saturating_duration_since(now))
+    }
+
+    /// The changed folders, once they have been quiet long enough (or have
+    /// waited the maximum delay). Folders inside another pending folder are left
+    /// out since rescanning the outer one covers them.
+    pub fn take(&mut self, now: Instant) -> Option<Vec<PathBuf>> {
+        if !self.time_left(now)?.is_zero() {
+            return None;
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        self.first_change = None;
+        self.last_change = None;
+
+        let mut folders: Vec<PathBuf> = Vec::new();
+        // BTreeSet order puts a folder before everything inside it
+        for folder in pending {
+            if !folders.iter().any(|outer| folder.starts_with(outer)) {
+                folders.push(folder);
+            }
+        }
+        Some(folders)
+    }
+}
+
+/// Folder to rescan after `relative` (as reported by `ReadDirectoryChangesW`) changed under `root`
+fn affected_folder(root: &Path, relative: &str) -> PathBuf {
+    // The parent covers the file itself as well as a renamed or deleted folder
+    match Path::new(relative).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => root.join(parent),
+        _ => root.to_path_buf(),
+    }
+}
+
+/// Watch `roots` (including subfolders) and call `on_change` with the folders to
+/// rescan whenever shortcuts appear, change or disappear. Runs until the process exits.
+pub fn watch(roots: Vec<PathBuf>, on_change: impl Fn(Vec<PathBuf>) + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+
+    for root in roots {
+        let sender = sender.clone();
+        thread::spawn(move || unsafe { watch_root(&root, &sender) });
+    }
+    drop(sender);
+
+    thread::spawn(move || debounce(receiver, on_change));
+}
+
+/// Forward debounced batches of changed folders from `receiver` to `on_change`
+fn debounce(receiver: Receiver<PathBuf>, on_change: impl Fn(Vec<PathBuf>)) {
+    let mut debouncer = Debouncer::new(QUIET_PERIOD, MAX_DELAY);
+
+    loop {
+        let received = match debouncer.time_left(Instant::now()) {
+            Some(wait) => receiver.recv_timeout(wait),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(folder) => debouncer.push(folder, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => {}
+            // Every root watcher has given up
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if let Some(folders) = debouncer.take(Instant::now()) {
+            on_change(folders);
+        }
+    }
+}
+
+/// Watch `root`, sending each affected folder. A root that can't be opened
+/// (e.g. a per-user Start Menu folder not created yet) is tried again every
+/// `RETRY_INTERVAL`, and rescanned as a whole once it can be.
+unsafe fn watch_root(root: &Path, sender: &Sender<PathBuf>) {
+    use std::os::windows::ffi::OsStrExt;
+
+    let path_wide: Vec<u16> = root
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut missing = false;
+    loop {
+        let opened = CreateFileW(
+            PCWSTR(path_wide.as_ptr()),
+            FILE_LIST_DIRECTORY.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS,
+            HANDLE::default(),
+        );
+        match opened {
+            Ok(handle) => {
+                // Whatever appeared with the folder has not been scanned yet
+                if missing {
+                    write_debug_log(&format!("Watching {} now", root.display()));
+                    if sender.send(root.to_path_buf()).is_err() {
+                        let _ = CloseHandle(handle);
+                        return;
+                    }
+                }
+                let listening = read_changes(root, handle, sender);
+                let _ = CloseHandle(handle);
+                if !listening {
+                    return;
+                }
+                write_debug_log(&format!(
+                    "Stopped watching {}; retrying every {} s",
+                    root.display(),
+                    RETRY_INTERVAL.as_secs()
+                ));
+                missing = true;
+                // The folder was most likely deleted, taking its entries with it
+                if sender.send(root.to_path_buf()).is_err() {
+                    return;
+                }
+            }
+            Err(e) if !missing => {
+                write_debug_log(&format!(
+                    "Can't watch {}: {}; retrying every {} s",
+                    root.display(),
+                    e,
+                    RETRY_INTERVAL.as_secs()
+                ));
+                missing = true;
+            }
+            Err(_) => {}
+        }
+        thread::sleep(RETRY_INTERVAL);
+    }
+}
+
+/// Block on `ReadDirectoryChangesW` for `root`, open as `handle`, sending each
+/// affected folder. Returns `false` once nobody listens any more, `true` when
+/// the folder can't be watched any longer.
+unsafe fn read_changes(root: &Path, handle: HANDLE, sender: &Sender<PathBuf>) -> bool {
+    // u32 elements keep the buffer DWORD-aligned as the API requires
+    let mut buffer = vec![0u32; NOTIFY_BUFFER_BYTES / 4];
+    loop {
+        let mut bytes_returned = 0u32;
+        let result = ReadDirectoryChangesW(
+            handle,
+            buffer.as_mut_ptr() as *mut _,
+            NOTIFY_BUFFER_BYTES as u32,
+            true,
+            FILE_NOTIFY_CHANGE_FILE_NAME
+                | FILE_NOTIFY_CHANGE_DIR_NAME
+                | FILE_NOTIFY_CHANGE_SIZE
+                | FILE_NOTIFY_CHANGE_LAST_WRITE,
+            Some(&mut bytes_returned),
+            None,
+            None,
+        );
+        if result.is_err() {
+            return true;
+        }
+
+        // The buffer overflowed and the individual changes were lost
+        if bytes_returned == 0 {
+            if sender.send(root.to_path_buf()).is_err() {
+                return false;
+            }
+            continue;
+        }
+
+        let mut offset = 0usize;
+        loop {
+            let info =
+                &*((buffer.as_ptr() as *const u8).add(offset) as *const FILE_NOTIFY_INFORMATION);
+            let name = std::slice::from_raw_parts(
+                info.FileName.as_ptr(),
+                info.FileNameLength as usize / 2,
+            );
+            let relative = String::from_utf16_lossy(name);
+            if sender.send(affected_folder(root, &relative)).is_err() {
+                return false;
+            }
+
+            if info.NextEntryOffset == 0 {
+                break;
+            }
+            offset += info.NextEntryOffset as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET: Duration = Duration::from_millis(100);
+    const MAX: Duration = Duration::from_millis(1000);
+
+    fn ms(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_waits_for_quiet_period() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(QUIET, MAX);
+        assert_eq!(debouncer.time_left(start), None);
+
+        debouncer.push(PathBuf::from("Programs\\Vendor"), start);
+        assert_eq!(debouncer.take(ms(start, 50)), None);
+
+        // A new change restarts the quiet period
+        debouncer.push(PathBuf::from("Programs\\Other"), ms(start, 80));
+        assert_eq!(debouncer.take(ms(start, 150)), None);
+        assert_eq!(
+            debouncer.time_left(ms(start, 150)),
+            Some(QUIET - Duration::from_millis(70))
+        );
+
+        assert_eq!(
+            debouncer.take(ms(start, 180)),
+            Some(vec![
+                PathBuf::from("Programs\\Other"),
+                PathBuf::from("Programs\\Vendor")
+            ])
+        );
+        assert_eq!(debouncer.take(ms(start, 500)), None);
+    }
+
+    #[test]
+    fn test_max_delay_under_constant_changes() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(QUIET, MAX);
+
+        for step in 0..20 {
+            debouncer.push(PathBuf::from("Programs"), ms(start, step * 60));
+        }
+        assert!(debouncer.take(ms(start, 999)).is_none());
+        assert_eq!(
+            debouncer.take(ms(start, 1000)),
+            Some(vec![PathBuf::from("Programs")])
+        );
+    }
+
+    #[test]
+    fn test_nested_folders_collapse() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(QUIET, MAX);
+        debouncer.push(PathBuf::from("Root").join("Vendor").join("Tools"), start);
+        debouncer.push(PathBuf::from("Root").join("Vendor"), start);
+        debouncer.push(PathBuf::from("Root").join("Vendors"), start);
+
+        assert_eq!(
+            debouncer.take(ms(start, 100)),
+            Some(vec![
+                PathBuf::from("Root").join("Vendor"),
+                PathBuf::from("Root").join("Vendors")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_affected_folder() {
+        let root = Path::new("Programs");
+        assert_eq!(affected_folder(root, "App.lnk"), PathBuf::from("Programs"));
+        assert_eq!(
+            affected_folder(root, &format!("Vendor{}App.lnk", std::path::MAIN_SEPARATOR)),
+            root.join("Vendor")
+        );
+    }
+}
@@ -0,0 +1,255 @@
+/// An XML element with its attributes, child elements and text.
+///
+/// Names lose their namespace prefix (`uap:VisualElements` is `VisualElements`)
+/// and namespace declarations are dropped: package manifests mix several
+/// schema versions of the same elements, and callers only care about names.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// Text directly inside the element, entities decoded
+    pub text: String,
+}
+
+impl Element {
+    /// Value of the attribute `name`
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// First child element called `name`
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Child elements called `name`
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// Parse a document into its root element.
+///
+/// Covers what manifests use: elements, attributes, text, comments, CDATA,
+/// processing instructions and the predefined and numeric entities. A DTD is
+/// skipped rather than read.
+pub fn parse(xml: &str) -> Result<Element, String> {
+    let mut rest = xml.trim_start_matches('\u{feff}');
+    let mut open: Vec<Element> = Vec::new();
+    let mut root = None;
+
+    while let Some(start) = rest.find('<') {
+        if let Some(element) = open.last_mut() {
+            element.text.push_str(&decode(&rest[..start])?);
+        } else if !rest[..start].trim().is_empty() {
+            return Err("Text outside the root element".to_string());
+        }
+        rest = &rest[start..];
+
+        let skip = [("<!--", "-->"), ("<?", "?>"), ("<!DOCTYPE", ">")]
+            .into_iter()
+            .find(|(opener, _)| rest.starts_with(opener));
+        if let Some((opener, closer)) = skip {
+            let end = rest
+                .find(closer)
+                .ok_or_else(|| format!("Unterminated {}", opener))?;
+            rest = &rest[end + closer.len()..];
+            continue;
+        }
+
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").ok_or("Unterminated CDATA section")?;
+            if let Some(element) = open.last_mut() {
+                element.text.push_str(&cdata[..end]);
+            }
+            rest = &cdata[end + 3..];
+            continue;
+        }
+
+        let end = tag_end(rest).ok_or("Unterminated tag")?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = open.pop().ok_or("Closing tag without an opening tag")?;
+            if local_name(name.trim()) != element.name {
+                return Err(format!(
+                    "Expected </{}>, found </{}>",
+                    element.name,
+                    name.trim()
+                ));
+            }
+            close(element, &mut open, &mut root)?;
+            continue;
+        }
+
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let element = start_tag(tag)?;
+        if self_closing {
+            close(element, &mut open, &mut root)?;
+        } else {
+            open.push(element);
+        }
+    }
+
+    if let Some(element) = open.last() {
+        return Err(format!("<{}> is never closed", element.name));
+    }
+    if !rest.trim().is_empty() {
+        return Err("Text outside the root element".to_string());
+    }
+    root.ok_or_else(|| "No root element".to_string())
+}
+
+/// Attach a finished element to its parent, or make it the root
+fn close(element: Element, open: &mut [Element], root: &mut Option<Element>) -> Result<(), String> {
+    match open.last_mut() {
+        Some(parent) => parent.children.push(element),
+        None if root.is_none() => *root = Some(element),
+        None => return Err("More than one root element".to_string()),
+    }
+    Ok(())
+}
+
+/// Position of the `>` ending the tag at the start of `text`, ignoring any
+/// inside quoted attribute values
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the inside of a start tag: a name, then `name="value"` pairs
+fn start_tag(tag: &str) -> Result<Element, String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = &tag[..name_end];
+    if name.is_empty() {
+        return Err("Tag without a name".to_string());
+    }
+
+    let mut element = Element {
+        name: local_name(name).to_string(),
+        ..Default::default()
+    };
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let equals = rest
+            .find('=')
+            .ok_or_else(|| format!("Attribute without a value in <{}>", name))?;
+        let key = rest[..equals].trim();
+        let value = rest[equals + 1..].trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| format!("Unquoted value for {} in <{}>", key, name))?;
+        let value = &value[1..];
+        let end = value
+            .find(quote)
+            .ok_or_else(|| format!("Unterminated value for {} in <{}>", key, name))?;
+
+        if key != "xmlns" && !key.starts_with("xmlns:") {
+            element
+                .attributes
+                .push((local_name(key).to_string(), decode(&value[..end])?));
+        }
+        rest = value[end + 1..].trim_start();
+    }
+    Ok(element)
+}
+
+/// `name` without its namespace prefix
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Replace entity and character references in text or an attribute value
+fn decode(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or_else(|| format!("Unterminated entity in {:?}", text))?;
+        let entity = &rest[start + 1..start + end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        out.push(decoded.ok_or_else(|| format!("Unknown entity &{};", entity))?);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elements_attributes_and_text() {
+        let root = parse(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <!-- generated -->\n\
+             <Package xmlns=\"urn:a\" xmlns:uap=\"urn:b\">\n\
+               <uap:Item Id='one' uap:Size=\"2\"/>\n\
+               <Item Id=\"a &gt; b\">Tom &amp; Jerry&#33;</Item>\n\
+               <Note><![CDATA[<raw>]]></Note>\n\
+             </Package>",
+        )
+        .unwrap();
+
+        assert_eq!(root.name, "Package");
+        assert!(root.attributes.is_empty());
+        let items = root.children_named("Item").collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].attr("Id"), Some("one"));
+        assert_eq!(items[0].attr("Size"), Some("2"));
+        assert_eq!(items[1].attr("Id"), Some("a > b"));
+        assert_eq!(items[1].text, "Tom & Jerry!");
+        assert_eq!(root.child("Note").unwrap().text, "<raw>");
+    }
+
+    #[test]
+    fn test_quoted_angle_brackets() {
+        let root = parse("<a title=\"x > y\"><b/></a>").unwrap();
+        assert_eq!(root.attr("title"), Some("x > y"));
+        assert_eq!(root.children.len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_documents_are_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("<a><b></a>").is_err());
+        assert!(parse("<a>").is_err());
+        assert!(parse("<a/><b/>").is_err());
+        assert!(parse("<a x=1/>").is_err());
+        assert!(parse("<a>&bogus;</a>").is_err());
+    }
+}
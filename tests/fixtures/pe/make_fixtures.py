@@ -0,0 +1,196 @@
+"""Write the small PE files the version resource tests read.
+
+They have headers, section tables and resources but no code. Run from this
+folder: python3 make_fixtures.py
+"""
+import struct
+
+RT_VERSION = 16
+RT_MANIFEST = 24
+
+
+def utf16z(text):
+    return (text + "\0").encode("utf-16-le")
+
+
+def pad4(data):
+    return data + b"\0" * (-len(data) % 4)
+
+
+def block(key, value=b"", value_length=0, text=False, children=()):
+    """A VS_VERSIONINFO-style block; wLength leaves out trailing padding"""
+    body = pad4(struct.pack("<HHH", 0, value_length, int(text)) + utf16z(key))
+    body += value
+    if children:
+        body = pad4(body) + b"".join(pad4(child) for child in children[:-1]) + children[-1]
+    return struct.pack("<H", len(body)) + body[2:]
+
+
+def string(key, value):
+    return block(key, utf16z(value), len(value) + 1, text=True)
+
+
+def version_info(tables, translations, version):
+    major, minor, build, revision = version
+    fixed = struct.pack(
+        "<13I",
+        0xFEEF04BD, 0x00010000,
+        major << 16 | minor, build << 16 | revision,
+        major << 16 | minor, build << 16 | revision,
+        0x3F, 0, 0x40004, 1, 0, 0, 0,
+    )
+    string_tables = [
+        block(name, text=True, children=[string(k, v) for k, v in strings])
+        for name, strings in tables
+    ]
+    translation = b"".join(struct.pack("<HH", lang, cp) for lang, cp in translations)
+    return block(
+        "VS_VERSION_INFO",
+        fixed,
+        len(fixed),
+        children=[
+            block("StringFileInfo", text=True, children=string_tables),
+            block("VarFileInfo", text=True, children=[
+                block("Translation", translation, len(translation)),
+            ]),
+        ],
+    )
+
+
+def resources(rva, items):
+    """A .rsrc section at `rva` holding `items`: (type, id, language, data)"""
+    dirs = b""
+    leaves = []
+    # type directory, then one name and one language directory per item
+    count = len(items)
+    type_dir = struct.pack("<IIHHHH", 0, 0, 0, 0, 0, count)
+    header = 16 + 8 * count
+    name_dirs = header
+    lang_dirs = name_dirs + 24 * count
+    entries = lang_dirs + 24 * count
+    data_start = entries + 16 * count
+    for i, (kind, _, _, _) in enumerate(items):
+        type_dir += struct.pack("<II", kind, 0x80000000 | (name_dirs + 24 * i))
+    for i, (_, ident, _, _) in enumerate(items):
+        dirs += struct.pack("<IIHHHH", 0, 0, 0, 0, 0, 1)
+        dirs += struct.pack("<II", ident, 0x80000000 | (lang_dirs + 24 * i))
+    for i, (_, _, language, _) in enumerate(items):
+        dirs += struct.pack("<IIHHHH", 0, 0, 0, 0, 0, 1)
+        dirs += struct.pack("<II", language, entries + 16 * i)
+    data = b""
+    for _, _, _, payload in items:
+        leaves.append((data_start + len(data), len(payload)))
+        data += pad4(payload)
+    entry_bytes = b"".join(
+        struct.pack("<IIII", rva + offset, size, 0, 0) for offset, size in leaves
+    )
+    return type_dir + dirs + entry_bytes + data
+
+
+def pe(machine, sections, resource_section=None):
+    """A PE image with `sections` (name, contents); the one called
+    `resource_section` is the resource directory"""
+    pe32_plus = machine == 0x8664
+    optional_size = 240 if pe32_plus else 224
+    headers = 0x40 + 24 + optional_size + 40 * len(sections)
+    file_align, section_align = 0x200, 0x1000
+    headers_size = -(-headers // file_align) * file_align
+
+    table = b""
+    raw = b""
+    resource_dir = (0, 0)
+    for i, (name, contents) in enumerate(sections):
+        rva = section_align * (i + 1)
+        if callable(contents):
+            contents = contents(rva)
+        size = -(-len(contents) // file_align) * file_align
+        table += struct.pack(
+            "<8sIIIIIIHHI", name, len(contents), rva, size, headers_size + len(raw),
+            0, 0, 0, 0, 0x40000040,
+        )
+        if name == resource_section:
+            resource_dir = (rva, len(contents))
+        raw += contents + b"\0" * (size - len(contents))
+
+    directories = [(0, 0)] * 16
+    directories[2] = resource_dir
+    directory_bytes = b"".join(struct.pack("<II", *d) for d in directories)
+    image_size = section_align * (len(sections) + 1)
+    if pe32_plus:
+        optional = struct.pack("<HBBIIIII", 0x20B, 14, 0, 0, 0, 0, 0, 0)
+        optional += struct.pack("<QII", 0x140000000, section_align, file_align)
+        optional += struct.pack("<HHHHHHIII", 6, 0, 0, 0, 6, 0, 0, image_size, headers_size)
+        optional += struct.pack("<IHHQQQQII", 0, 3, 0x8160, 0x100000, 0x1000,
+                                0x100000, 0x1000, 0, 16)
+    else:
+        optional = struct.pack("<HBBIIIIII", 0x10B, 14, 0, 0, 0, 0, 0, 0, 0)
+        optional += struct.pack("<III", 0x400000, section_align, file_align)
+        optional += struct.pack("<HHHHHHIII", 6, 0, 0, 0, 6, 0, 0, image_size, headers_size)
+        optional += struct.pack("<IHHIIIIII", 0, 3, 0x8140, 0x100000, 0x1000,
+                                0x100000, 0x1000, 0, 16)
+    optional += directory_bytes
+    assert len(optional) == optional_size
+
+    dos = b"MZ" + b"\0" * 58 + struct.pack("<I", 0x40)
+    coff = b"PE\0\0" + struct.pack("<HHIIIHH", machine, len(sections), 0, 0, 0,
+                                   optional_size, 0x22)
+    head = dos + coff + optional + table
+    return head + b"\0" * (headers_size - len(head)) + raw
+
+
+def write(name, data):
+    with open(name, "wb") as f:
+        f.write(data)
+
+
+pwsh = version_info(
+    [("040904b0", [
+        ("CompanyName", "Microsoft Corporation"),
+        ("FileDescription", "PowerShell 7"),
+        ("FileVersion", "7.4.1.500"),
+        ("ProductName", "PowerShell"),
+    ])],
+    [(0x0409, 0x04B0)],
+    (7, 4, 1, 500),
+)
+write("pwsh.exe", pe(0x8664, [
+    (b".text", b"\xc3" * 16),
+    (b".rsrc", lambda rva: resources(rva, [(RT_VERSION, 1, 0x0409, pwsh)])),
+], b".rsrc"))
+
+# 32-bit, a manifest before the version resource, the English string table
+# second and no FileDescription or FileVersion string
+toolbox = version_info(
+    [
+        ("040704b0", [
+            ("CompanyName", "Beispiel GmbH"),
+            ("FileDescription", " "),
+            ("ProductName", "Werkzeugkasten Pro"),
+        ]),
+        ("040904b0", [
+            ("CompanyName", "Example Inc."),
+            ("ProductName", "Toolbox Pro"),
+        ]),
+    ],
+    [(0x0407, 0x04B0), (0x0409, 0x04B0)],
+    (2, 1, 0, 7),
+)
+write("toolbox.exe", pe(0x14C, [
+    (b".text", b"\xc3" * 16),
+    (b".data", b"\0" * 600),
+    (b".rsrc", lambda rva: resources(rva, [
+        (RT_MANIFEST, 1, 0x0409, b"<assembly/>"),
+        (RT_VERSION, 1, 0x0407, toolbox),
+    ])),
+], b".rsrc"))
+
+write("noversion.exe", pe(0x8664, [(b".text", b"\xc3" * 16)]))
+
+# A version resource of nothing but blocks nested inside each other, 8,000 deep
+nested = b""
+for _ in range(8000):
+    nested = block("", children=[nested] if nested else ())
+write("nested.exe", pe(0x8664, [
+    (b".text", b"\xc3" * 16),
+    (b".rsrc", lambda rva: resources(rva, [(RT_VERSION, 1, 0x0409, nested)])),
+], b".rsrc"))